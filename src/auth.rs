@@ -0,0 +1,576 @@
+use std::fmt;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use header_plz::{
+    HeaderMap, OneRequestLine, OneResponseLine,
+    const_headers::{
+        AUTHORIZATION, CONTENT_LENGTH, PROXY_AUTHENTICATE,
+        PROXY_AUTHORIZATION, WWW_AUTHENTICATE,
+    },
+    status::StatusCode,
+};
+use thiserror::Error;
+
+use crate::{
+    OneOne, Request, Response, Version,
+    utils::{
+        quoted::{is_quotable, quote, quoted_string, split_unquoted, unquote},
+        token::is_token,
+    },
+};
+
+// auth-params sent as token, everything else is a quoted-string
+const TOKEN_PARAMS: [&str; 2] = ["algorithm", "stale"];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum AuthError {
+    #[error("Invalid Credentials| {0}")]
+    InvalidCredentials(String),
+    #[error("Invalid Base64| {0}")]
+    InvalidBase64(String),
+    #[error("Invalid Challenge| {0}")]
+    InvalidChallenge(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthScheme {
+    Basic,
+    Bearer,
+    Digest,
+    Negotiate,
+    Other(String),
+}
+
+impl AuthScheme {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AuthScheme::Basic => "Basic",
+            AuthScheme::Bearer => "Bearer",
+            AuthScheme::Digest => "Digest",
+            AuthScheme::Negotiate => "Negotiate",
+            AuthScheme::Other(scheme) => scheme,
+        }
+    }
+}
+
+// auth-scheme is case-insensitive
+impl From<&str> for AuthScheme {
+    fn from(value: &str) -> Self {
+        [
+            AuthScheme::Basic,
+            AuthScheme::Bearer,
+            AuthScheme::Digest,
+            AuthScheme::Negotiate,
+        ]
+        .into_iter()
+        .find(|scheme| scheme.as_str().eq_ignore_ascii_case(value))
+        .unwrap_or_else(|| AuthScheme::Other(value.to_string()))
+    }
+}
+
+impl fmt::Display for AuthScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Authorization / Proxy-Authorization, RFC 9110 11.4
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credentials {
+    Basic {
+        username: String,
+        password: String,
+    },
+    Bearer(String),
+    // auth-params, names lowercased and values unquoted
+    Digest(Vec<(String, String)>),
+    Negotiate(String),
+    Other {
+        scheme: String,
+        value: String,
+    },
+}
+
+impl Credentials {
+    pub fn scheme(&self) -> AuthScheme {
+        match self {
+            Credentials::Basic {
+                ..
+            } => AuthScheme::Basic,
+            Credentials::Bearer(_) => AuthScheme::Bearer,
+            Credentials::Digest(_) => AuthScheme::Digest,
+            Credentials::Negotiate(_) => AuthScheme::Negotiate,
+            Credentials::Other {
+                scheme,
+                ..
+            } => AuthScheme::Other(scheme.clone()),
+        }
+    }
+
+    // Digest auth-param by name
+    pub fn param(&self, name: &str) -> Option<&str> {
+        match self {
+            Credentials::Digest(params) => params
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/* Description:
+ *      Parse an Authorization or Proxy-Authorization value.
+ *
+ *      credentials = auth-scheme [ 1*SP ( token68 / #auth-param ) ]
+ *
+ * Steps:
+ *      1. Split the scheme at the first whitespace.
+ *      2. Basic  => base64 decode, split user-id and password at the first
+ *                   ':'.
+ *         Bearer, Negotiate => token68, must be present.
+ *         Digest => comma separated auth-params.
+ *
+ * Error:
+ *      AuthError::InvalidCredentials   [1, 2]
+ *      AuthError::InvalidBase64        [2]
+ */
+
+pub fn parse_credentials(value: &[u8]) -> Result<Credentials, AuthError> {
+    let value = std::str::from_utf8(value.trim_ascii()).map_err(|_| {
+        AuthError::InvalidCredentials(String::from_utf8_lossy(value).into())
+    })?;
+    let invalid = || AuthError::InvalidCredentials(value.to_string());
+    let (scheme, rest) = match value.split_once(|c: char| c.is_whitespace()) {
+        Some((scheme, rest)) => (scheme, rest.trim()),
+        None => (value, ""),
+    };
+    if scheme.is_empty() {
+        return Err(invalid());
+    }
+
+    let credentials = match AuthScheme::from(scheme) {
+        AuthScheme::Basic => {
+            let decoded = STANDARD
+                .decode(rest)
+                .map_err(|_| AuthError::InvalidBase64(rest.to_string()))?;
+            let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+            let (username, password) =
+                decoded.split_once(':').ok_or_else(invalid)?;
+            Credentials::Basic {
+                username: username.to_string(),
+                password: password.to_string(),
+            }
+        }
+        AuthScheme::Bearer | AuthScheme::Negotiate if rest.is_empty() => {
+            return Err(invalid());
+        }
+        AuthScheme::Bearer => Credentials::Bearer(rest.to_string()),
+        AuthScheme::Negotiate => Credentials::Negotiate(rest.to_string()),
+        AuthScheme::Digest => Credentials::Digest(
+            split_unquoted(rest, b',')
+                .into_iter()
+                .filter_map(|param| param.split_once('='))
+                .map(|(name, value)| {
+                    (name.trim().to_ascii_lowercase(), unquote(value.trim()))
+                })
+                .collect(),
+        ),
+        AuthScheme::Other(scheme) => Credentials::Other {
+            scheme,
+            value: rest.to_string(),
+        },
+    };
+    Ok(credentials)
+}
+
+/* Description:
+ *      WWW-Authenticate / Proxy-Authenticate challenge.
+ *
+ *      Challenge::basic("proxy").with_param("charset", "UTF-8")
+ *
+ *      => Basic realm="proxy", charset="UTF-8"
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenge {
+    scheme: AuthScheme,
+    token68: Option<String>,
+    params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    pub fn new(scheme: AuthScheme) -> Self {
+        Challenge {
+            scheme,
+            token68: None,
+            params: Vec::new(),
+        }
+    }
+
+    pub fn basic(realm: &str) -> Self {
+        Self::new(AuthScheme::Basic).with_param("realm", realm)
+    }
+
+    pub fn bearer(realm: &str) -> Self {
+        Self::new(AuthScheme::Bearer).with_param("realm", realm)
+    }
+
+    pub fn digest(realm: &str, nonce: &str) -> Self {
+        Self::new(AuthScheme::Digest)
+            .with_param("realm", realm)
+            .with_param("nonce", nonce)
+    }
+
+    pub fn negotiate() -> Self {
+        Self::new(AuthScheme::Negotiate)
+    }
+
+    pub fn with_param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // replaces the params, a challenge carries one or the other
+    pub fn with_token68(mut self, token68: &str) -> Self {
+        self.params.clear();
+        self.token68 = Some(token68.to_string());
+        self
+    }
+
+    pub fn scheme(&self) -> &AuthScheme {
+        &self.scheme
+    }
+
+    /* Description:
+     *      Challenge as a WWW-Authenticate / Proxy-Authenticate value.
+     *
+     *      challenge = auth-scheme [ 1*SP ( token68 / #auth-param ) ]
+     *
+     * Error:
+     *      AuthError::InvalidChallenge
+     *          scheme or param name is not a token, token68 does not match
+     *          its grammar or a param value has a control character.
+     */
+
+    pub fn to_header_value(&self) -> Result<String, AuthError> {
+        let invalid = |value: &str| AuthError::InvalidChallenge(value.into());
+        let scheme = self.scheme.as_str();
+        if !is_token(scheme.as_bytes()) {
+            return Err(invalid(scheme));
+        }
+        if let Some(token68) = &self.token68 {
+            if !is_token68(token68) {
+                return Err(invalid(token68));
+            }
+            return Ok(format!("{scheme} {token68}"));
+        }
+        let mut params = Vec::with_capacity(self.params.len());
+        for (name, value) in self.params.iter() {
+            if !is_token(name.as_bytes()) {
+                return Err(invalid(name));
+            }
+            if !is_quotable(value) {
+                return Err(invalid(value));
+            }
+            if TOKEN_PARAMS.iter().any(|t| t.eq_ignore_ascii_case(name)) {
+                params.push(format!("{name}={}", quote(value)));
+            } else {
+                params.push(format!("{name}={}", quoted_string(value)));
+            }
+        }
+        if params.is_empty() {
+            Ok(scheme.to_string())
+        } else {
+            Ok(format!("{scheme} {}", params.join(", ")))
+        }
+    }
+}
+
+// token68 = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+fn is_token68(value: &str) -> bool {
+    let data = value.trim_end_matches('=');
+    !data.is_empty()
+        && data.bytes().all(|b| {
+            b.is_ascii_alphanumeric()
+                || matches!(b, b'-' | b'.' | b'_' | b'~' | b'+' | b'/')
+        })
+}
+
+impl OneOne<OneRequestLine> {
+    pub fn authorization(&self) -> Option<Result<Credentials, AuthError>> {
+        self.message_head
+            .header_map()
+            .value_of_key(AUTHORIZATION)
+            .map(parse_credentials)
+    }
+
+    pub fn proxy_authorization(
+        &self,
+    ) -> Option<Result<Credentials, AuthError>> {
+        self.message_head
+            .header_map()
+            .value_of_key(PROXY_AUTHORIZATION)
+            .map(parse_credentials)
+    }
+}
+
+impl Request {
+    pub fn authorization(&self) -> Option<Result<Credentials, AuthError>> {
+        self.headers.value_of_key(AUTHORIZATION).map(parse_credentials)
+    }
+
+    pub fn proxy_authorization(
+        &self,
+    ) -> Option<Result<Credentials, AuthError>> {
+        self.headers.value_of_key(PROXY_AUTHORIZATION).map(parse_credentials)
+    }
+}
+
+impl OneOne<OneResponseLine> {
+    pub fn add_www_authenticate(
+        &mut self,
+        challenge: &Challenge,
+    ) -> Result<(), AuthError> {
+        let value = challenge.to_header_value()?;
+        self.add_header(WWW_AUTHENTICATE, value.as_bytes());
+        Ok(())
+    }
+
+    pub fn add_proxy_authenticate(
+        &mut self,
+        challenge: &Challenge,
+    ) -> Result<(), AuthError> {
+        let value = challenge.to_header_value()?;
+        self.add_header(PROXY_AUTHENTICATE, value.as_bytes());
+        Ok(())
+    }
+
+    // 401 with one WWW-Authenticate header per challenge
+    pub fn unauthorized(challenges: &[Challenge]) -> Result<Self, AuthError> {
+        let mut headers = HeaderMap::new();
+        for challenge in challenges {
+            headers.insert(WWW_AUTHENTICATE, challenge.to_header_value()?);
+        }
+        headers.insert(CONTENT_LENGTH, "0");
+        let res = Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .headers(headers)
+            .build();
+        Ok(Self::from_two(res, Version::H11))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use crate::{OneRequest, OneResponse};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_credentials_basic() {
+        // RFC 7617 2
+        let verify = Credentials::Basic {
+            username: "Aladdin".to_string(),
+            password: "open sesame".to_string(),
+        };
+        assert_eq!(
+            parse_credentials(b"Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==").unwrap(),
+            verify
+        );
+        assert_eq!(
+            parse_credentials(b"basic  QWxhZGRpbjpvcGVuIHNlc2FtZQ==").unwrap(),
+            verify
+        );
+        assert_eq!(
+            parse_credentials(b"Basic YTpiOmM=").unwrap(),
+            Credentials::Basic {
+                username: "a".to_string(),
+                password: "b:c".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_credentials_basic_invalid() {
+        assert_eq!(
+            parse_credentials(b"Basic !!!"),
+            Err(AuthError::InvalidBase64("!!!".to_string()))
+        );
+        // "nocolon"
+        assert_eq!(
+            parse_credentials(b"Basic bm9jb2xvbg=="),
+            Err(AuthError::InvalidCredentials(
+                "Basic bm9jb2xvbg==".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_credentials(b""),
+            Err(AuthError::InvalidCredentials("".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_credentials_token68() {
+        assert_eq!(
+            parse_credentials(b"Bearer mF_9.B5f-4.1JqM").unwrap(),
+            Credentials::Bearer("mF_9.B5f-4.1JqM".to_string())
+        );
+        assert_eq!(
+            parse_credentials(b"Negotiate YIIBzgYGKwYBBQUCoIIBwjCC").unwrap(),
+            Credentials::Negotiate("YIIBzgYGKwYBBQUCoIIBwjCC".to_string())
+        );
+        assert_eq!(
+            parse_credentials(b"Bearer"),
+            Err(AuthError::InvalidCredentials("Bearer".to_string()))
+        );
+        let other = parse_credentials(b"HOBA result=abc").unwrap();
+        assert_eq!(other.scheme(), AuthScheme::Other("HOBA".to_string()));
+    }
+
+    #[test]
+    fn test_parse_credentials_digest() {
+        let input = b"Digest username=\"Mufasa\", realm=\"a, b\", \
+                      nonce=\"dcd98b\", uri=\"/dir/index.html\", qop=auth, \
+                      nc=00000001";
+        let credentials = parse_credentials(input).unwrap();
+        assert_eq!(credentials.scheme(), AuthScheme::Digest);
+        assert_eq!(credentials.param("username"), Some("Mufasa"));
+        assert_eq!(credentials.param("Realm"), Some("a, b"));
+        assert_eq!(credentials.param("qop"), Some("auth"));
+        assert_eq!(credentials.param("nc"), Some("00000001"));
+        assert_eq!(credentials.param("cnonce"), None);
+    }
+
+    #[test]
+    fn test_one_request_authorization() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Authorization: Bearer abc\r\n\
+                     Proxy-Authorization: Basic YTpi\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.authorization(),
+            Some(Ok(Credentials::Bearer("abc".to_string())))
+        );
+        assert_eq!(
+            req.proxy_authorization(),
+            Some(Ok(Credentials::Basic {
+                username: "a".to_string(),
+                password: "b".to_string(),
+            }))
+        );
+
+        let req =
+            OneRequest::try_from(BytesMut::from("GET / HTTP/1.1\r\n\r\n"))
+                .unwrap();
+        assert_eq!(req.proxy_authorization(), None);
+    }
+
+    #[test]
+    fn test_request_authorization() {
+        let mut headers = HeaderMap::new();
+        headers.insert("proxy-authorization", "Negotiate abc");
+        let req = Request::builder().headers(headers).build();
+        assert_eq!(
+            req.proxy_authorization(),
+            Some(Ok(Credentials::Negotiate("abc".to_string())))
+        );
+        assert_eq!(req.authorization(), None);
+    }
+
+    #[test]
+    fn test_challenge_to_header_value() {
+        assert_eq!(
+            Challenge::basic("proxy")
+                .with_param("charset", "UTF-8")
+                .to_header_value()
+                .unwrap(),
+            "Basic realm=\"proxy\", charset=\"UTF-8\""
+        );
+        assert_eq!(
+            Challenge::digest("a \"b\"", "n1")
+                .with_param("qop", "auth, auth-int")
+                .with_param("algorithm", "SHA-256")
+                .with_param("stale", "true")
+                .to_header_value()
+                .unwrap(),
+            "Digest realm=\"a \\\"b\\\"\", nonce=\"n1\", \
+             qop=\"auth, auth-int\", algorithm=SHA-256, stale=true"
+        );
+        assert_eq!(
+            Challenge::negotiate().to_header_value().unwrap(),
+            "Negotiate"
+        );
+        assert_eq!(
+            Challenge::negotiate()
+                .with_token68("abc=")
+                .to_header_value()
+                .unwrap(),
+            "Negotiate abc="
+        );
+    }
+
+    #[test]
+    fn test_challenge_to_header_value_injection() {
+        let realm = "x\r\nSet-Cookie: a=b";
+        assert_eq!(
+            Challenge::basic(realm).to_header_value(),
+            Err(AuthError::InvalidChallenge(realm.to_string()))
+        );
+        assert!(
+            Challenge::negotiate()
+                .with_token68("abc\r\nSet-Cookie: a=b")
+                .to_header_value()
+                .is_err()
+        );
+        assert!(
+            Challenge::negotiate()
+                .with_token68("a=b")
+                .to_header_value()
+                .is_err()
+        );
+        assert!(
+            Challenge::basic("proxy")
+                .with_param("a b", "c")
+                .to_header_value()
+                .is_err()
+        );
+        assert!(
+            Challenge::new(AuthScheme::Other("X\r\nY".to_string()))
+                .to_header_value()
+                .is_err()
+        );
+        assert!(
+            OneResponse::unauthorized(&[Challenge::bearer(realm)]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_response_challenges() {
+        let res = OneResponse::unauthorized(&[
+            Challenge::bearer("api"),
+            Challenge::basic("api"),
+        ])
+        .unwrap();
+        let verify = "HTTP/1.1 401 Unauthorized\r\n\
+                      www-authenticate: Bearer realm=\"api\"\r\n\
+                      www-authenticate: Basic realm=\"api\"\r\n\
+                      content-length: 0\r\n\r\n";
+        assert_eq!(res.into_bytes(), verify);
+
+        let mut res = OneResponse::bad_gateway(None);
+        res.add_proxy_authenticate(&Challenge::basic("proxy")).unwrap();
+        res.add_www_authenticate(&Challenge::negotiate()).unwrap();
+        let verify = "HTTP/1.1 502 Bad Gateway\r\n\
+                      content-length: 0\r\n\
+                      proxy-authenticate: Basic realm=\"proxy\"\r\n\
+                      www-authenticate: Negotiate\r\n\r\n";
+        assert_eq!(res.into_bytes(), verify);
+
+        let mut res = OneResponse::bad_gateway(None);
+        assert!(res.add_www_authenticate(&Challenge::basic("a\0b")).is_err());
+        assert!(!res.message_head().header_map().has_key(WWW_AUTHENTICATE));
+    }
+}
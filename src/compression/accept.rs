@@ -0,0 +1,472 @@
+use std::fmt;
+
+use header_plz::{
+    OneRequestLine,
+    body_headers::content_encoding::ContentEncoding,
+    const_headers::ACCEPT_ENCODING,
+    message_head::header_map::{HMap, Hmap},
+};
+
+use crate::{
+    OneOne, Request, compression::decode::is_decodable,
+    utils::token::header_tokens,
+};
+
+// content codings in order of preference
+const PREFERENCE: [ContentEncoding; 6] = [
+    ContentEncoding::Zstd,
+    ContentEncoding::Brotli,
+    ContentEncoding::Gzip,
+    ContentEncoding::Deflate,
+    ContentEncoding::Compress,
+    ContentEncoding::Identity,
+];
+
+const WILDCARD: &str = "*";
+const X_GZIP: &str = "x-gzip";
+const MAX_QUALITY: u16 = 1000;
+const IMPLICIT_IDENTITY_QUALITY: u16 = 1;
+
+/* One Accept-Encoding item, RFC 9110 12.5.3.
+ *
+ * Quality is the q-value in thousandths, 0 to 1000.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodingPreference {
+    coding: String,
+    quality: u16,
+}
+
+impl EncodingPreference {
+    pub fn new(coding: &str, quality: u16) -> Self {
+        EncodingPreference {
+            coding: coding.to_ascii_lowercase(),
+            quality: quality.min(MAX_QUALITY),
+        }
+    }
+
+    // lowercase, as sent
+    pub fn coding(&self) -> &str {
+        &self.coding
+    }
+
+    pub fn quality(&self) -> u16 {
+        self.quality
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        self.coding == WILDCARD
+    }
+
+    // None for "*", x-gzip is gzip
+    pub fn encoding(&self) -> Option<ContentEncoding> {
+        match self.coding.as_str() {
+            WILDCARD => None,
+            X_GZIP => Some(ContentEncoding::Gzip),
+            coding => Some(ContentEncoding::from(coding)),
+        }
+    }
+}
+
+// gzip;q=0.5, q is omitted when 1
+impl fmt::Display for EncodingPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.coding)?;
+        match self.quality {
+            MAX_QUALITY => Ok(()),
+            0 => write!(f, ";q=0"),
+            q => {
+                let frac = format!("{q:03}");
+                write!(f, ";q=0.{}", frac.trim_end_matches('0'))
+            }
+        }
+    }
+}
+
+// qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )
+fn parse_quality(value: &str) -> Option<u16> {
+    let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let frac = format!("{frac:0<3}").parse::<u16>().ok()?;
+    match int {
+        "0" => Some(frac),
+        "1" if frac == 0 => Some(MAX_QUALITY),
+        _ => None,
+    }
+}
+
+// coding [ ";" "q=" qvalue ], None if malformed
+fn parse_item(item: &[u8]) -> Option<EncodingPreference> {
+    let item = std::str::from_utf8(item).ok()?;
+    let mut parts = item.split(';').map(str::trim);
+    let coding = parts.next().filter(|c| !c.is_empty())?;
+    let mut quality = MAX_QUALITY;
+    for param in parts {
+        let (name, value) = param.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("q") {
+            quality = parse_quality(value.trim())?;
+        }
+    }
+    Some(EncodingPreference::new(coding, quality))
+}
+
+// Encodings decompression-plz can decode, in order of preference
+pub fn decodable_encodings() -> Vec<ContentEncoding> {
+    PREFERENCE.into_iter().filter(is_decodable).collect()
+}
+
+// malformed items are skipped
+pub fn parse_accept_encoding(value: &[u8]) -> Vec<EncodingPreference> {
+    value
+        .split(|b| *b == b',')
+        .map(<[u8]>::trim_ascii)
+        .filter(|item| !item.is_empty())
+        .filter_map(parse_item)
+        .collect()
+}
+
+/* Description:
+ *      Quality of encoding in preferences.
+ *
+ * Steps:
+ *      1. The first item naming encoding.
+ *      2. Else "*".
+ *      3. Else identity is acceptable with the lowest quality, a fallback
+ *         when nothing listed is supported. Any other coding is not.
+ */
+
+fn quality_of(
+    preferences: &[EncodingPreference],
+    encoding: &ContentEncoding,
+) -> u16 {
+    preferences
+        .iter()
+        .find(|p| p.encoding().as_ref() == Some(encoding))
+        .or_else(|| preferences.iter().find(|p| p.is_wildcard()))
+        .map_or_else(
+            || match encoding {
+                ContentEncoding::Identity => IMPLICIT_IDENTITY_QUALITY,
+                _ => 0,
+            },
+            EncodingPreference::quality,
+        )
+}
+
+/* Description:
+ *      Pick the encoding of supported with the highest non zero quality in
+ *      preferences. Ties are broken by the order of supported.
+ *
+ * Returns:
+ *      None if no coding in supported is acceptable, 406.
+ */
+
+pub fn best_encoding(
+    preferences: &[EncodingPreference],
+    supported: &[ContentEncoding],
+) -> Option<ContentEncoding> {
+    supported
+        .iter()
+        .map(|encoding| (quality_of(preferences, encoding), encoding))
+        .filter(|(quality, _)| *quality > 0)
+        .fold(None, |best, (quality, encoding)| match best {
+            Some((q, _)) if q >= quality => best,
+            _ => Some((quality, encoding)),
+        })
+        .map(|(_, encoding)| encoding.clone())
+}
+
+fn preferences<T>(header_map: &HMap<T>) -> Option<Vec<EncodingPreference>>
+where
+    T: Hmap,
+{
+    header_map.has_key(ACCEPT_ENCODING).then(|| {
+        header_tokens(header_map, ACCEPT_ENCODING)
+            .filter_map(parse_item)
+            .collect()
+    })
+}
+
+// a missing Accept-Encoding accepts any coding
+fn best_of<T>(
+    header_map: &HMap<T>,
+    supported: &[ContentEncoding],
+) -> Option<ContentEncoding>
+where
+    T: Hmap,
+{
+    let any = [EncodingPreference::new(WILDCARD, MAX_QUALITY)];
+    match preferences(header_map) {
+        Some(preferences) => best_encoding(&preferences, supported),
+        None => best_encoding(&any, supported),
+    }
+}
+
+// "*;q=0" or "identity;q=0", rejections the origin must still see
+fn is_rejection(preference: &EncodingPreference) -> bool {
+    preference.quality == 0
+        && (preference.is_wildcard()
+            || preference.encoding() == Some(ContentEncoding::Identity))
+}
+
+/* Steps:
+ *      1. For each Accept-Encoding header, keep the items naming a coding
+ *         in supported, and the "*;q=0" and "identity;q=0" rejections.
+ *         Any other "*" is dropped.
+ *      2. If nothing is left, set "identity", as a missing
+ *         Accept-Encoding means any coding is acceptable.
+ */
+
+pub(crate) fn restrict_accept_encoding<T>(
+    header_map: &mut HMap<T>,
+    supported: &[ContentEncoding],
+) where
+    T: Hmap,
+{
+    let updates: Vec<(usize, String)> = header_map
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| h.key_as_ref().eq_ignore_ascii_case(ACCEPT_ENCODING))
+        .map(|(pos, h)| {
+            let filtered: Vec<String> =
+                parse_accept_encoding(h.value_as_ref())
+                    .iter()
+                    .filter(|p| {
+                        p.encoding().is_some_and(|e| supported.contains(&e))
+                            || is_rejection(p)
+                    })
+                    .map(ToString::to_string)
+                    .collect();
+            let filtered = if filtered.is_empty() {
+                ContentEncoding::Identity.as_ref().to_string()
+            } else {
+                filtered.join(", ")
+            };
+            (pos, filtered)
+        })
+        .collect();
+    for (pos, value) in updates {
+        header_map.update_header_value_on_position(pos, value.as_str());
+    }
+}
+
+impl OneOne<OneRequestLine> {
+    // None if Accept-Encoding is missing
+    pub fn accept_encoding(&self) -> Option<Vec<EncodingPreference>> {
+        preferences(self.message_head.header_map())
+    }
+
+    pub fn best_encoding(
+        &self,
+        supported: &[ContentEncoding],
+    ) -> Option<ContentEncoding> {
+        best_of(self.message_head.header_map(), supported)
+    }
+
+    pub fn restrict_accept_encoding(&mut self, supported: &[ContentEncoding]) {
+        restrict_accept_encoding(
+            self.message_head.header_map_as_mut(),
+            supported,
+        );
+    }
+}
+
+impl Request {
+    // None if Accept-Encoding is missing
+    pub fn accept_encoding(&self) -> Option<Vec<EncodingPreference>> {
+        preferences(&self.headers)
+    }
+
+    pub fn best_encoding(
+        &self,
+        supported: &[ContentEncoding],
+    ) -> Option<ContentEncoding> {
+        best_of(&self.headers, supported)
+    }
+
+    pub fn restrict_accept_encoding(&mut self, supported: &[ContentEncoding]) {
+        restrict_accept_encoding(&mut self.headers, supported);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use header_plz::HeaderMap;
+
+    use crate::OneRequest;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_accept_encoding() {
+        let verify = vec![
+            EncodingPreference::new("gzip", 1000),
+            EncodingPreference::new("br", 800),
+            EncodingPreference::new("*", 0),
+            EncodingPreference::new("x-gzip", 1000),
+        ];
+        assert_eq!(
+            parse_accept_encoding(b"GZIP, br;q=0.8 ,, *;Q=0, x-gzip;q=1.000"),
+            verify
+        );
+    }
+
+    #[test]
+    fn test_parse_accept_encoding_malformed() {
+        let verify = vec![EncodingPreference::new("zstd", 5)];
+        assert_eq!(
+            parse_accept_encoding(
+                b"gzip;q=2, br;q=0.1234, deflate;q, zstd;q=0.005, ;q=1"
+            ),
+            verify
+        );
+    }
+
+    #[test]
+    fn test_encoding_preference_display() {
+        assert_eq!(EncodingPreference::new("gzip", 1000).to_string(), "gzip");
+        assert_eq!(EncodingPreference::new("br", 800).to_string(), "br;q=0.8");
+        assert_eq!(
+            EncodingPreference::new("zstd", 25).to_string(),
+            "zstd;q=0.025"
+        );
+        assert_eq!(EncodingPreference::new("*", 0).to_string(), "*;q=0");
+    }
+
+    #[test]
+    fn test_decodable_encodings() {
+        let verify = vec![
+            ContentEncoding::Zstd,
+            ContentEncoding::Brotli,
+            ContentEncoding::Gzip,
+            ContentEncoding::Deflate,
+            ContentEncoding::Identity,
+        ];
+        assert_eq!(decodable_encodings(), verify);
+    }
+
+    #[test]
+    fn test_best_encoding() {
+        let supported = decodable_encodings();
+        let preferences = parse_accept_encoding(b"gzip;q=0.5, br;q=0.8");
+        assert_eq!(
+            best_encoding(&preferences, &supported),
+            Some(ContentEncoding::Brotli)
+        );
+
+        // ties follow supported
+        let preferences = parse_accept_encoding(b"gzip, deflate, zstd");
+        assert_eq!(
+            best_encoding(&preferences, &supported),
+            Some(ContentEncoding::Zstd)
+        );
+
+        // wildcard
+        let preferences = parse_accept_encoding(b"zstd;q=0, *;q=0.3");
+        assert_eq!(
+            best_encoding(&preferences, &supported),
+            Some(ContentEncoding::Brotli)
+        );
+
+        // x-gzip
+        let preferences = parse_accept_encoding(b"x-gzip");
+        assert_eq!(
+            best_encoding(&preferences, &[ContentEncoding::Gzip]),
+            Some(ContentEncoding::Gzip)
+        );
+    }
+
+    #[test]
+    fn test_best_encoding_identity() {
+        let supported = decodable_encodings();
+        // empty value, only identity
+        assert_eq!(
+            best_encoding(&[], &supported),
+            Some(ContentEncoding::Identity)
+        );
+        let preferences = parse_accept_encoding(b"sdch");
+        assert_eq!(
+            best_encoding(&preferences, &supported),
+            Some(ContentEncoding::Identity)
+        );
+        // 406
+        let preferences = parse_accept_encoding(b"sdch, identity;q=0");
+        assert_eq!(best_encoding(&preferences, &supported), None);
+        let preferences = parse_accept_encoding(b"*;q=0");
+        assert_eq!(best_encoding(&preferences, &supported), None);
+    }
+
+    #[test]
+    fn test_one_accept_encoding() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Accept-Encoding: gzip;q=0.2\r\n\
+                     Accept-Encoding: br\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let verify = vec![
+            EncodingPreference::new("gzip", 200),
+            EncodingPreference::new("br", 1000),
+        ];
+        assert_eq!(req.accept_encoding().unwrap(), verify);
+        assert_eq!(
+            req.best_encoding(&decodable_encodings()),
+            Some(ContentEncoding::Brotli)
+        );
+
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.accept_encoding().is_none());
+        assert_eq!(
+            req.best_encoding(&decodable_encodings()),
+            Some(ContentEncoding::Zstd)
+        );
+    }
+
+    #[test]
+    fn test_one_restrict_accept_encoding() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Accept-Encoding: gzip;q=1.0, sdch;q=0.5, br;q=0.80, *\r\n\
+                     Host: example.com\r\n\
+                     Accept-Encoding: compress\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.restrict_accept_encoding(&[
+            ContentEncoding::Gzip,
+            ContentEncoding::Brotli,
+        ]);
+        let verify = "GET / HTTP/1.1\r\n\
+                      Accept-Encoding: gzip, br;q=0.8\r\n\
+                      Host: example.com\r\n\
+                      Accept-Encoding: identity\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_one_restrict_accept_encoding_rejections() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Accept-Encoding: sdch, gzip, *;q=0\r\n\
+                     Accept-Encoding: br;q=0.5, identity;q=0, *\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.restrict_accept_encoding(&decodable_encodings());
+        let verify = "GET / HTTP/1.1\r\n\
+                      Accept-Encoding: gzip, *;q=0\r\n\
+                      Accept-Encoding: br;q=0.5, identity;q=0\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_request_accept_encoding() {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-encoding", "deflate, zstd;q=0.9, sdch");
+        let mut req = Request::builder().headers(headers).build();
+        assert_eq!(
+            req.best_encoding(&decodable_encodings()),
+            Some(ContentEncoding::Deflate)
+        );
+        req.restrict_accept_encoding(&decodable_encodings());
+        assert_eq!(
+            req.headers().value_of_key(ACCEPT_ENCODING).unwrap(),
+            b"deflate, zstd;q=0.9"
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use std::io::{self, Write};
+
+use body_plz::variants::chunked::ChunkType;
+use bytes::BytesMut;
+use flate2::write::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use header_plz::{
+    body_headers::content_encoding::ContentEncoding,
+    const_headers::{CONTENT_ENCODING, TRANSFER_ENCODING},
+    message_head::header_map::{HMap, Hmap},
+};
+
+use crate::utils::token::header_tokens;
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const LIMIT_EXCEEDED: &str = "decoded size exceeds limit";
+
+// Output of a decoder chain, total output is bounded by limit
+#[derive(Debug)]
+pub(crate) struct Sink {
+    buf: BytesMut,
+    written: usize,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl Sink {
+    pub(crate) fn new(limit: usize) -> Self {
+        Sink {
+            buf: BytesMut::new(),
+            written: 0,
+            limit,
+            exceeded: false,
+        }
+    }
+
+    pub(crate) fn is_exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    // output written since the last take
+    pub(crate) fn take(&mut self) -> BytesMut {
+        self.buf.split()
+    }
+}
+
+// keeps the bytes that fit, errors on the rest
+impl Write for Sink {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let remaining = self.limit.saturating_sub(self.written);
+        if data.len() > remaining {
+            self.buf.extend_from_slice(&data[..remaining]);
+            self.written += remaining;
+            self.exceeded = true;
+            return Err(io::Error::other(LIMIT_EXCEEDED));
+        }
+        self.buf.extend_from_slice(data);
+        self.written += data.len();
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/* Push based decoder, encoded input is written to it and the decoded
+ * output ends up in the Sink at the end of the chain.
+ */
+pub(crate) trait DecodeWrite: Write {
+    fn sink(&mut self) -> &mut Sink;
+
+    // end of input, flush the remaining output
+    fn try_finish(&mut self) -> io::Result<()>;
+}
+
+impl DecodeWrite for Sink {
+    fn sink(&mut self) -> &mut Sink {
+        self
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+type Inner = Box<dyn DecodeWrite>;
+
+enum Stage {
+    Gzip(MultiGzDecoder<Inner>),
+    // zlib or raw deflate, decided on the first byte
+    Deflate(Option<Inner>),
+    Zlib(ZlibDecoder<Inner>),
+    RawDeflate(DeflateDecoder<Inner>),
+    Brotli(Box<brotli::DecompressorWriter<Inner>>),
+    Zstd(zstd::stream::write::Decoder<'static, Inner>),
+}
+
+// RFC 1950 2.2, CM = 8 and CINFO <= 7
+#[inline]
+pub(crate) fn is_zlib_header(b: u8) -> bool {
+    b & 0x0f == 8 && b >> 4 <= 7
+}
+
+impl Stage {
+    fn inner(&mut self) -> &mut Inner {
+        match self {
+            Stage::Gzip(d) => d.get_mut(),
+            Stage::Deflate(inner) => inner.as_mut().unwrap(),
+            Stage::Zlib(d) => d.get_mut(),
+            Stage::RawDeflate(d) => d.get_mut(),
+            Stage::Brotli(d) => d.get_mut(),
+            Stage::Zstd(d) => d.get_mut(),
+        }
+    }
+}
+
+impl Write for Stage {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let Stage::Deflate(inner) = self {
+            let Some(first) = data.first() else {
+                return Ok(0);
+            };
+            let inner = inner.take().unwrap();
+            *self = if is_zlib_header(*first) {
+                Stage::Zlib(ZlibDecoder::new(inner))
+            } else {
+                Stage::RawDeflate(DeflateDecoder::new(inner))
+            };
+        }
+        match self {
+            Stage::Gzip(d) => d.write(data),
+            Stage::Deflate(_) => unreachable!(),
+            Stage::Zlib(d) => d.write(data),
+            Stage::RawDeflate(d) => d.write(data),
+            Stage::Brotli(d) => d.write(data),
+            Stage::Zstd(d) => d.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stage::Gzip(d) => d.flush(),
+            Stage::Deflate(inner) => inner.as_mut().unwrap().flush(),
+            Stage::Zlib(d) => d.flush(),
+            Stage::RawDeflate(d) => d.flush(),
+            Stage::Brotli(d) => d.flush(),
+            Stage::Zstd(d) => d.flush(),
+        }
+    }
+}
+
+impl DecodeWrite for Stage {
+    fn sink(&mut self) -> &mut Sink {
+        self.inner().sink()
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        match self {
+            Stage::Gzip(d) => d.try_finish()?,
+            Stage::Deflate(_) => (),
+            Stage::Zlib(d) => d.try_finish()?,
+            Stage::RawDeflate(d) => d.try_finish()?,
+            Stage::Brotli(d) => d.close()?,
+            Stage::Zstd(d) => d.flush()?,
+        }
+        self.inner().try_finish()
+    }
+}
+
+// chunk data without the trailing CRLF
+pub(crate) fn chunk_data(chunk: &ChunkType) -> Option<&[u8]> {
+    match chunk {
+        ChunkType::Chunk(data) => Some(&data[..data.len().saturating_sub(2)]),
+        _ => None,
+    }
+}
+
+// Codings listed in every header with the given key, without identity
+pub(crate) fn header_encodings<T>(
+    header_map: &HMap<T>,
+    key: &[u8],
+) -> Vec<ContentEncoding>
+where
+    T: Hmap,
+{
+    header_tokens(header_map, key)
+        .map(|token| {
+            let token = String::from_utf8_lossy(token).to_ascii_lowercase();
+            ContentEncoding::from(token.as_str())
+        })
+        .filter(|e| *e != ContentEncoding::Identity)
+        .collect()
+}
+
+// Content-Encoding then Transfer-Encoding chain, without chunked
+pub(crate) fn body_encodings<T>(header_map: &HMap<T>) -> Vec<ContentEncoding>
+where
+    T: Hmap,
+{
+    let mut encodings = header_encodings(header_map, CONTENT_ENCODING);
+    encodings.extend(
+        header_encodings(header_map, TRANSFER_ENCODING)
+            .into_iter()
+            .filter(|e| *e != ContentEncoding::Chunked),
+    );
+    encodings
+}
+
+/* Description:
+ *      Build a decoder for encodings, listed in the order they were
+ *      applied. The last applied coding receives the input first.
+ *
+ *      identity is skipped.
+ *
+ * Returns:
+ *      Err(encoding) for the first coding that cannot be decoded.
+ */
+
+pub(crate) fn decoder_chain(
+    encodings: &[ContentEncoding],
+    limit: usize,
+) -> Result<Box<dyn DecodeWrite>, ContentEncoding> {
+    let mut chain: Inner = Box::new(Sink::new(limit));
+    for encoding in encodings {
+        let stage = match encoding {
+            ContentEncoding::Identity => continue,
+            ContentEncoding::Gzip => Stage::Gzip(MultiGzDecoder::new(chain)),
+            ContentEncoding::Deflate => Stage::Deflate(Some(chain)),
+            ContentEncoding::Brotli => Stage::Brotli(Box::new(
+                brotli::DecompressorWriter::new(chain, BROTLI_BUFFER_SIZE),
+            )),
+            ContentEncoding::Zstd => Stage::Zstd(
+                zstd::stream::write::Decoder::new(chain)
+                    .map_err(|_| encoding.clone())?,
+            ),
+            ContentEncoding::Chunked
+            | ContentEncoding::Compress
+            | ContentEncoding::Unknown(_) => return Err(encoding.clone()),
+        };
+        chain = Box::new(stage);
+    }
+    Ok(chain)
+}
+
+// the decoders are the ones decompression-plz uses
+pub(crate) fn is_decodable(encoding: &ContentEncoding) -> bool {
+    decoder_chain(std::slice::from_ref(encoding), 0).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::encode::compress;
+
+    use super::*;
+
+    const INPUT: &[u8] = b"hello world hello world hello world";
+
+    fn decode(encoded: &[u8], encodings: &[ContentEncoding]) -> BytesMut {
+        let mut chain = decoder_chain(encodings, usize::MAX).unwrap();
+        chain.write_all(encoded).unwrap();
+        chain.try_finish().unwrap();
+        chain.sink().take()
+    }
+
+    #[test]
+    fn test_decoder_chain_single() {
+        for encoding in [
+            ContentEncoding::Gzip,
+            ContentEncoding::Deflate,
+            ContentEncoding::Brotli,
+            ContentEncoding::Zstd,
+            ContentEncoding::Identity,
+        ] {
+            let encoded = compress(INPUT, &encoding).unwrap();
+            assert_eq!(decode(&encoded, &[encoding]), INPUT);
+        }
+    }
+
+    #[test]
+    fn test_decoder_chain_raw_deflate() {
+        let mut encoder = flate2::write::DeflateEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(INPUT).unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(decode(&encoded, &[ContentEncoding::Deflate]), INPUT);
+    }
+
+    #[test]
+    fn test_decoder_chain_multiple() {
+        let gzip = compress(INPUT, &ContentEncoding::Gzip).unwrap();
+        let encoded = compress(&gzip, &ContentEncoding::Zstd).unwrap();
+        assert_eq!(
+            decode(&encoded, &[ContentEncoding::Gzip, ContentEncoding::Zstd]),
+            INPUT
+        );
+    }
+
+    #[test]
+    fn test_decoder_chain_limit() {
+        let encoded = compress(INPUT, &ContentEncoding::Gzip).unwrap();
+        let mut chain = decoder_chain(&[ContentEncoding::Gzip], 10).unwrap();
+        let result =
+            chain.write_all(&encoded).and_then(|_| chain.try_finish());
+        assert!(result.is_err());
+        assert!(chain.sink().is_exceeded());
+        assert_eq!(chain.sink().take(), INPUT[..10]);
+    }
+
+    #[test]
+    fn test_decoder_chain_unsupported() {
+        assert_eq!(
+            decoder_chain(&[ContentEncoding::Compress], 10).err(),
+            Some(ContentEncoding::Compress)
+        );
+    }
+}
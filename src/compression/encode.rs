@@ -0,0 +1,123 @@
+use std::io::Write;
+
+use bytes::BytesMut;
+use flate2::{
+    Compression,
+    write::{GzEncoder, ZlibEncoder},
+};
+use header_plz::body_headers::content_encoding::ContentEncoding;
+
+use super::error::CompressError;
+
+const BROTLI_QUALITY: i32 = 5;
+const BROTLI_LGWIN: i32 = 22;
+
+/* Description:
+ *      Encode data with a single content-coding.
+ *
+ *      deflate is the zlib format, RFC 9110 8.4.1.2.
+ *
+ * Error:
+ *      CompressError::UnsupportedEncoding  chunked, compress and unknown
+ *      CompressError::Encode               encoder failure
+ */
+
+pub fn compress(
+    data: &[u8],
+    encoding: &ContentEncoding,
+) -> Result<BytesMut, CompressError> {
+    let to_error = |e: std::io::Error| {
+        CompressError::Encode(encoding.as_ref().to_string(), e.to_string())
+    };
+    let encoded = match encoding {
+        ContentEncoding::Identity => data.to_vec(),
+        ContentEncoding::Gzip => {
+            let mut encoder =
+                GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).map_err(to_error)?;
+            encoder.finish().map_err(to_error)?
+        }
+        ContentEncoding::Deflate => {
+            let mut encoder =
+                ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).map_err(to_error)?;
+            encoder.finish().map_err(to_error)?
+        }
+        ContentEncoding::Brotli => {
+            let params = brotli::enc::BrotliEncoderParams {
+                quality: BROTLI_QUALITY,
+                lgwin: BROTLI_LGWIN,
+                ..Default::default()
+            };
+            let mut encoded = Vec::new();
+            brotli::BrotliCompress(&mut &data[..], &mut encoded, &params)
+                .map_err(to_error)?;
+            encoded
+        }
+        ContentEncoding::Zstd => {
+            zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL)
+                .map_err(to_error)?
+        }
+        ContentEncoding::Chunked
+        | ContentEncoding::Compress
+        | ContentEncoding::Unknown(_) => {
+            return Err(CompressError::UnsupportedEncoding(
+                encoding.as_ref().to_string(),
+            ));
+        }
+    };
+    Ok(BytesMut::from(&encoded[..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::{GzDecoder, ZlibDecoder};
+
+    use super::*;
+
+    const INPUT: &[u8] = b"hello world hello world hello world";
+
+    #[test]
+    fn test_compress_gzip() {
+        let encoded = compress(INPUT, &ContentEncoding::Gzip).unwrap();
+        let mut decoded = Vec::new();
+        GzDecoder::new(&encoded[..]).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, INPUT);
+    }
+
+    #[test]
+    fn test_compress_deflate() {
+        let encoded = compress(INPUT, &ContentEncoding::Deflate).unwrap();
+        let mut decoded = Vec::new();
+        ZlibDecoder::new(&encoded[..]).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, INPUT);
+    }
+
+    #[test]
+    fn test_compress_brotli() {
+        let encoded = compress(INPUT, &ContentEncoding::Brotli).unwrap();
+        let mut decoded = Vec::new();
+        brotli::BrotliDecompress(&mut &encoded[..], &mut decoded).unwrap();
+        assert_eq!(decoded, INPUT);
+    }
+
+    #[test]
+    fn test_compress_zstd() {
+        let encoded = compress(INPUT, &ContentEncoding::Zstd).unwrap();
+        assert_eq!(zstd::decode_all(&encoded[..]).unwrap(), INPUT);
+    }
+
+    #[test]
+    fn test_compress_unsupported() {
+        assert_eq!(
+            compress(INPUT, &ContentEncoding::Compress),
+            Err(CompressError::UnsupportedEncoding("compress".to_string()))
+        );
+        assert_eq!(
+            compress(INPUT, &ContentEncoding::Identity).unwrap(),
+            INPUT
+        );
+    }
+}
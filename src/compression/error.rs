@@ -0,0 +1,34 @@
+use bytes::BytesMut;
+use decompression_plz::MultiDecompressErrorReason;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CompressError {
+    #[error("Unsupported Encoding| {0}")]
+    UnsupportedEncoding(String),
+    #[error("Failed to Compress| {0}| {1}")]
+    Encode(String, String),
+    #[error("Transfer Encoded Body| {0}")]
+    TransferEncoded(String),
+}
+
+#[derive(Debug, Error)]
+pub enum DecompressError {
+    #[error("Decompressed Size Exceeds Limit| {limit}")]
+    LimitExceeded {
+        limit: usize,
+        partial: BytesMut,
+    },
+    #[error("Failed to Decompress| {0}")]
+    Decompress(#[from] MultiDecompressErrorReason),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum StreamDecodeError {
+    #[error("Unsupported Encoding| {0}")]
+    UnsupportedEncoding(String),
+    #[error("Failed to Decode| {0}")]
+    Decode(String),
+    #[error("Decoded Size Exceeds Limit| {0}")]
+    LimitExceeded(usize),
+}
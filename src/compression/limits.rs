@@ -0,0 +1,368 @@
+use std::io::Write;
+
+use body_plz::variants::Body;
+use bytes::BytesMut;
+use decompression_plz::MultiDecompressErrorReason;
+use header_plz::{
+    OneHeaderMap, OneInfoLine, OneMessageHead,
+    body_headers::{
+        content_encoding::ContentEncoding, parse::ParseBodyHeaders,
+    },
+    const_headers::{CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING},
+};
+
+use crate::{
+    OneOne,
+    compression::{
+        decode::{decoder_chain, header_encodings, is_decodable},
+        error::DecompressError,
+    },
+    utils::header::set_value,
+};
+
+const DEFAULT_MAX_SIZE: usize = 64 * 1024 * 1024;
+const DEFAULT_MAX_RATIO: usize = 100;
+
+/* Bounds on the decoded size of a body.
+ *
+ *      max_size    : decoded bytes, regardless of input size
+ *      max_ratio   : decoded bytes per encoded byte, None to disable
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressLimits {
+    max_size: usize,
+    max_ratio: Option<usize>,
+}
+
+impl Default for DecompressLimits {
+    fn default() -> Self {
+        DecompressLimits {
+            max_size: DEFAULT_MAX_SIZE,
+            max_ratio: Some(DEFAULT_MAX_RATIO),
+        }
+    }
+}
+
+impl DecompressLimits {
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn with_max_ratio(mut self, max_ratio: Option<usize>) -> Self {
+        self.max_ratio = max_ratio;
+        self
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn max_ratio(&self) -> Option<usize> {
+        self.max_ratio
+    }
+
+    // decoded bytes allowed for input_len encoded bytes
+    pub fn limit_for(&self, input_len: usize) -> usize {
+        self.max_ratio.map_or(self.max_size, |ratio| {
+            self.max_size.min(input_len.saturating_mul(ratio))
+        })
+    }
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    /* Tail of body_encodings() after the last coding that cannot be
+     * decoded, try_decompress() stops there.
+     */
+    fn decodable_tail(&self) -> Vec<ContentEncoding> {
+        let mut encodings = self.body_encodings();
+        let start = encodings
+            .iter()
+            .rposition(|e| !is_decodable(e))
+            .map_or(0, |pos| pos + 1);
+        encodings.split_off(start)
+    }
+
+    /* Description:
+     *      try_decompress() with the decoded size bounded by limits, the
+     *      limit is computed from the encoded body and extra body size.
+     *
+     * Steps:
+     *      1. Nothing to decode => try_decompress(), which then only
+     *         converts a chunked body to raw.
+     *      2. Decode the body and extra body into a bounded sink.
+     *      3. Limit exceeded => return LimitExceeded with the output
+     *         decoded till the limit.
+     *      4. Decoding failed => return Decompress(Corrupt).
+     *      5. Install the decoded output as a raw body, see
+     *         set_decoded_body().
+     *
+     *      The body is decoded once and never without the limit. The
+     *      message is left untouched on error.
+     *
+     * Error:
+     *      DecompressError::LimitExceeded      [3]
+     *      DecompressError::Decompress         [1] [4]
+     */
+
+    pub fn try_decompress_with_limits(
+        &mut self,
+        buf: &mut BytesMut,
+        limits: &DecompressLimits,
+    ) -> Result<(), DecompressError> {
+        let encodings = self.decodable_tail();
+        let body = match self.body_data() {
+            Some(body) if !encodings.is_empty() => body,
+            _ => return Ok(self.try_decompress(buf)?),
+        };
+        let input = [Some(body.as_ref()), self.extra_body.as_deref()];
+        let input_len = input.iter().flatten().map(|d| d.len()).sum();
+        let limit = limits.limit_for(input_len);
+        let mut chain = decoder_chain(&encodings, limit)
+            .map_err(|_| MultiDecompressErrorReason::Corrupt)?;
+        let result = input
+            .iter()
+            .flatten()
+            .try_for_each(|d| chain.write_all(d))
+            .and_then(|_| chain.try_finish());
+        let sink = chain.sink();
+        match result {
+            Err(_) if sink.is_exceeded() => {
+                return Err(DecompressError::LimitExceeded {
+                    limit,
+                    partial: sink.take(),
+                });
+            }
+            Err(_) => return Err(MultiDecompressErrorReason::Corrupt.into()),
+            Ok(()) => (),
+        }
+        let decoded = sink.take();
+        self.set_decoded_body(encodings.len(), decoded);
+        Ok(())
+    }
+
+    /* Headers as try_decompress() leaves them once the last decoded_count
+     * codings are decoded. Trailers are merged, chunked and the decoded
+     * codings are removed from Transfer-Encoding and Content-Encoding, and
+     * Content-Length is set to the decoded size.
+     */
+    fn set_decoded_body(&mut self, decoded_count: usize, decoded: BytesMut) {
+        let header_map = self.message_head.header_map();
+        let content = header_encodings(header_map, CONTENT_ENCODING);
+        let transfer: Vec<ContentEncoding> =
+            header_encodings(header_map, TRANSFER_ENCODING)
+                .into_iter()
+                .filter(|e| *e != ContentEncoding::Chunked)
+                .collect();
+        let keep = content.len() + transfer.len() - decoded_count;
+        let (content_kept, content_decoded) =
+            content.split_at(keep.min(content.len()));
+
+        self.chunked_body_to_raw();
+        let header_map = self.message_head.header_map_as_mut();
+        set_codings(header_map, CONTENT_ENCODING, content_kept);
+        set_codings(
+            header_map,
+            TRANSFER_ENCODING,
+            &transfer[..keep.saturating_sub(content.len())],
+        );
+        set_value(header_map, CONTENT_LENGTH, &decoded.len().to_string());
+        self.body = Some(Body::Raw(decoded));
+        self.extra_body = None;
+        self.body_headers = self.message_head.parse_body_headers();
+        self.set_decoded_encodings(content_decoded.to_vec());
+    }
+}
+
+// replace all headers with key by a single one listing codings
+fn set_codings(
+    header_map: &mut OneHeaderMap,
+    key: &[u8],
+    codings: &[ContentEncoding],
+) {
+    header_map.remove_header_on_key_all(key);
+    if !codings.is_empty() {
+        let value = codings
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<&str>>()
+            .join(", ");
+        header_map.insert(key, value.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use body_plz::variants::chunked::ChunkType;
+    use header_plz::{HeaderMap, OneHeaderMap};
+
+    use crate::{OneResponse, compression::encode::compress};
+
+    use super::*;
+
+    #[test]
+    fn test_limit_for() {
+        let limits = DecompressLimits::default()
+            .with_max_size(1000)
+            .with_max_ratio(Some(10));
+        assert_eq!(limits.limit_for(10), 100);
+        assert_eq!(limits.limit_for(500), 1000);
+        assert_eq!(limits.with_max_ratio(None).limit_for(10), 1000);
+        assert_eq!(
+            DecompressLimits::default().limit_for(usize::MAX),
+            DEFAULT_MAX_SIZE
+        );
+    }
+
+    #[test]
+    fn test_try_decompress_with_limits_within() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 11\r\n\r\n\
+                     hello world";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.try_compress(&[ContentEncoding::Gzip]).unwrap();
+        res.try_decompress_with_limits(
+            &mut BytesMut::new(),
+            &DecompressLimits::default(),
+        )
+        .unwrap();
+        let verify = "HTTP/1.1 200 OK\r\n\
+                      Content-Length: 11\r\n\r\n\
+                      hello world";
+        assert_eq!(res.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_try_decompress_with_limits_max_size() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 1\r\n\r\n\
+                     0";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.body = Some(Body::Raw(BytesMut::zeroed(1024 * 1024)));
+        res.try_compress(&[ContentEncoding::Gzip]).unwrap();
+        let body = res.body_data().unwrap().into_owned();
+        let limits = DecompressLimits::default()
+            .with_max_size(4096)
+            .with_max_ratio(None);
+        let Err(DecompressError::LimitExceeded {
+            limit,
+            partial,
+        }) = res.try_decompress_with_limits(&mut BytesMut::new(), &limits)
+        else {
+            panic!("expected limit exceeded");
+        };
+        assert_eq!(limit, 4096);
+        assert_eq!(partial.as_ref(), &[0; 4096]);
+        // untouched
+        let Some(Body::Raw(raw)) = res.body() else {
+            panic!("expected raw body");
+        };
+        assert_eq!(raw.as_ref(), body);
+        assert!(res.decoded_encodings().is_empty());
+    }
+
+    #[test]
+    fn test_try_decompress_with_limits_max_ratio() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 1\r\n\r\n\
+                     0";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.body = Some(Body::Raw(BytesMut::zeroed(1024 * 1024)));
+        res.try_compress(&[ContentEncoding::Gzip, ContentEncoding::Brotli])
+            .unwrap();
+        let body = res.body_data().unwrap().into_owned();
+        let Err(DecompressError::LimitExceeded {
+            limit,
+            partial,
+        }) = res.try_decompress_with_limits(
+            &mut BytesMut::new(),
+            &DecompressLimits::default(),
+        )
+        else {
+            panic!("expected limit exceeded");
+        };
+        assert_eq!(limit, body.len() * DEFAULT_MAX_RATIO);
+        assert_eq!(partial.len(), limit);
+    }
+
+    #[test]
+    fn test_try_decompress_with_limits_chunked() {
+        let zeros = vec![0; 64 * 1024];
+        let body = compress(&zeros, &ContentEncoding::Zstd).unwrap();
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Encoding: zstd\r\n\
+                     Transfer-Encoding: chunked\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        let (first, second) = body.split_at(body.len() / 2);
+        let size = |data: &[u8]| {
+            let size = format!("{:x}\r\n", data.len());
+            ChunkType::Size(size.as_str().into())
+        };
+        let chunk = |data: &[u8]| {
+            let mut chunk = BytesMut::from(data);
+            chunk.extend_from_slice(b"\r\n");
+            ChunkType::Chunk(chunk)
+        };
+        res.body = Some(Body::Chunked(vec![
+            size(first),
+            chunk(first),
+            size(second),
+            chunk(second),
+            ChunkType::LastChunk("0\r\n".into()),
+            ChunkType::Trailers(OneHeaderMap::from(HeaderMap::new())),
+        ]));
+        let limits = DecompressLimits::default().with_max_ratio(None);
+        let mut smaller = limits.with_max_size(zeros.len() - 1);
+        assert!(matches!(
+            res.try_decompress_with_limits(&mut BytesMut::new(), &smaller),
+            Err(DecompressError::LimitExceeded { .. })
+        ));
+        smaller = limits.with_max_size(zeros.len());
+        res.try_decompress_with_limits(&mut BytesMut::new(), &smaller)
+            .unwrap();
+        let Some(Body::Raw(raw)) = res.body() else {
+            panic!("expected raw body");
+        };
+        assert_eq!(raw.as_ref(), zeros);
+    }
+
+    #[test]
+    fn test_try_decompress_with_limits_corrupt() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Encoding: gzip\r\n\
+                     Content-Length: 8\r\n\r\n\
+                     not gzip";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        let result = res.try_decompress_with_limits(
+            &mut BytesMut::new(),
+            &DecompressLimits::default(),
+        );
+        assert!(matches!(result, Err(DecompressError::Decompress(_))));
+        assert_eq!(res.into_bytes(), input);
+    }
+
+    #[test]
+    fn test_try_decompress_with_limits_unknown_kept() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Encoding: foo\r\n\
+                     Content-Length: 11\r\n\r\n\
+                     hello world";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.try_compress(&[ContentEncoding::Gzip]).unwrap();
+        res.try_decompress_with_limits(
+            &mut BytesMut::new(),
+            &DecompressLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(res.decoded_encodings(), [ContentEncoding::Gzip]);
+        let verify = "HTTP/1.1 200 OK\r\n\
+                      Content-Length: 11\r\n\
+                      content-encoding: foo\r\n\r\n\
+                      hello world";
+        assert_eq!(res.into_bytes(), verify);
+    }
+}
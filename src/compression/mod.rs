@@ -0,0 +1,439 @@
+use body_plz::variants::Body;
+use bytes::BytesMut;
+use decompression_plz::chunked::chunked_to_raw;
+use header_plz::{
+    OneInfoLine, OneMessageHead,
+    body_headers::{
+        content_encoding::ContentEncoding, parse::ParseBodyHeaders,
+    },
+    const_headers::{CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING},
+    message_head::header_map::{HMap, Hmap},
+};
+
+use crate::{
+    Message, OneOne,
+    compression::decode::header_encodings,
+    utils::header::{LIST_DELIMITER, append_list_value},
+};
+
+pub mod accept;
+pub(crate) mod decode;
+pub mod encode;
+pub mod error;
+pub mod limits;
+pub mod sniff;
+pub mod stream;
+
+use encode::compress;
+use error::CompressError;
+
+// apply encodings in order, identity is skipped
+fn compress_all(
+    data: &[u8],
+    encodings: &[ContentEncoding],
+) -> Result<Option<BytesMut>, CompressError> {
+    let mut encoded: Option<BytesMut> = None;
+    for encoding in
+        encodings.iter().filter(|e| **e != ContentEncoding::Identity)
+    {
+        let input = encoded.as_deref().unwrap_or(data);
+        encoded = Some(compress(input, encoding)?);
+    }
+    Ok(encoded)
+}
+
+fn append_content_encoding<T>(
+    header_map: &mut HMap<T>,
+    encodings: &[ContentEncoding],
+) where
+    T: Hmap + for<'a> From<(&'a [u8], &'a [u8])>,
+{
+    let value = encodings
+        .iter()
+        .filter(|e| **e != ContentEncoding::Identity)
+        .map(AsRef::as_ref)
+        .collect::<Vec<&str>>()
+        .join(", ");
+    append_list_value(header_map, CONTENT_ENCODING, &value);
+}
+
+// transfer-codings other than chunked, try_decompress() removes them
+fn check_transfer_codings<T>(header_map: &HMap<T>) -> Result<(), CompressError>
+where
+    T: Hmap,
+{
+    let codings: Vec<String> = header_encodings(header_map, TRANSFER_ENCODING)
+        .into_iter()
+        .filter(|e| *e != ContentEncoding::Chunked)
+        .map(|e| e.as_ref().to_string())
+        .collect();
+    if codings.is_empty() {
+        return Ok(());
+    }
+    Err(CompressError::TransferEncoded(codings.join(LIST_DELIMITER)))
+}
+
+// first Content-Length is updated, duplicates would frame the old body
+fn replace_content_length<T>(header_map: &mut HMap<T>, len: usize)
+where
+    T: Hmap + for<'a> From<(&'a [u8], &'a [u8])>,
+{
+    let positions: Vec<usize> = header_map
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| h.key_as_ref().eq_ignore_ascii_case(CONTENT_LENGTH))
+        .map(|(pos, _)| pos)
+        .collect();
+    let len = len.to_string();
+    match positions.split_first() {
+        Some((first, duplicates)) => {
+            header_map.update_header_value_on_position(*first, len.as_str());
+            for pos in duplicates.iter().rev() {
+                header_map.remove_header_on_position(*pos);
+            }
+        }
+        None => header_map.insert(CONTENT_LENGTH, len.as_bytes()),
+    }
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    // trailers are merged into the headers, Transfer-Encoding is removed
+    pub(crate) fn chunked_body_to_raw(&mut self) {
+        if matches!(self.body, Some(Body::Chunked(_))) {
+            chunked_to_raw(self, &mut BytesMut::new());
+            self.message_head
+                .header_map_as_mut()
+                .remove_header_on_key_all(TRANSFER_ENCODING);
+        }
+    }
+
+    /* Description:
+     *      Inverse of try_decompress(), encode the body with encodings in
+     *      order.
+     *
+     * Steps:
+     *      1. Transfer codings other than chunked have to be decoded
+     *         already.
+     *      2. Encode the body, chunk data is concatenated.
+     *      3. Convert a chunked body to raw, trailers are merged into the
+     *         headers and Transfer-Encoding is removed.
+     *      4. Append encodings to Content-Encoding, replace Content-Length
+     *         and parse the body headers again.
+     *
+     *      The message is left untouched on error, or if encodings has
+     *      nothing but identity.
+     *
+     * Error:
+     *      CompressError::TransferEncoded      [1]
+     *      CompressError                       [2]
+     */
+
+    pub fn try_compress(
+        &mut self,
+        encodings: &[ContentEncoding],
+    ) -> Result<(), CompressError> {
+        if self.body.is_none() {
+            return Ok(());
+        }
+        check_transfer_codings(self.message_head.header_map())?;
+        let body = self.body_data().unwrap_or_default();
+        let Some(encoded) = compress_all(&body, encodings)? else {
+            return Ok(());
+        };
+
+        self.chunked_body_to_raw();
+        let header_map = self.message_head.header_map_as_mut();
+        append_content_encoding(header_map, encodings);
+        replace_content_length(header_map, encoded.len());
+        self.body = Some(Body::Raw(encoded));
+        self.body_headers = self.message_head.parse_body_headers();
+        Ok(())
+    }
+
+    /* Description:
+     *      Encode the body again with the content-codings removed by
+     *      try_decompress(), the downstream gets the encoding chain the
+     *      origin sent.
+     *
+     *      No-op if nothing was decoded. On error the codings are kept, so
+     *      the call can be retried.
+     */
+
+    pub fn reencode(&mut self) -> Result<(), CompressError> {
+        let encodings = self.take_decoded_encodings();
+        if let Err(e) = self.try_compress(&encodings) {
+            self.set_decoded_encodings(encodings);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+impl<T> Message<T> {
+    // Content-Length is updated only if present
+    pub fn try_compress(
+        &mut self,
+        encodings: &[ContentEncoding],
+    ) -> Result<(), CompressError> {
+        let Some(body) = self.body_as_ref() else {
+            return Ok(());
+        };
+        check_transfer_codings(&self.headers)?;
+        let Some(encoded) = compress_all(body, encodings)? else {
+            return Ok(());
+        };
+        append_content_encoding(&mut self.headers, encodings);
+        if self.headers.has_key(CONTENT_LENGTH) {
+            replace_content_length(&mut self.headers, encoded.len());
+        }
+        self.set_body(encoded);
+        self.update_body_headers();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use body_plz::variants::chunked::ChunkType;
+    use header_plz::{
+        HeaderMap, OneHeaderMap, message_head::header_map::HeaderStr,
+    };
+
+    use crate::{OneResponse, Response};
+
+    use super::*;
+
+    fn gzip_body() -> BytesMut {
+        compress(b"hello world", &ContentEncoding::Gzip).unwrap()
+    }
+
+    #[test]
+    fn test_one_try_compress_round_trip() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Type: text/plain\r\n\
+                     Content-Length: 11\r\n\r\n\
+                     hello world";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.try_compress(&[ContentEncoding::Gzip, ContentEncoding::Brotli])
+            .unwrap();
+        let header_map = res.message_head().header_map();
+        assert_eq!(
+            header_map.value_of_key(CONTENT_ENCODING).unwrap(),
+            b"gzip, br"
+        );
+        let Some(Body::Raw(body)) = res.body() else {
+            panic!("expected raw body");
+        };
+        assert_eq!(
+            header_map.value_of_key(CONTENT_LENGTH).unwrap(),
+            body.len().to_string().as_bytes()
+        );
+
+        let mut buf = BytesMut::new();
+        res.try_decompress(&mut buf).unwrap();
+        let verify = "HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain\r\n\
+                      Content-Length: 11\r\n\r\n\
+                      hello world";
+        assert_eq!(res.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_one_try_compress_appends_content_encoding() {
+        let body = gzip_body();
+        let input = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Encoding: gzip\r\n\
+             Content-Length: {}\r\n\r\n",
+            body.len()
+        );
+        let mut input = BytesMut::from(input.as_bytes());
+        input.extend_from_slice(&body);
+        let mut res = OneResponse::try_from(input).unwrap();
+        res.try_compress(&[ContentEncoding::Identity, ContentEncoding::Zstd])
+            .unwrap();
+        assert_eq!(
+            res.message_head()
+                .header_map()
+                .value_of_key(CONTENT_ENCODING)
+                .unwrap(),
+            b"gzip, zstd"
+        );
+    }
+
+    #[test]
+    fn test_one_try_compress_chunked() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Transfer-Encoding: chunked\r\n\
+                     Trailer: X-Checksum\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-checksum", "abc");
+        res.body = Some(Body::Chunked(vec![
+            ChunkType::Size("5\r\n".into()),
+            ChunkType::Chunk("hello\r\n".into()),
+            ChunkType::Size("6\r\n".into()),
+            ChunkType::Chunk(" world\r\n".into()),
+            ChunkType::LastChunk("0\r\n".into()),
+            ChunkType::Trailers(OneHeaderMap::from(trailers)),
+        ]));
+        res.try_compress(&[ContentEncoding::Deflate]).unwrap();
+        let header_map = res.message_head().header_map();
+        assert!(!header_map.has_key(TRANSFER_ENCODING));
+        assert!(!header_map.has_key("trailer"));
+        assert!(header_map.has_key("x-checksum"));
+        assert!(header_map.has_key(CONTENT_LENGTH));
+
+        let mut buf = BytesMut::new();
+        res.try_decompress(&mut buf).unwrap();
+        let Some(Body::Raw(body)) = res.body() else {
+            panic!("expected raw body");
+        };
+        assert_eq!(body.as_ref(), b"hello world");
+    }
+
+    #[test]
+    fn test_one_try_compress_chunked_untouched() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Transfer-Encoding: chunked\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.body = Some(Body::Chunked(vec![
+            ChunkType::Size("5\r\n".into()),
+            ChunkType::Chunk("hello\r\n".into()),
+            ChunkType::LastChunk("0\r\n".into()),
+            ChunkType::Trailers(OneHeaderMap::from(HeaderMap::new())),
+        ]));
+        assert!(res.try_compress(&[ContentEncoding::Compress]).is_err());
+        res.try_compress(&[ContentEncoding::Identity]).unwrap();
+        res.try_compress(&[]).unwrap();
+        assert!(matches!(res.body(), Some(Body::Chunked(_))));
+        let header_map = res.message_head().header_map();
+        assert!(header_map.has_key(TRANSFER_ENCODING));
+        assert!(!header_map.has_key(CONTENT_LENGTH));
+    }
+
+    #[test]
+    fn test_one_try_compress_transfer_encoded() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Transfer-Encoding: gzip, chunked\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.body = Some(Body::Chunked(vec![
+            ChunkType::Size("5\r\n".into()),
+            ChunkType::Chunk("hello\r\n".into()),
+            ChunkType::LastChunk("0\r\n".into()),
+            ChunkType::Trailers(OneHeaderMap::from(HeaderMap::new())),
+        ]));
+        assert_eq!(
+            res.try_compress(&[ContentEncoding::Gzip]),
+            Err(CompressError::TransferEncoded("gzip".to_string()))
+        );
+        assert!(matches!(res.body(), Some(Body::Chunked(_))));
+        assert!(!res.message_head().header_map().has_key(CONTENT_ENCODING));
+    }
+
+    #[test]
+    fn test_one_try_compress_duplicate_content_length() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 11\r\n\
+                     Content-Length: 11\r\n\r\n\
+                     hello world";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.try_compress(&[ContentEncoding::Gzip]).unwrap();
+        let header_map = res.message_head().header_map();
+        let lengths: Vec<&[u8]> = header_map
+            .iter()
+            .filter(|h| h.key_as_ref().eq_ignore_ascii_case(CONTENT_LENGTH))
+            .map(|h| h.value_as_ref())
+            .collect();
+        let Some(Body::Raw(body)) = res.body() else {
+            panic!("expected raw body");
+        };
+        assert_eq!(lengths, [body.len().to_string().as_bytes()]);
+    }
+
+    #[test]
+    fn test_one_try_compress_unsupported() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 5\r\n\r\n\
+                     hello";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            res.try_compress(&[
+                ContentEncoding::Gzip,
+                ContentEncoding::Compress
+            ]),
+            Err(CompressError::UnsupportedEncoding("compress".to_string()))
+        );
+        assert_eq!(res.into_bytes(), input);
+    }
+
+    #[test]
+    fn test_one_reencode() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 11\r\n\r\n\
+                     hello world";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        let encodings = [ContentEncoding::Gzip, ContentEncoding::Brotli];
+        res.try_compress(&encodings).unwrap();
+        res.try_decompress(&mut BytesMut::new()).unwrap();
+        assert_eq!(res.decoded_encodings(), encodings);
+
+        // edit
+        let body = BytesMut::from("hello world, edited");
+        res.body = Some(Body::Raw(body));
+        res.reencode().unwrap();
+        assert!(res.decoded_encodings().is_empty());
+        assert_eq!(
+            res.message_head()
+                .header_map()
+                .value_of_key(CONTENT_ENCODING)
+                .unwrap(),
+            b"gzip, br"
+        );
+
+        res.try_decompress(&mut BytesMut::new()).unwrap();
+        let Some(Body::Raw(body)) = res.body() else {
+            panic!("expected raw body");
+        };
+        assert_eq!(body.as_ref(), b"hello world, edited");
+    }
+
+    #[test]
+    fn test_one_reencode_nothing_decoded() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 5\r\n\r\n\
+                     hello";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.reencode().unwrap();
+        assert_eq!(res.into_bytes(), input);
+    }
+
+    #[test]
+    fn test_message_try_compress() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-length", "11");
+        let mut res = Response::builder()
+            .headers(headers)
+            .body(BytesMut::from("hello world"))
+            .build();
+        res.try_compress(&[ContentEncoding::Zstd]).unwrap();
+        let body = res.body_as_ref().unwrap();
+        assert_eq!(zstd::decode_all(&body[..]).unwrap(), b"hello world");
+        let headers: Vec<(&str, String)> = res
+            .headers()
+            .iter()
+            .filter_map(|h| {
+                Some((h.key_as_str()?, h.value_as_str()?.to_string()))
+            })
+            .collect();
+        let verify = vec![
+            ("content-length", body.len().to_string()),
+            ("content-encoding", "zstd".to_string()),
+        ];
+        assert_eq!(headers, verify);
+    }
+}
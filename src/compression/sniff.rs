@@ -0,0 +1,425 @@
+use std::io::Write;
+
+use bytes::BytesMut;
+use decompression_plz::MultiDecompressErrorReason;
+use header_plz::{
+    OneInfoLine, OneMessageHead,
+    body_headers::{
+        content_encoding::ContentEncoding, parse::ParseBodyHeaders,
+    },
+    const_headers::CONTENT_ENCODING,
+    message_head::header_map::{HMap, Hmap},
+};
+
+use crate::{
+    Message, OneOne,
+    compression::decode::{decoder_chain, header_encodings, is_zlib_header},
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b, 0x08];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+// decoded bytes needed to accept a coding without magic bytes
+const TRIAL_DECODE_LIMIT: usize = 64 * 1024;
+
+// RFC 1950 2.2, CMF and FLG as a big endian u16 is a multiple of 31
+fn is_zlib(body: &[u8]) -> bool {
+    match body {
+        [cmf, flg, ..] => {
+            is_zlib_header(*cmf)
+                && u16::from_be_bytes([*cmf, *flg]).is_multiple_of(31)
+        }
+        _ => false,
+    }
+}
+
+/* Description:
+ *      Whether body decodes as encoding. Decoding the whole body without
+ *      error, or reaching TRIAL_DECODE_LIMIT, counts as a match. Empty
+ *      output does not, a single byte is a valid empty brotli stream.
+ */
+
+fn decodes_as(body: &[u8], encoding: &ContentEncoding) -> bool {
+    let Ok(mut chain) =
+        decoder_chain(std::slice::from_ref(encoding), TRIAL_DECODE_LIMIT)
+    else {
+        return false;
+    };
+    let result = chain.write_all(body).and_then(|_| chain.try_finish());
+    let sink = chain.sink();
+    sink.is_exceeded() || (result.is_ok() && !sink.take().is_empty())
+}
+
+/* Description:
+ *      Detect the outermost coding of body.
+ *
+ * Steps:
+ *      1. gzip and zstd by magic bytes.
+ *      2. zlib wrapped deflate by the RFC 1950 header check, which about 1
+ *         in 31 plain bodies pass, so body must also decode as deflate.
+ *      3. brotli has no magic bytes, accepted if body decodes as brotli.
+ *
+ * Returns:
+ *      None for unencoded bodies and raw deflate, which has no header.
+ */
+
+pub fn sniff_encoding(body: &[u8]) -> Option<ContentEncoding> {
+    if body.starts_with(GZIP_MAGIC) {
+        Some(ContentEncoding::Gzip)
+    } else if body.starts_with(ZSTD_MAGIC) {
+        Some(ContentEncoding::Zstd)
+    } else if is_zlib(body) && decodes_as(body, &ContentEncoding::Deflate) {
+        Some(ContentEncoding::Deflate)
+    } else if decodes_as(body, &ContentEncoding::Brotli) {
+        Some(ContentEncoding::Brotli)
+    } else {
+        None
+    }
+}
+
+fn matches_declared(
+    declared: &ContentEncoding,
+    sniffed: Option<&ContentEncoding>,
+    body: &[u8],
+) -> bool {
+    match declared {
+        ContentEncoding::Compress | ContentEncoding::Unknown(_) => true,
+        _ if sniffed == Some(declared) => true,
+        ContentEncoding::Deflate | ContentEncoding::Brotli
+            if sniffed.is_none() =>
+        {
+            decodes_as(body, declared)
+        }
+        _ => false,
+    }
+}
+
+// Outermost declared content-coding against the sniffed one
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodingSniff {
+    declared: Option<ContentEncoding>,
+    sniffed: Option<ContentEncoding>,
+    mismatch: bool,
+}
+
+impl EncodingSniff {
+    /* Description:
+     *      Compare the last coding of declared with body.
+     *
+     *      Raw deflate and brotli have no magic bytes, a declared one is
+     *      accepted if body decodes as it. compress and unknown codings
+     *      cannot be checked and never mismatch.
+     */
+
+    pub fn new(declared: &[ContentEncoding], body: &[u8]) -> Self {
+        let declared = declared.last().cloned();
+        let sniffed = sniff_encoding(body);
+        let mismatch = match &declared {
+            None => sniffed.is_some(),
+            Some(d) => !matches_declared(d, sniffed.as_ref(), body),
+        };
+        EncodingSniff {
+            declared,
+            sniffed,
+            mismatch,
+        }
+    }
+
+    pub fn declared(&self) -> Option<&ContentEncoding> {
+        self.declared.as_ref()
+    }
+
+    pub fn sniffed(&self) -> Option<&ContentEncoding> {
+        self.sniffed.as_ref()
+    }
+
+    pub fn is_mismatch(&self) -> bool {
+        self.mismatch
+    }
+}
+
+// Content-Encoding replaced by the sniffed coding, removed if None
+// returns the removed Content-Encoding values, see restore_content_encoding()
+fn replace_content_encoding<T>(
+    header_map: &mut HMap<T>,
+    sniffed: Option<&ContentEncoding>,
+) -> Vec<Vec<u8>>
+where
+    T: Hmap + for<'a> From<(&'a [u8], &'a [u8])>,
+{
+    let original = header_map
+        .iter()
+        .filter(|h| h.key_as_ref().eq_ignore_ascii_case(CONTENT_ENCODING))
+        .map(|h| h.value_as_ref().to_vec())
+        .collect();
+    header_map.remove_header_on_key_all(CONTENT_ENCODING);
+    if let Some(encoding) = sniffed {
+        header_map.insert(CONTENT_ENCODING, encoding.as_ref().as_bytes());
+    }
+    original
+}
+
+fn restore_content_encoding<T>(header_map: &mut HMap<T>, original: &[Vec<u8>])
+where
+    T: Hmap + for<'a> From<(&'a [u8], &'a [u8])>,
+{
+    header_map.remove_header_on_key_all(CONTENT_ENCODING);
+    for value in original {
+        header_map.insert(CONTENT_ENCODING, value.as_slice());
+    }
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    // None for a missing or empty body, transfer-codings are not considered
+    pub fn sniff_encoding(&self) -> Option<EncodingSniff> {
+        let body = self.body_data()?;
+        Some(EncodingSniff::new(&self.content_encodings(), &body))
+    }
+
+    /* Description:
+     *      try_decompress() that trusts the body over Content-Encoding.
+     *
+     * Steps:
+     *      1. Sniff the body, on a mismatch replace the Content-Encoding
+     *         chain with the sniffed coding, or remove it for a plain body,
+     *         and parse the body headers again.
+     *      2. Call try_decompress(), on error the original
+     *         Content-Encoding is restored.
+     *
+     * Returns:
+     *      Ok(Some(EncodingSniff)) if the headers were replaced.
+     */
+
+    pub fn try_decompress_or_sniff(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<EncodingSniff>, MultiDecompressErrorReason> {
+        let Some(sniff) =
+            self.sniff_encoding().filter(EncodingSniff::is_mismatch)
+        else {
+            return self.try_decompress(buf).map(|_| None);
+        };
+        let original = replace_content_encoding(
+            self.message_head.header_map_as_mut(),
+            sniff.sniffed(),
+        );
+        self.body_headers = self.message_head.parse_body_headers();
+        if let Err(e) = self.try_decompress(buf) {
+            restore_content_encoding(
+                self.message_head.header_map_as_mut(),
+                &original,
+            );
+            self.body_headers = self.message_head.parse_body_headers();
+            return Err(e);
+        }
+        Ok(Some(sniff))
+    }
+}
+
+impl<T> Message<T>
+where
+    T: std::fmt::Debug,
+{
+    // None for a missing or empty body
+    pub fn sniff_encoding(&self) -> Option<EncodingSniff> {
+        let body = self.body_as_ref().filter(|b| !b.is_empty())?;
+        let declared = header_encodings(&self.headers, CONTENT_ENCODING);
+        Some(EncodingSniff::new(&declared, body))
+    }
+
+    // OneOne::try_decompress_or_sniff()
+    pub fn try_decompress_or_sniff(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<EncodingSniff>, MultiDecompressErrorReason> {
+        let Some(sniff) =
+            self.sniff_encoding().filter(EncodingSniff::is_mismatch)
+        else {
+            return self.try_decompress(buf).map(|_| None);
+        };
+        let original =
+            replace_content_encoding(&mut self.headers, sniff.sniffed());
+        if let Err(e) = self.try_decompress(buf) {
+            restore_content_encoding(&mut self.headers, &original);
+            self.update_body_headers();
+            return Err(e);
+        }
+        Ok(Some(sniff))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use body_plz::variants::Body;
+    use header_plz::HeaderMap;
+
+    use crate::{OneResponse, Response, compression::encode::compress};
+
+    use super::*;
+
+    const INPUT: &[u8] = b"hello world hello world hello world";
+
+    #[test]
+    fn test_sniff_encoding() {
+        for encoding in [
+            ContentEncoding::Gzip,
+            ContentEncoding::Deflate,
+            ContentEncoding::Brotli,
+            ContentEncoding::Zstd,
+        ] {
+            let body = compress(INPUT, &encoding).unwrap();
+            assert_eq!(sniff_encoding(&body), Some(encoding));
+        }
+        assert_eq!(sniff_encoding(INPUT), None);
+        assert_eq!(sniff_encoding(b"{\"json\": true}"), None);
+        assert_eq!(sniff_encoding(b""), None);
+        // passes the zlib header check, 0x3830 is a multiple of 31
+        assert_eq!(sniff_encoding(b"800"), None);
+    }
+
+    #[test]
+    fn test_encoding_sniff_match() {
+        let body = compress(INPUT, &ContentEncoding::Gzip).unwrap();
+        let sniff = EncodingSniff::new(&[ContentEncoding::Gzip], &body);
+        assert!(!sniff.is_mismatch());
+        assert_eq!(sniff.declared(), Some(&ContentEncoding::Gzip));
+        assert_eq!(sniff.sniffed(), Some(&ContentEncoding::Gzip));
+
+        // raw deflate has no header
+        let mut encoder = flate2::write::DeflateEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(INPUT).unwrap();
+        let body = encoder.finish().unwrap();
+        let sniff = EncodingSniff::new(&[ContentEncoding::Deflate], &body);
+        assert_eq!(sniff.sniffed(), None);
+        assert!(!sniff.is_mismatch());
+
+        let sniff = EncodingSniff::new(&[ContentEncoding::Compress], INPUT);
+        assert!(!sniff.is_mismatch());
+    }
+
+    #[test]
+    fn test_encoding_sniff_mismatch() {
+        // gzip without Content-Encoding
+        let body = compress(INPUT, &ContentEncoding::Gzip).unwrap();
+        let sniff = EncodingSniff::new(&[], &body);
+        assert!(sniff.is_mismatch());
+        assert_eq!(sniff.sniffed(), Some(&ContentEncoding::Gzip));
+
+        // plain body labelled br
+        let sniff = EncodingSniff::new(&[ContentEncoding::Brotli], INPUT);
+        assert!(sniff.is_mismatch());
+        assert_eq!(sniff.sniffed(), None);
+
+        // zstd labelled gzip
+        let body = compress(INPUT, &ContentEncoding::Zstd).unwrap();
+        let sniff = EncodingSniff::new(&[ContentEncoding::Gzip], &body);
+        assert!(sniff.is_mismatch());
+        assert_eq!(sniff.sniffed(), Some(&ContentEncoding::Zstd));
+    }
+
+    #[test]
+    fn test_one_sniff_encoding_empty_body() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Encoding: gzip\r\n\
+                     Content-Length: 0\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert!(res.sniff_encoding().is_none());
+    }
+
+    #[test]
+    fn test_one_try_decompress_or_sniff_missing_header() {
+        // gzip of INPUT
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Length: 35\r\n\r\n\
+                      \x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03\xcb\x48\xcd\xc9\
+                      \xc9\x57\x28\xcf\x2f\xca\x49\x51\xc8\xc0\xce\x06\x00\xbb\
+                      \xfe\x42\x0f\x23\x00\x00\x00";
+        let input = BytesMut::from(&input[..]);
+        let mut res = OneResponse::try_from(input).unwrap();
+        let sniff = res
+            .try_decompress_or_sniff(&mut BytesMut::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(sniff.sniffed(), Some(&ContentEncoding::Gzip));
+        assert_eq!(res.decoded_encodings(), [ContentEncoding::Gzip]);
+        let verify = "HTTP/1.1 200 OK\r\n\
+                      Content-Length: 35\r\n\r\n\
+                      hello world hello world hello world";
+        assert_eq!(res.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_one_try_decompress_or_sniff_plain_body() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Encoding: br\r\n\
+                     Content-Length: 35\r\n\r\n\
+                     hello world hello world hello world";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        let sniff = res
+            .try_decompress_or_sniff(&mut BytesMut::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(sniff.declared(), Some(&ContentEncoding::Brotli));
+        assert!(res.decoded_encodings().is_empty());
+        let Some(Body::Raw(body)) = res.body() else {
+            panic!("expected raw body");
+        };
+        assert_eq!(body.as_ref(), INPUT);
+        assert!(!res.message_head().header_map().has_key(CONTENT_ENCODING));
+    }
+
+    #[test]
+    fn test_one_try_decompress_or_sniff_restore_on_error() {
+        // gzip magic with a corrupt stream
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Encoding: br\r\n\
+                      Content-Length: 7\r\n\r\n\
+                      \x1f\x8b\x08junk";
+        let input = BytesMut::from(&input[..]);
+        let mut res = OneResponse::try_from(input).unwrap();
+        assert!(res.try_decompress_or_sniff(&mut BytesMut::new()).is_err());
+        assert_eq!(
+            res.message_head()
+                .header_map()
+                .value_of_key(CONTENT_ENCODING)
+                .unwrap(),
+            b"br"
+        );
+    }
+
+    #[test]
+    fn test_one_try_decompress_or_sniff_match() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 35\r\n\r\n\
+                     hello world hello world hello world";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.try_compress(&[ContentEncoding::Brotli]).unwrap();
+        assert!(
+            res.try_decompress_or_sniff(&mut BytesMut::new())
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(res.decoded_encodings(), [ContentEncoding::Brotli]);
+    }
+
+    #[test]
+    fn test_message_try_decompress_or_sniff() {
+        let body = compress(INPUT, &ContentEncoding::Zstd).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", "gzip");
+        let mut res = Response::builder().headers(headers).body(body).build();
+        let sniff = res
+            .try_decompress_or_sniff(&mut BytesMut::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(sniff.sniffed(), Some(&ContentEncoding::Zstd));
+        assert_eq!(res.body_as_ref().unwrap().as_ref(), INPUT);
+        assert!(!res.headers().has_key("content-encoding"));
+    }
+}
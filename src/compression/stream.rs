@@ -0,0 +1,309 @@
+use std::io;
+
+use body_plz::variants::chunked::ChunkType;
+use bytes::BytesMut;
+use header_plz::{
+    OneInfoLine, OneMessageHead,
+    body_headers::{
+        BodyHeader, content_encoding::ContentEncoding,
+        encoding_info::EncodingInfo, parse::ParseBodyHeaders,
+    },
+};
+
+use crate::{
+    Message, OneOne,
+    compression::{
+        decode::{DecodeWrite, chunk_data, decoder_chain},
+        error::StreamDecodeError,
+        limits::DecompressLimits,
+    },
+};
+
+/* Incremental decoder for a body, encoded chunks are fed with decode() as
+ * they arrive and the decoded output available so far is returned, memory
+ * use is bounded by the decoder windows instead of the body size. The total
+ * decoded size is capped by DecompressLimits::default().max_size() unless
+ * set with with_limit().
+ *
+ *      let mut decoder = res.stream_decoder().unwrap()?;
+ *      for chunk in chunks {
+ *          send(decoder.decode(&chunk)?);
+ *      }
+ *      send(decoder.finish()?);
+ */
+pub struct StreamDecoder {
+    chain: Box<dyn DecodeWrite>,
+    limit: usize,
+}
+
+impl std::fmt::Debug for StreamDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamDecoder")
+            .field("limit", &self.limit)
+            .finish_non_exhaustive()
+    }
+}
+
+// codings in the order they were applied, chunked is not a content coding
+fn body_header_encodings(bh: &BodyHeader) -> Vec<ContentEncoding> {
+    [bh.content_encoding.as_ref(), bh.transfer_encoding.as_ref()]
+        .into_iter()
+        .flatten()
+        .flat_map(|infos| infos.iter().flat_map(EncodingInfo::encodings))
+        .filter(|e| **e != ContentEncoding::Chunked)
+        .cloned()
+        .collect()
+}
+
+impl StreamDecoder {
+    /* Description:
+     *      Decoder for encodings, listed in the order they were applied,
+     *      as in Content-Encoding.
+     *
+     * Error:
+     *      StreamDecodeError::UnsupportedEncoding
+     */
+
+    pub fn new(
+        encodings: &[ContentEncoding],
+    ) -> Result<Self, StreamDecodeError> {
+        let limit = DecompressLimits::default().max_size();
+        let chain = decoder_chain(encodings, limit).map_err(|e| {
+            StreamDecodeError::UnsupportedEncoding(e.as_ref().to_string())
+        })?;
+        Ok(StreamDecoder {
+            chain,
+            limit,
+        })
+    }
+
+    // Content-Encoding and Transfer-Encoding chain of the body headers
+    pub fn from_body_headers(
+        bh: &BodyHeader,
+    ) -> Result<Self, StreamDecodeError> {
+        Self::new(&body_header_encodings(bh))
+    }
+
+    // bound the total decoded size, replaces the default
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.chain.sink().set_limit(limit);
+        self.limit = limit;
+        self
+    }
+
+    fn map_error(&mut self, e: io::Error) -> StreamDecodeError {
+        if self.chain.sink().is_exceeded() {
+            StreamDecodeError::LimitExceeded(self.limit)
+        } else {
+            StreamDecodeError::Decode(e.to_string())
+        }
+    }
+
+    // decoded output of data, may be empty while the decoder buffers
+    pub fn decode(
+        &mut self,
+        data: &[u8],
+    ) -> Result<BytesMut, StreamDecodeError> {
+        if let Err(e) = self.chain.write_all(data) {
+            return Err(self.map_error(e));
+        }
+        Ok(self.chain.sink().take())
+    }
+
+    // data of a Body::Chunked entry, other entries yield nothing
+    pub fn decode_chunk(
+        &mut self,
+        chunk: &ChunkType,
+    ) -> Result<BytesMut, StreamDecodeError> {
+        match chunk_data(chunk) {
+            Some(data) => self.decode(data),
+            None => Ok(BytesMut::new()),
+        }
+    }
+
+    // end of input, the remaining decoded output
+    pub fn finish(mut self) -> Result<BytesMut, StreamDecodeError> {
+        if let Err(e) = self.chain.try_finish() {
+            return Err(self.map_error(e));
+        }
+        Ok(self.chain.sink().take())
+    }
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    // None if the message has no body headers
+    pub fn stream_decoder(
+        &self,
+    ) -> Option<Result<StreamDecoder, StreamDecodeError>> {
+        self.body_headers.as_ref().map(StreamDecoder::from_body_headers)
+    }
+}
+
+impl<T> Message<T> {
+    // None if the message has no body headers
+    pub fn stream_decoder(
+        &self,
+    ) -> Option<Result<StreamDecoder, StreamDecodeError>> {
+        self.body_headers.as_ref().map(StreamDecoder::from_body_headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use body_plz::variants::Body;
+    use header_plz::{HeaderMap, OneHeaderMap};
+
+    use crate::{OneResponse, Response, compression::encode::compress};
+
+    use super::*;
+
+    fn decode_in_pieces(
+        mut decoder: StreamDecoder,
+        encoded: &[u8],
+        size: usize,
+    ) -> BytesMut {
+        let mut decoded = BytesMut::new();
+        for piece in encoded.chunks(size) {
+            decoded.unsplit(decoder.decode(piece).unwrap());
+        }
+        decoded.unsplit(decoder.finish().unwrap());
+        decoded
+    }
+
+    fn input() -> Vec<u8> {
+        (0..100_000u32).flat_map(|i| (i % 251).to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_stream_decoder_single() {
+        let input = input();
+        for encoding in [
+            ContentEncoding::Gzip,
+            ContentEncoding::Deflate,
+            ContentEncoding::Brotli,
+            ContentEncoding::Zstd,
+        ] {
+            let encoded = compress(&input, &encoding).unwrap();
+            let decoder = StreamDecoder::new(&[encoding]).unwrap();
+            assert_eq!(decode_in_pieces(decoder, &encoded, 1000), input);
+        }
+    }
+
+    #[test]
+    fn test_stream_decoder_multiple() {
+        let input = input();
+        let encodings = [ContentEncoding::Zstd, ContentEncoding::Gzip];
+        let zstd = compress(&input, &encodings[0]).unwrap();
+        let encoded = compress(&zstd, &encodings[1]).unwrap();
+        let decoder = StreamDecoder::new(&encodings).unwrap();
+        assert_eq!(decode_in_pieces(decoder, &encoded, 7), input);
+    }
+
+    #[test]
+    fn test_stream_decoder_output_per_call() {
+        let input = vec![0; 1024 * 1024];
+        let encoded = compress(&input, &ContentEncoding::Zstd).unwrap();
+        let mut decoder =
+            StreamDecoder::new(&[ContentEncoding::Zstd]).unwrap();
+        let mut total = 0;
+        for piece in encoded.chunks(16) {
+            let decoded = decoder.decode(piece).unwrap();
+            assert!(decoded.len() < input.len());
+            total += decoded.len();
+        }
+        total += decoder.finish().unwrap().len();
+        assert_eq!(total, input.len());
+    }
+
+    #[test]
+    fn test_stream_decoder_limit() {
+        let input = vec![0; 1024 * 1024];
+        let encoded = compress(&input, &ContentEncoding::Gzip).unwrap();
+        let mut decoder = StreamDecoder::new(&[ContentEncoding::Gzip])
+            .unwrap()
+            .with_limit(4096);
+        let result = encoded
+            .chunks(64)
+            .try_for_each(|piece| decoder.decode(piece).map(|_| ()));
+        assert_eq!(result, Err(StreamDecodeError::LimitExceeded(4096)));
+    }
+
+    #[test]
+    fn test_stream_decoder_default_limit() {
+        let decoder = StreamDecoder::new(&[ContentEncoding::Gzip]).unwrap();
+        assert_eq!(decoder.limit, DecompressLimits::default().max_size());
+    }
+
+    #[test]
+    fn test_stream_decoder_corrupt() {
+        let mut decoder =
+            StreamDecoder::new(&[ContentEncoding::Gzip]).unwrap();
+        assert!(matches!(
+            decoder.decode(b"definitely not gzip"),
+            Err(StreamDecodeError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_stream_decoder_unsupported() {
+        assert_eq!(
+            StreamDecoder::new(&[ContentEncoding::Compress]).unwrap_err(),
+            StreamDecodeError::UnsupportedEncoding("compress".to_string())
+        );
+    }
+
+    #[test]
+    fn test_one_stream_decoder_chunked() {
+        let input = input();
+        let encoded = compress(&input, &ContentEncoding::Brotli).unwrap();
+        let head = "HTTP/1.1 200 OK\r\n\
+                    Content-Encoding: br\r\n\
+                    Transfer-Encoding: chunked\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(head)).unwrap();
+        let mut chunks = Vec::new();
+        for piece in encoded.chunks(4096) {
+            let size = format!("{:x}\r\n", piece.len());
+            chunks.push(ChunkType::Size(size.as_str().into()));
+            let mut data = BytesMut::from(piece);
+            data.extend_from_slice(b"\r\n");
+            chunks.push(ChunkType::Chunk(data));
+        }
+        chunks.push(ChunkType::LastChunk("0\r\n".into()));
+        chunks.push(ChunkType::Trailers(OneHeaderMap::from(HeaderMap::new())));
+        res.body = Some(Body::Chunked(chunks));
+
+        let mut decoder = res.stream_decoder().unwrap().unwrap();
+        let mut decoded = BytesMut::new();
+        let Some(Body::Chunked(chunks)) = res.body() else {
+            panic!("expected chunked body");
+        };
+        for chunk in chunks {
+            decoded.unsplit(decoder.decode_chunk(chunk).unwrap());
+        }
+        decoded.unsplit(decoder.finish().unwrap());
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn test_message_stream_decoder() {
+        let mut encoder = flate2::write::DeflateEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(b"hello world").unwrap();
+        let encoded = encoder.finish().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", "identity, deflate");
+        let res = Response::builder().headers(headers).build();
+        let decoder = res.stream_decoder().unwrap().unwrap();
+        assert_eq!(decode_in_pieces(decoder, &encoded, 3), "hello world");
+
+        assert!(Response::builder().build().stream_decoder().is_none());
+    }
+}
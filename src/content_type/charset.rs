@@ -0,0 +1,294 @@
+use crate::content_type::error::TextError;
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16_LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16_BE_BOM: &[u8] = &[0xfe, 0xff];
+// HTML Standard 13.2.3.2, prescan the first 1024 bytes
+const META_PRESCAN_LEN: usize = 1024;
+
+// windows-1252 0x80 - 0x9f, undefined bytes map to the C1 control
+const WINDOWS_1252_C1: [char; 32] = [
+    '\u{20ac}', '\u{0081}', '\u{201a}', '\u{0192}', '\u{201e}', '\u{2026}',
+    '\u{2020}', '\u{2021}', '\u{02c6}', '\u{2030}', '\u{0160}', '\u{2039}',
+    '\u{0152}', '\u{008d}', '\u{017d}', '\u{008f}', '\u{0090}', '\u{2018}',
+    '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02dc}', '\u{2122}', '\u{0161}', '\u{203a}', '\u{0153}', '\u{009d}',
+    '\u{017e}', '\u{0178}',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1252,
+    Ascii,
+}
+
+impl Charset {
+    // case insensitive, common aliases
+    pub fn from_label(label: &str) -> Option<Self> {
+        let charset = match label.trim().to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => Charset::Utf8,
+            "utf-16le" | "utf-16" => Charset::Utf16Le,
+            "utf-16be" => Charset::Utf16Be,
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "l1" => {
+                Charset::Latin1
+            }
+            "windows-1252" | "cp1252" | "x-cp1252" => Charset::Windows1252,
+            "us-ascii" | "ascii" => Charset::Ascii,
+            _ => return None,
+        };
+        Some(charset)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Utf16Le => "utf-16le",
+            Charset::Utf16Be => "utf-16be",
+            Charset::Latin1 => "iso-8859-1",
+            Charset::Windows1252 => "windows-1252",
+            Charset::Ascii => "us-ascii",
+        }
+    }
+
+    pub fn bom(&self) -> &'static [u8] {
+        match self {
+            Charset::Utf8 => UTF8_BOM,
+            Charset::Utf16Le => UTF16_LE_BOM,
+            Charset::Utf16Be => UTF16_BE_BOM,
+            _ => &[],
+        }
+    }
+
+    fn invalid(&self, position: usize) -> TextError {
+        TextError::InvalidText {
+            charset: self.as_str(),
+            position,
+        }
+    }
+
+    fn unencodable(&self, ch: char) -> TextError {
+        TextError::Unencodable {
+            charset: self.as_str(),
+            ch,
+        }
+    }
+
+    // strict, the byte offset of the first invalid sequence is reported
+    pub fn decode(&self, data: &[u8]) -> Result<String, TextError> {
+        match self {
+            Charset::Utf8 => std::str::from_utf8(data)
+                .map(str::to_string)
+                .map_err(|e| self.invalid(e.valid_up_to())),
+            Charset::Utf16Le | Charset::Utf16Be => {
+                if !data.len().is_multiple_of(2) {
+                    return Err(self.invalid(data.len() - 1));
+                }
+                let units = data.chunks_exact(2).map(|pair| {
+                    let pair = [pair[0], pair[1]];
+                    match self {
+                        Charset::Utf16Le => u16::from_le_bytes(pair),
+                        _ => u16::from_be_bytes(pair),
+                    }
+                });
+                let mut text = String::with_capacity(data.len() / 2);
+                let mut position = 0;
+                for ch in char::decode_utf16(units) {
+                    let ch = ch.map_err(|_| self.invalid(position))?;
+                    position += ch.len_utf16() * 2;
+                    text.push(ch);
+                }
+                Ok(text)
+            }
+            Charset::Latin1 => {
+                Ok(data.iter().map(|b| char::from(*b)).collect())
+            }
+            Charset::Windows1252 => Ok(data
+                .iter()
+                .map(|b| match b {
+                    0x80..=0x9f => WINDOWS_1252_C1[(b - 0x80) as usize],
+                    _ => char::from(*b),
+                })
+                .collect()),
+            Charset::Ascii => match data.iter().position(|b| !b.is_ascii()) {
+                Some(position) => Err(self.invalid(position)),
+                None => Ok(data.iter().map(|b| char::from(*b)).collect()),
+            },
+        }
+    }
+
+    // without a BOM
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, TextError> {
+        match self {
+            Charset::Utf8 => Ok(text.as_bytes().to_vec()),
+            Charset::Utf16Le => {
+                Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect())
+            }
+            Charset::Utf16Be => {
+                Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect())
+            }
+            Charset::Latin1 => text
+                .chars()
+                .map(|ch| u8::try_from(ch).map_err(|_| self.unencodable(ch)))
+                .collect(),
+            Charset::Windows1252 => text
+                .chars()
+                .map(|ch| {
+                    if let Some(pos) =
+                        WINDOWS_1252_C1.iter().position(|c| *c == ch)
+                    {
+                        return Ok(0x80 + pos as u8);
+                    }
+                    match u8::try_from(ch) {
+                        Ok(b) if !(0x80..=0x9f).contains(&b) => Ok(b),
+                        _ => Err(self.unencodable(ch)),
+                    }
+                })
+                .collect(),
+            Charset::Ascii => text
+                .chars()
+                .map(|ch| match ch.is_ascii() {
+                    true => Ok(ch as u8),
+                    false => Err(self.unencodable(ch)),
+                })
+                .collect(),
+        }
+    }
+}
+
+// UTF-8 and UTF-16 byte order marks
+pub fn sniff_bom(data: &[u8]) -> Option<Charset> {
+    [Charset::Utf8, Charset::Utf16Le, Charset::Utf16Be]
+        .into_iter()
+        .find(|charset| data.starts_with(charset.bom()))
+}
+
+/* Description:
+ *      charset label declared by a <meta> in the first 1024 bytes of an
+ *      HTML document, either
+ *
+ *          <meta charset="utf-8">
+ *          <meta http-equiv="Content-Type" content="text/html; charset=x">
+ *
+ *      Simplified prescan, the first "charset=" after a "<meta" is used.
+ */
+
+pub(crate) fn sniff_meta_charset(data: &[u8]) -> Option<String> {
+    let prefix = &data[..data.len().min(META_PRESCAN_LEN)];
+    let lower = prefix.to_ascii_lowercase();
+    let mut rest = lower.as_slice();
+    while let Some(start) = find(rest, b"<meta") {
+        rest = &rest[start + 5..];
+        let end = rest.iter().position(|b| *b == b'>').unwrap_or(rest.len());
+        let tag = &rest[..end];
+        if let Some(pos) = find(tag, b"charset=") {
+            let value = tag[pos + 8..]
+                .iter()
+                .skip_while(|b| matches!(b, b'"' | b'\'' | b' '))
+                .take_while(|b| {
+                    !matches!(b, b'"' | b'\'' | b' ' | b';' | b'/' | b'>')
+                })
+                .map(|b| char::from(*b))
+                .collect::<String>();
+            if !value.is_empty() {
+                return Some(value);
+            }
+        }
+        rest = &rest[end..];
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "caf\u{e9} \u{20ac}5";
+
+    #[test]
+    fn test_charset_from_label() {
+        assert_eq!(Charset::from_label(" UTF8 "), Some(Charset::Utf8));
+        assert_eq!(Charset::from_label("Latin1"), Some(Charset::Latin1));
+        assert_eq!(Charset::from_label("cp1252"), Some(Charset::Windows1252));
+        assert_eq!(Charset::from_label("koi8-r"), None);
+    }
+
+    #[test]
+    fn test_charset_round_trip() {
+        for charset in [
+            Charset::Utf8,
+            Charset::Utf16Le,
+            Charset::Utf16Be,
+            Charset::Windows1252,
+        ] {
+            let encoded = charset.encode(TEXT).unwrap();
+            assert_eq!(charset.decode(&encoded).unwrap(), TEXT);
+        }
+        assert_eq!(
+            Charset::Windows1252.encode(TEXT).unwrap(),
+            b"caf\xe9 \x805"
+        );
+        assert_eq!(
+            Charset::Utf16Be.decode(&[0xd8, 0x3d, 0xde, 0x00]).unwrap(),
+            "\u{1f600}"
+        );
+    }
+
+    #[test]
+    fn test_charset_latin1() {
+        assert_eq!(Charset::Latin1.decode(b"caf\xe9").unwrap(), "caf\u{e9}");
+        assert_eq!(
+            Charset::Latin1.encode(TEXT),
+            Err(TextError::Unencodable {
+                charset: "iso-8859-1",
+                ch: '\u{20ac}'
+            })
+        );
+    }
+
+    #[test]
+    fn test_charset_decode_invalid() {
+        assert_eq!(
+            Charset::Utf8.decode(b"ok\xff"),
+            Err(TextError::InvalidText {
+                charset: "utf-8",
+                position: 2
+            })
+        );
+        assert_eq!(
+            Charset::Ascii.decode(b"a\x80"),
+            Err(TextError::InvalidText {
+                charset: "us-ascii",
+                position: 1
+            })
+        );
+        assert!(Charset::Utf16Le.decode(b"abc").is_err());
+        // lone surrogate
+        assert!(Charset::Utf16Le.decode(&[0x00, 0xd8]).is_err());
+    }
+
+    #[test]
+    fn test_sniff_bom() {
+        assert_eq!(sniff_bom(b"\xef\xbb\xbfhi"), Some(Charset::Utf8));
+        assert_eq!(sniff_bom(b"\xff\xfeh\x00"), Some(Charset::Utf16Le));
+        assert_eq!(sniff_bom(b"\xfe\xff\x00h"), Some(Charset::Utf16Be));
+        assert_eq!(sniff_bom(b"hi"), None);
+    }
+
+    #[test]
+    fn test_sniff_meta_charset() {
+        let html = b"<html><head><META Charset=\"Windows-1252\">";
+        assert_eq!(sniff_meta_charset(html).unwrap(), "windows-1252");
+        let html = b"<meta name=x><meta http-equiv=\"Content-Type\" \
+                     content=\"text/html; charset=ISO-8859-1\">";
+        assert_eq!(sniff_meta_charset(html).unwrap(), "iso-8859-1");
+        assert!(sniff_meta_charset(b"<p>charset=utf-8</p>").is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ContentTypeError {
+    #[error("Invalid Media Type| {0}")]
+    InvalidMediaType(String),
+    #[error("Invalid Param| {0}")]
+    InvalidParam(String),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TextError {
+    #[error("Unsupported Charset| {0}")]
+    UnsupportedCharset(String),
+    #[error("Invalid Text| {charset}| {position}")]
+    InvalidText {
+        charset: &'static str,
+        position: usize,
+    },
+    #[error("Unencodable Character| {charset}| {ch}")]
+    Unencodable {
+        charset: &'static str,
+        ch: char,
+    },
+    #[error("Content Encoded Body| {0}")]
+    ContentEncoded(String),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MultipartError {
+    #[error("Missing Content-Type")]
+    MissingContentType,
+    #[error("Content-Type| {0}")]
+    ContentType(#[from] ContentTypeError),
+    #[error("Not Multipart| {0}")]
+    NotMultipart(String),
+    #[error("Missing Boundary")]
+    MissingBoundary,
+    #[error("Invalid Boundary| {0}")]
+    InvalidBoundary(String),
+    #[error("Boundary In Content")]
+    BoundaryInContent,
+    #[error("Invalid Part Header| {0}")]
+    InvalidHeader(String),
+    #[error("Content Encoded Body| {0}")]
+    ContentEncoded(String),
+    #[error("Missing Delimiter")]
+    MissingDelimiter,
+    #[error("Missing Close Delimiter")]
+    MissingCloseDelimiter,
+    #[error("Invalid Part| {0}")]
+    InvalidPart(usize),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum FormError {
+    #[error("Missing Content-Type")]
+    MissingContentType,
+    #[error("Content-Type| {0}")]
+    ContentType(#[from] ContentTypeError),
+    #[error("Not Url Encoded Form| {0}")]
+    NotUrlEncoded(String),
+    #[error("Content Encoded Body| {0}")]
+    ContentEncoded(String),
+}
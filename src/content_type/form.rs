@@ -0,0 +1,430 @@
+use std::fmt;
+
+use body_plz::variants::Body;
+use bytes::BytesMut;
+use header_plz::{
+    OneRequestLine,
+    body_headers::parse::ParseBodyHeaders,
+    const_headers::{CONTENT_LENGTH, CONTENT_TYPE},
+    message_head::header_map::{HMap, Hmap},
+};
+
+use crate::{
+    OneOne, Request,
+    compression::decode::body_encodings,
+    content_type::{
+        check_not_encoded,
+        error::{ContentTypeError, FormError},
+        media_type::MediaType,
+    },
+    utils::{header::set_value, percent},
+};
+
+pub const FORM_URLENCODED: &str = "application/x-www-form-urlencoded";
+
+// WHATWG URL 5.1, "+" is a space
+fn decode_component(value: &[u8]) -> String {
+    let value: Vec<u8> = value
+        .iter()
+        .map(|b| match b {
+            b'+' => b' ',
+            b => *b,
+        })
+        .collect();
+    String::from_utf8_lossy(&percent::decode(&value)).into_owned()
+}
+
+// WHATWG URL 5.2, space is "+", everything but alphanumerics and "*-._"
+// is escaped
+fn encode_component(value: &str) -> String {
+    percent::encode(value.as_bytes(), |b| {
+        b.is_ascii_alphanumeric() || b"*-._ ".contains(&b)
+    })
+    .replace(' ', "+")
+}
+
+/* Pairs of an application/x-www-form-urlencoded body.
+ *
+ *      user=admin&tag=a&tag=b+c
+ *      [("user", "admin"), ("tag", "a"), ("tag", "b c")]
+ *
+ * Order and duplicate names are kept.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlEncodedForm {
+    pairs: Vec<(String, String)>,
+}
+
+impl UrlEncodedForm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pairs(&self) -> &[(String, String)] {
+        &self.pairs
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    // first value of name, case sensitive
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_all<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.pairs.push((name.to_string(), value.to_string()));
+    }
+
+    // replaces the first value of name and removes the rest, appends if
+    // missing
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.pairs.iter().position(|(n, _)| n == name) {
+            Some(pos) => {
+                self.pairs[pos].1 = value.to_string();
+                let mut index = 0;
+                self.pairs.retain(|(n, _)| {
+                    index += 1;
+                    index - 1 <= pos || n != name
+                });
+            }
+            None => self.append(name, value),
+        }
+    }
+
+    // true if any pair was removed
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.pairs.len();
+        self.pairs.retain(|(n, _)| n != name);
+        self.pairs.len() != len
+    }
+
+    pub fn to_bytes(&self) -> BytesMut {
+        BytesMut::from(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for UrlEncodedForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (name, value)) in self.pairs.iter().enumerate() {
+            if index > 0 {
+                f.write_str("&")?;
+            }
+            write!(
+                f,
+                "{}={}",
+                encode_component(name),
+                encode_component(value)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for UrlEncodedForm
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        UrlEncodedForm {
+            pairs: iter
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+/* Description:
+ *      Parse an application/x-www-form-urlencoded body, WHATWG URL 5.1.
+ *
+ * Steps:
+ *      1. Split on "&", empty sequences are skipped.
+ *      2. Split on the first "=", a missing "=" is an empty value.
+ *      3. Replace "+" with space and percent decode, invalid UTF-8 is
+ *         replaced with U+FFFD.
+ */
+
+pub fn parse_form_urlencoded(body: &[u8]) -> UrlEncodedForm {
+    body.split(|b| *b == b'&')
+        .filter(|sequence| !sequence.is_empty())
+        .map(|sequence| {
+            let (name, value) = match sequence.iter().position(|b| *b == b'=')
+            {
+                Some(pos) => (&sequence[..pos], &sequence[pos + 1..]),
+                None => (sequence, &[][..]),
+            };
+            (decode_component(name), decode_component(value))
+        })
+        .collect()
+}
+
+fn check_form_content_type(
+    media_type: Option<Result<MediaType, ContentTypeError>>,
+) -> Result<(), FormError> {
+    let media_type = media_type.ok_or(FormError::MissingContentType)??;
+    match media_type.essence() == FORM_URLENCODED {
+        true => Ok(()),
+        false => Err(FormError::NotUrlEncoded(media_type.essence())),
+    }
+}
+
+// Content-Length: 0 frames an empty body, not a missing one
+fn is_zero_length<T>(header_map: &HMap<T>) -> bool
+where
+    T: Hmap,
+{
+    header_map
+        .value_of_key(CONTENT_LENGTH)
+        .is_some_and(|value| value.trim_ascii() == b"0")
+}
+
+impl OneOne<OneRequestLine> {
+    /* Description:
+     *      Pairs of an application/x-www-form-urlencoded body.
+     *
+     * Returns:
+     *      None if the request has no body, Content-Length: 0 is an empty
+     *      form.
+     *
+     * Error:
+     *      FormError::ContentEncoded, call try_decompress() first.
+     *      FormError::MissingContentType
+     *      FormError::ContentType
+     *      FormError::NotUrlEncoded
+     */
+
+    pub fn form(&self) -> Option<Result<UrlEncodedForm, FormError>> {
+        if self.body.is_none()
+            && !is_zero_length(self.message_head.header_map())
+        {
+            return None;
+        }
+        let body = self.body_data().unwrap_or_default();
+        let form = check_not_encoded(&self.body_encodings())
+            .map_err(FormError::ContentEncoded)
+            .and_then(|_| check_form_content_type(self.content_type()))
+            .map(|_| parse_form_urlencoded(&body));
+        Some(form)
+    }
+
+    /* Description:
+     *      Replace the body with the serialized form.
+     *
+     * Steps:
+     *      1. Convert a chunked body to raw.
+     *      2. Set Content-Type to application/x-www-form-urlencoded, the
+     *         previous type no longer describes the body, and
+     *         Content-Length.
+     *      3. Set the body and parse the body headers again.
+     *
+     * Error:
+     *      FormError::ContentEncoded
+     */
+
+    pub fn set_form(
+        &mut self,
+        form: &UrlEncodedForm,
+    ) -> Result<(), FormError> {
+        check_not_encoded(&self.body_encodings())
+            .map_err(FormError::ContentEncoded)?;
+        let body = form.to_bytes();
+        self.chunked_body_to_raw();
+        let header_map = self.message_head.header_map_as_mut();
+        set_value(header_map, CONTENT_TYPE, FORM_URLENCODED);
+        set_value(header_map, CONTENT_LENGTH, &body.len().to_string());
+        self.body = Some(Body::Raw(body));
+        self.body_headers = self.message_head.parse_body_headers();
+        Ok(())
+    }
+}
+
+impl Request {
+    // OneOne::form()
+    pub fn form(&self) -> Option<Result<UrlEncodedForm, FormError>> {
+        if self.body_as_ref().is_none() && !is_zero_length(&self.headers) {
+            return None;
+        }
+        let body = self.body_as_ref().map(AsRef::as_ref).unwrap_or_default();
+        let form = check_not_encoded(&body_encodings(&self.headers))
+            .map_err(FormError::ContentEncoded)
+            .and_then(|_| check_form_content_type(self.content_type()))
+            .map(|_| parse_form_urlencoded(body));
+        Some(form)
+    }
+
+    // OneOne::set_form(), Content-Length is updated only if present
+    pub fn set_form(
+        &mut self,
+        form: &UrlEncodedForm,
+    ) -> Result<(), FormError> {
+        check_not_encoded(&body_encodings(&self.headers))
+            .map_err(FormError::ContentEncoded)?;
+        let body = form.to_bytes();
+        set_value(&mut self.headers, CONTENT_TYPE, FORM_URLENCODED);
+        self.headers.update_header_value_on_key(
+            CONTENT_LENGTH,
+            body.len().to_string().as_bytes(),
+        );
+        self.set_body(body);
+        self.update_body_headers();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OneRequest;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_form_urlencoded() {
+        let form = parse_form_urlencoded(
+            b"user=admin&&tag=a&tag=b+c%26d&flag&=x&bad=%zz%FF",
+        );
+        assert_eq!(
+            form.pairs(),
+            [
+                ("user".into(), "admin".into()),
+                ("tag".into(), "a".into()),
+                ("tag".into(), "b c&d".into()),
+                ("flag".into(), "".into()),
+                ("".into(), "x".into()),
+                ("bad".into(), "%zz\u{fffd}".into()),
+            ]
+        );
+        assert_eq!(form.get("tag"), Some("a"));
+        assert_eq!(form.get_all("tag").collect::<Vec<_>>(), ["a", "b c&d"]);
+        assert!(form.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_form_urlencoded_serialize() {
+        let form: UrlEncodedForm =
+            [("a b", "1+1=2"), ("x", "caf\u{e9}/*-._~")].into_iter().collect();
+        let serialized = form.to_string();
+        assert_eq!(serialized, "a+b=1%2B1%3D2&x=caf%C3%A9%2F*-._%7E");
+        assert_eq!(parse_form_urlencoded(serialized.as_bytes()), form);
+    }
+
+    #[test]
+    fn test_form_urlencoded_edit() {
+        let mut form = parse_form_urlencoded(b"a=1&b=2&a=3&c=4&a=5");
+        form.set("a", "x");
+        assert_eq!(form.to_string(), "a=x&b=2&c=4");
+        form.set("d", "y");
+        assert_eq!(form.to_string(), "a=x&b=2&c=4&d=y");
+        assert!(form.remove("b"));
+        assert!(!form.remove("b"));
+        form.append("a", "z");
+        assert_eq!(form.to_string(), "a=x&c=4&d=y&a=z");
+        assert_eq!(form.len(), 4);
+    }
+
+    #[test]
+    fn test_one_form() {
+        let input = "POST /login HTTP/1.1\r\n\
+                     Content-Type: application/x-www-form-urlencoded\r\n\
+                     Content-Length: 16\r\n\r\n\
+                     user=a&pass=b%21";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let mut form = req.form().unwrap().unwrap();
+        assert_eq!(form.get("pass"), Some("b!"));
+        form.set("user", "admin user");
+        req.set_form(&form).unwrap();
+        let verify = "POST /login HTTP/1.1\r\n\
+                      Content-Type: application/x-www-form-urlencoded\r\n\
+                      Content-Length: 25\r\n\r\n\
+                      user=admin+user&pass=b%21";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_one_form_errors() {
+        let input = "POST / HTTP/1.1\r\n\
+                     Content-Type: application/json\r\n\
+                     Content-Length: 2\r\n\r\n\
+                     {}";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.form().unwrap(),
+            Err(FormError::NotUrlEncoded("application/json".to_string()))
+        );
+        let form: UrlEncodedForm = [("a", "1")].into_iter().collect();
+        req.set_form(&form).unwrap();
+        let verify = "POST / HTTP/1.1\r\n\
+                      Content-Type: application/x-www-form-urlencoded\r\n\
+                      Content-Length: 3\r\n\r\n\
+                      a=1";
+        assert_eq!(req.into_bytes(), verify);
+
+        let input = "POST / HTTP/1.1\r\n\
+                     Content-Type: application/x-www-form-urlencoded\r\n\
+                     Transfer-Encoding: gzip, chunked\r\n\r\n\
+                     1\r\n\
+                     x\r\n\
+                     0\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.form().unwrap(),
+            Err(FormError::ContentEncoded("gzip".to_string()))
+        );
+        assert_eq!(
+            req.set_form(&form),
+            Err(FormError::ContentEncoded("gzip".to_string()))
+        );
+
+        let input = "POST / HTTP/1.1\r\n\
+                     Content-Type: application/x-www-form-urlencoded\r\n\
+                     Content-Length: 0\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.form().unwrap().unwrap().is_empty());
+        assert!(Request::from(req).form().unwrap().unwrap().is_empty());
+
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.form().is_none());
+        let form: UrlEncodedForm = [("q", "1")].into_iter().collect();
+        req.set_form(&form).unwrap();
+        let verify = "GET / HTTP/1.1\r\n\
+                      content-type: application/x-www-form-urlencoded\r\n\
+                      content-length: 3\r\n\r\n\
+                      q=1";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_request_form() {
+        let form: UrlEncodedForm =
+            [("a", "1"), ("a", "2")].into_iter().collect();
+        let mut req = Request::builder().form(form).build();
+        assert_eq!(
+            req.headers().value_of_key(CONTENT_TYPE).unwrap(),
+            FORM_URLENCODED.as_bytes()
+        );
+        let mut form = req.form().unwrap().unwrap();
+        assert_eq!(form.get_all("a").collect::<Vec<_>>(), ["1", "2"]);
+        form.remove("a");
+        form.append("b", "x y");
+        req.set_form(&form).unwrap();
+        assert_eq!(req.body_as_ref().unwrap().as_ref(), b"b=x+y");
+        assert_eq!(req.headers().value_of_key(CONTENT_LENGTH).unwrap(), b"5");
+    }
+}
@@ -0,0 +1,277 @@
+use std::fmt;
+
+use crate::{
+    content_type::error::ContentTypeError,
+    utils::{
+        quoted::{is_quotable, quote, split_unquoted, unquote},
+        token::is_token,
+    },
+};
+
+const CHARSET: &str = "charset";
+
+/* Parsed Content-Type, RFC 9110 8.3.1.
+ *
+ *      image/svg+xml; charset="utf-8"
+ *      main_type   : image
+ *      subtype     : svg
+ *      suffix      : xml, RFC 6838 4.2.8
+ *      params      : [("charset", "utf-8")]
+ *
+ * Type, subtype, suffix and parameter names are lowercase, parameter values
+ * are kept as sent without the quotes.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    main_type: String,
+    subtype: String,
+    suffix: Option<String>,
+    params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    /* Description:
+     *      type/subtype, a "+" in subtype starts the suffix.
+     *
+     * Error:
+     *      ContentTypeError::InvalidMediaType  [type or subtype not a token]
+     */
+
+    pub fn new(
+        main_type: &str,
+        subtype: &str,
+    ) -> Result<Self, ContentTypeError> {
+        if !is_token(main_type.as_bytes()) || !is_token(subtype.as_bytes()) {
+            return Err(ContentTypeError::InvalidMediaType(format!(
+                "{main_type}/{subtype}"
+            )));
+        }
+        let subtype = subtype.to_ascii_lowercase();
+        let (subtype, suffix) = match subtype.rsplit_once('+') {
+            Some((subtype, suffix)) if !subtype.is_empty() => {
+                (subtype.to_string(), Some(suffix.to_string()))
+            }
+            _ => (subtype, None),
+        };
+        Ok(MediaType {
+            main_type: main_type.to_ascii_lowercase(),
+            subtype,
+            suffix,
+            params: Vec::new(),
+        })
+    }
+
+    /* Description:
+     *      Add a parameter, replaces a parameter with the same name.
+     *
+     * Error:
+     *      ContentTypeError::InvalidParam
+     *          name is not a token or value has a control character.
+     */
+
+    pub fn with_param(
+        mut self,
+        name: &str,
+        value: &str,
+    ) -> Result<Self, ContentTypeError> {
+        if !is_token(name.as_bytes()) || !is_quotable(value) {
+            return Err(ContentTypeError::InvalidParam(format!(
+                "{name}={value}"
+            )));
+        }
+        let name = name.to_ascii_lowercase();
+        self.params.retain(|(n, _)| *n != name);
+        self.params.push((name, value.to_string()));
+        Ok(self)
+    }
+
+    pub fn main_type(&self) -> &str {
+        &self.main_type
+    }
+
+    pub fn subtype(&self) -> &str {
+        &self.subtype
+    }
+
+    pub fn suffix(&self) -> Option<&str> {
+        self.suffix.as_deref()
+    }
+
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    // first parameter with name, case insensitive
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.param(CHARSET)
+    }
+
+    // type/subtype[+suffix] without parameters
+    pub fn essence(&self) -> String {
+        match &self.suffix {
+            Some(suffix) => {
+                format!("{}/{}+{}", self.main_type, self.subtype, suffix)
+            }
+            None => format!("{}/{}", self.main_type, self.subtype),
+        }
+    }
+
+    pub fn is_html(&self) -> bool {
+        self.main_type == "text" && self.subtype == "html"
+    }
+
+    pub fn to_header_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.essence())?;
+        for (name, value) in self.params.iter() {
+            write!(f, "; {}={}", name, quote(value))?;
+        }
+        Ok(())
+    }
+}
+
+/* Description:
+ *      Parse a Content-Type header value.
+ *
+ * Steps:
+ *      1. Split on unquoted ";".
+ *      2. First part is "type/subtype", both tokens.
+ *      3. Remaining parts are "name=value" parameters, value is a token or
+ *         a quoted-string. Malformed parameters, and values with a control
+ *         character, are skipped.
+ *
+ * Error:
+ *      ContentTypeError::InvalidMediaType      [2]
+ */
+
+pub fn parse_content_type(
+    value: &[u8],
+) -> Result<MediaType, ContentTypeError> {
+    let value = std::str::from_utf8(value).map_err(|_| {
+        ContentTypeError::InvalidMediaType(
+            String::from_utf8_lossy(value).to_string(),
+        )
+    })?;
+    let mut parts = split_unquoted(value, b';').into_iter().map(str::trim);
+    let essence = parts.next().unwrap_or_default();
+    let mut media_type = match essence.split_once('/') {
+        Some((main_type, subtype))
+            if is_token(main_type.as_bytes())
+                && is_token(subtype.as_bytes()) =>
+        {
+            MediaType::new(main_type, subtype)?
+        }
+        _ => {
+            return Err(ContentTypeError::InvalidMediaType(
+                essence.to_string(),
+            ));
+        }
+    };
+    for param in parts {
+        let Some((name, value)) = param.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        let value = unquote(value.trim());
+        if !is_token(name.as_bytes()) || !is_quotable(&value) {
+            continue;
+        }
+        media_type.params.push((name.to_ascii_lowercase(), value));
+    }
+    Ok(media_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_type() {
+        let media_type =
+            parse_content_type(b"Text/HTML; Charset=\"UTF-8\"; foo=bar")
+                .unwrap();
+        assert_eq!(media_type.main_type(), "text");
+        assert_eq!(media_type.subtype(), "html");
+        assert!(media_type.suffix().is_none());
+        assert_eq!(media_type.charset(), Some("UTF-8"));
+        assert_eq!(media_type.param("FOO"), Some("bar"));
+        assert!(media_type.is_html());
+    }
+
+    #[test]
+    fn test_parse_content_type_suffix() {
+        let media_type =
+            parse_content_type(b"application/vnd.api+json").unwrap();
+        assert_eq!(media_type.subtype(), "vnd.api");
+        assert_eq!(media_type.suffix(), Some("json"));
+        assert_eq!(media_type.essence(), "application/vnd.api+json");
+    }
+
+    #[test]
+    fn test_parse_content_type_quoted_params() {
+        let media_type = parse_content_type(
+            b"multipart/form-data; boundary=\"a;b\\\"c\"; bad; =x",
+        )
+        .unwrap();
+        assert_eq!(
+            media_type.params(),
+            [("boundary".into(), "a;b\"c".into())]
+        );
+    }
+
+    #[test]
+    fn test_parse_content_type_invalid() {
+        for value in ["", "text", "text/", "/html", "te xt/html"] {
+            assert_eq!(
+                parse_content_type(value.as_bytes()),
+                Err(ContentTypeError::InvalidMediaType(value.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_media_type_to_header_value() {
+        let media_type = MediaType::new("Text", "Plain")
+            .and_then(|m| m.with_param("charset", "utf-8"))
+            .and_then(|m| m.with_param("format", "a b"))
+            .and_then(|m| m.with_param("Charset", "iso-8859-1"))
+            .unwrap();
+        assert_eq!(
+            media_type.to_header_value(),
+            "text/plain; format=\"a b\"; charset=iso-8859-1"
+        );
+    }
+
+    #[test]
+    fn test_media_type_injection() {
+        assert_eq!(
+            MediaType::new("text", "plain\r\nX-Injected: 1"),
+            Err(ContentTypeError::InvalidMediaType(
+                "text/plain\r\nX-Injected: 1".to_string()
+            ))
+        );
+        let media_type = MediaType::new("text", "plain").unwrap();
+        assert_eq!(
+            media_type.clone().with_param("charset", "x\r\nX-Injected: 1"),
+            Err(ContentTypeError::InvalidParam(
+                "charset=x\r\nX-Injected: 1".to_string()
+            ))
+        );
+        assert!(media_type.with_param("a b", "c").is_err());
+        let media_type =
+            parse_content_type(b"text/plain; a=\"x\x01y\"; b=c").unwrap();
+        assert_eq!(media_type.params(), [("b".into(), "c".into())]);
+    }
+}
@@ -0,0 +1,381 @@
+use body_plz::variants::Body;
+use bytes::BytesMut;
+use header_plz::{
+    OneInfoLine, OneMessageHead,
+    body_headers::parse::ParseBodyHeaders,
+    const_headers::{CONTENT_LENGTH, CONTENT_TYPE},
+};
+
+use crate::{
+    Message, OneOne, compression::decode::body_encodings,
+    utils::header::set_value,
+};
+
+pub mod charset;
+pub mod error;
+pub mod form;
+pub mod media_type;
+pub mod multipart;
+
+use charset::{Charset, sniff_bom, sniff_meta_charset};
+use error::{ContentTypeError, TextError};
+use media_type::{MediaType, parse_content_type};
+
+/* Description:
+ *      Charset of body and whether it starts with a byte order mark.
+ *
+ * Steps:
+ *      1. BOM, it wins over any declared charset.
+ *      2. charset parameter of Content-Type.
+ *      3. <meta> charset of an HTML body, or a body without Content-Type.
+ *      4. UTF-8.
+ *
+ * Error:
+ *      TextError::UnsupportedCharset           [2] [3]
+ */
+
+fn resolve_charset(
+    media_type: Option<&MediaType>,
+    body: &[u8],
+) -> Result<(Charset, bool), TextError> {
+    if let Some(charset) = sniff_bom(body) {
+        return Ok((charset, true));
+    }
+    let label = match media_type {
+        Some(media_type) if media_type.charset().is_some() => {
+            media_type.charset().map(str::to_string)
+        }
+        Some(media_type) if !media_type.is_html() => None,
+        _ => sniff_meta_charset(body),
+    };
+    match label {
+        Some(label) => Charset::from_label(&label)
+            .map(|charset| (charset, false))
+            .ok_or(TextError::UnsupportedCharset(label)),
+        None => Ok((Charset::Utf8, false)),
+    }
+}
+
+fn decode_text(
+    media_type: Option<&MediaType>,
+    body: &[u8],
+) -> Result<String, TextError> {
+    let (charset, has_bom) = resolve_charset(media_type, body)?;
+    let bom_len = if has_bom {
+        charset.bom().len()
+    } else {
+        0
+    };
+    charset.decode(&body[bom_len..])
+}
+
+// in the charset of the current body, the BOM is kept
+fn encode_text(
+    media_type: Option<&MediaType>,
+    body: &[u8],
+    text: &str,
+) -> Result<BytesMut, TextError> {
+    let (charset, has_bom) = resolve_charset(media_type, body)?;
+    let mut encoded = BytesMut::new();
+    if has_bom {
+        encoded.extend_from_slice(charset.bom());
+    }
+    encoded.extend_from_slice(&charset.encode(text)?);
+    Ok(encoded)
+}
+
+// a content-coded body is not readable, Err with the codings
+fn check_not_encoded(encodings: &[impl AsRef<str>]) -> Result<(), String> {
+    if encodings.is_empty() {
+        return Ok(());
+    }
+    let encodings: Vec<&str> = encodings.iter().map(AsRef::as_ref).collect();
+    Err(encodings.join(", "))
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    // None if Content-Type is missing
+    pub fn content_type(&self) -> Option<Result<MediaType, ContentTypeError>> {
+        self.message_head
+            .header_map()
+            .value_of_key(CONTENT_TYPE)
+            .map(parse_content_type)
+    }
+
+    /* Description:
+     *      Body decoded to a String with its charset, see resolve_charset().
+     *      An invalid Content-Type is treated as missing. The BOM is
+     *      removed.
+     *
+     * Returns:
+     *      None if the message has no body.
+     *
+     * Error:
+     *      TextError::ContentEncoded, call try_decompress() first.
+     *      TextError::UnsupportedCharset
+     *      TextError::InvalidText
+     */
+
+    pub fn body_text(&self) -> Option<Result<String, TextError>> {
+        self.body.as_ref()?;
+        if let Err(e) = check_not_encoded(&self.body_encodings()) {
+            return Some(Err(TextError::ContentEncoded(e)));
+        }
+        let body = self.body_data().unwrap_or_default();
+        let media_type = self.content_type().and_then(Result::ok);
+        Some(decode_text(media_type.as_ref(), &body))
+    }
+
+    /* Description:
+     *      Replace the body with text, encoded in the charset body_text()
+     *      would decode the current body with.
+     *
+     * Steps:
+     *      1. Encode text, on error the message is left untouched.
+     *      2. Convert a chunked body to raw.
+     *      3. Set the body and Content-Length and parse the body headers
+     *         again.
+     *
+     * Error:
+     *      TextError::ContentEncoded
+     *      TextError::UnsupportedCharset
+     *      TextError::Unencodable
+     */
+
+    pub fn set_body_text(&mut self, text: &str) -> Result<(), TextError> {
+        check_not_encoded(&self.body_encodings())
+            .map_err(TextError::ContentEncoded)?;
+        let media_type = self.content_type().and_then(Result::ok);
+        let body = self.body_data().unwrap_or_default();
+        let encoded = encode_text(media_type.as_ref(), &body, text)?;
+
+        self.chunked_body_to_raw();
+        let header_map = self.message_head.header_map_as_mut();
+        set_value(header_map, CONTENT_LENGTH, &encoded.len().to_string());
+        self.body = Some(Body::Raw(encoded));
+        self.body_headers = self.message_head.parse_body_headers();
+        Ok(())
+    }
+}
+
+impl<T> Message<T>
+where
+    T: std::fmt::Debug,
+{
+    // None if Content-Type is missing
+    pub fn content_type(&self) -> Option<Result<MediaType, ContentTypeError>> {
+        self.headers.value_of_key(CONTENT_TYPE).map(parse_content_type)
+    }
+
+    // OneOne::body_text()
+    pub fn body_text(&self) -> Option<Result<String, TextError>> {
+        let body = self.body_as_ref()?;
+        if let Err(e) = check_not_encoded(&body_encodings(&self.headers)) {
+            return Some(Err(TextError::ContentEncoded(e)));
+        }
+        let media_type = self.content_type().and_then(Result::ok);
+        Some(decode_text(media_type.as_ref(), body))
+    }
+
+    // OneOne::set_body_text(), Content-Length is updated only if present
+    pub fn set_body_text(&mut self, text: &str) -> Result<(), TextError> {
+        check_not_encoded(&body_encodings(&self.headers))
+            .map_err(TextError::ContentEncoded)?;
+        let media_type = self.content_type().and_then(Result::ok);
+        let body = self.body_as_ref().map(|b| b.as_ref()).unwrap_or_default();
+        let encoded = encode_text(media_type.as_ref(), body, text)?;
+        self.headers.update_header_value_on_key(
+            CONTENT_LENGTH,
+            encoded.len().to_string().as_bytes(),
+        );
+        self.set_body(encoded);
+        self.update_body_headers();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use body_plz::variants::chunked::ChunkType;
+    use header_plz::{HeaderMap, OneHeaderMap};
+
+    use crate::{OneResponse, Response};
+
+    use super::*;
+
+    #[test]
+    fn test_one_content_type() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Type: text/plain; charset=utf-8\r\n\
+                     Content-Length: 0\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        let media_type = res.content_type().unwrap().unwrap();
+        assert_eq!(media_type.essence(), "text/plain");
+        assert_eq!(media_type.charset(), Some("utf-8"));
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 0\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert!(res.content_type().is_none());
+    }
+
+    #[test]
+    fn test_one_body_text_declared_charset() {
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain; charset=ISO-8859-1\r\n\
+                      Content-Length: 4\r\n\r\n\
+                      caf\xe9";
+        let res = OneResponse::try_from(BytesMut::from(&input[..])).unwrap();
+        assert_eq!(res.body_text().unwrap().unwrap(), "caf\u{e9}");
+    }
+
+    #[test]
+    fn test_one_body_text_bom() {
+        // BOM wins over the declared charset
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain; charset=iso-8859-1\r\n\
+                      Content-Length: 6\r\n\r\n\
+                      \xff\xfeh\x00i\x00";
+        let res = OneResponse::try_from(BytesMut::from(&input[..])).unwrap();
+        assert_eq!(res.body_text().unwrap().unwrap(), "hi");
+    }
+
+    #[test]
+    fn test_one_body_text_meta() {
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/html\r\n\
+                      Content-Length: 38\r\n\r\n\
+                      <meta charset=windows-1252><p>\x93hi\x94</p>";
+        let res = OneResponse::try_from(BytesMut::from(&input[..])).unwrap();
+        assert_eq!(
+            res.body_text().unwrap().unwrap(),
+            "<meta charset=windows-1252><p>\u{201c}hi\u{201d}</p>"
+        );
+    }
+
+    #[test]
+    fn test_one_body_text_errors() {
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain; charset=koi8-r\r\n\
+                      Content-Length: 1\r\n\r\n\
+                      x";
+        let res = OneResponse::try_from(BytesMut::from(&input[..])).unwrap();
+        assert_eq!(
+            res.body_text().unwrap(),
+            Err(TextError::UnsupportedCharset("koi8-r".to_string()))
+        );
+
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Encoding: gzip\r\n\
+                      Content-Length: 1\r\n\r\n\
+                      x";
+        let res = OneResponse::try_from(BytesMut::from(&input[..])).unwrap();
+        assert_eq!(
+            res.body_text().unwrap(),
+            Err(TextError::ContentEncoded("gzip".to_string()))
+        );
+
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Transfer-Encoding: gzip, chunked\r\n\r\n\
+                     1\r\n\
+                     x\r\n\
+                     0\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            res.body_text().unwrap(),
+            Err(TextError::ContentEncoded("gzip".to_string()))
+        );
+        assert_eq!(
+            res.set_body_text("y"),
+            Err(TextError::ContentEncoded("gzip".to_string()))
+        );
+
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let req = crate::OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.body_text().is_none());
+    }
+
+    #[test]
+    fn test_one_set_body_text() {
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain; charset=iso-8859-1\r\n\
+                      Content-Length: 3\r\n\r\n\
+                      old";
+        let mut res =
+            OneResponse::try_from(BytesMut::from(&input[..])).unwrap();
+        res.set_body_text("caf\u{e9}!").unwrap();
+        let verify = b"HTTP/1.1 200 OK\r\n\
+                       Content-Type: text/plain; charset=iso-8859-1\r\n\
+                       Content-Length: 5\r\n\r\n\
+                       caf\xe9!";
+        assert_eq!(res.into_bytes().as_ref(), verify);
+    }
+
+    #[test]
+    fn test_one_set_body_text_unencodable() {
+        let input = b"HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/plain; charset=us-ascii\r\n\
+                      Content-Length: 3\r\n\r\n\
+                      old";
+        let mut res =
+            OneResponse::try_from(BytesMut::from(&input[..])).unwrap();
+        assert!(matches!(
+            res.set_body_text("\u{20ac}"),
+            Err(TextError::Unencodable { .. })
+        ));
+        assert_eq!(res.body_text().unwrap().unwrap(), "old");
+    }
+
+    #[test]
+    fn test_one_set_body_text_chunked_bom() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Type: text/plain\r\n\
+                     Transfer-Encoding: chunked\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.body = Some(Body::Chunked(vec![
+            ChunkType::Size("4\r\n".into()),
+            ChunkType::Chunk(BytesMut::from(&b"\xfe\xff\x00h\r\n"[..])),
+            ChunkType::LastChunk("0\r\n".into()),
+            ChunkType::Trailers(OneHeaderMap::from(HeaderMap::new())),
+        ]));
+        res.set_body_text("ok").unwrap();
+        let verify = b"HTTP/1.1 200 OK\r\n\
+                       Content-Type: text/plain\r\n\
+                       content-length: 6\r\n\r\n\
+                       \xfe\xff\x00o\x00k";
+        assert_eq!(res.into_bytes().as_ref(), verify);
+    }
+
+    #[test]
+    fn test_message_body_text() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json; charset=utf-16be");
+        headers.insert("content-length", "4");
+        let mut res = Response::builder()
+            .headers(headers)
+            .body(BytesMut::from(&b"\x00{\x00}"[..]))
+            .build();
+        assert_eq!(res.body_text().unwrap().unwrap(), "{}");
+        res.set_body_text("[1]").unwrap();
+        assert_eq!(res.body_as_ref().unwrap().as_ref(), b"\x00[\x001\x00]");
+        assert_eq!(res.headers().value_of_key(CONTENT_LENGTH).unwrap(), b"6");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("transfer-encoding", "br, chunked");
+        let mut res = Response::builder()
+            .headers(headers)
+            .body(BytesMut::from("x"))
+            .build();
+        assert_eq!(
+            res.body_text().unwrap(),
+            Err(TextError::ContentEncoded("br".to_string()))
+        );
+        assert_eq!(
+            res.set_body_text("y"),
+            Err(TextError::ContentEncoded("br".to_string()))
+        );
+    }
+}
@@ -0,0 +1,593 @@
+use bytes::BytesMut;
+use header_plz::{
+    OneHeaderMap, OneInfoLine, OneMessageHead,
+    body_headers::parse::ParseBodyHeaders,
+    const_headers::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    message_head::header_map::Hmap,
+};
+
+use crate::{
+    Message, OneOne,
+    compression::decode::body_encodings,
+    content_type::{
+        check_not_encoded, decode_text,
+        error::{ContentTypeError, MultipartError, TextError},
+        media_type::{MediaType, parse_content_type},
+    },
+    utils::{
+        header::set_value,
+        quoted::{split_unquoted, unquote},
+        random::random_u64,
+        token::is_token,
+    },
+};
+
+const BOUNDARY: &str = "boundary";
+const CRLF: &[u8] = b"\r\n";
+const DASHES: &[u8] = b"--";
+// RFC 2046 5.1.1
+const MAX_BOUNDARY_LEN: usize = 70;
+
+// RFC 2046 5.1.1 bchars, space is not allowed as the last character
+fn is_valid_boundary(boundary: &str) -> bool {
+    (1..=MAX_BOUNDARY_LEN).contains(&boundary.len())
+        && !boundary.ends_with(' ')
+        && boundary.bytes().all(|b| {
+            b.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&b)
+        })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+// browsers percent encode CR, LF and '"' in form-data names, WHATWG HTML
+fn escape_form_value(value: &str) -> String {
+    value.replace('\r', "%0D").replace('\n', "%0A").replace('"', "%22")
+}
+
+/* Single body part of a multipart body.
+ *
+ *      Content-Disposition: form-data; name="file"; filename="a.txt"
+ *      Content-Type: text/plain
+ *
+ *      hello
+ *
+ * Headers are kept as received, name and filename are read from the
+ * Content-Disposition header, RFC 7578 4.2.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    headers: OneHeaderMap,
+    content: BytesMut,
+}
+
+impl Part {
+    // without headers
+    pub fn new(content: BytesMut) -> Self {
+        Part {
+            headers: OneHeaderMap::from(BytesMut::from(CRLF)),
+            content,
+        }
+    }
+
+    // form field, Content-Disposition: form-data; name="name"
+    pub fn text(name: &str, value: &str) -> Self {
+        let disposition =
+            format!("form-data; name=\"{}\"", escape_form_value(name));
+        Part::new(BytesMut::from(value))
+            .with_header(CONTENT_DISPOSITION, &disposition)
+    }
+
+    // file field with filename and Content-Type
+    pub fn file(
+        name: &str,
+        filename: &str,
+        content_type: &str,
+        content: BytesMut,
+    ) -> Self {
+        let disposition = format!(
+            "form-data; name=\"{}\"; filename=\"{}\"",
+            escape_form_value(name),
+            escape_form_value(filename)
+        );
+        Part::new(content)
+            .with_header(CONTENT_DISPOSITION, &disposition)
+            .with_header(CONTENT_TYPE, content_type)
+    }
+
+    /* Replaces the first header with the same key. An invalid key, or a
+     * CR, LF or NUL in value, is rejected by MultipartBuilder::build().
+     */
+    pub fn with_header(mut self, key: &[u8], value: &str) -> Self {
+        set_value(&mut self.headers, key, value);
+        self
+    }
+
+    pub fn headers(&self) -> &OneHeaderMap {
+        &self.headers
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn into_content(self) -> BytesMut {
+        self.content
+    }
+
+    fn disposition_param(&self, name: &str) -> Option<String> {
+        let value = self.headers.value_of_key(CONTENT_DISPOSITION)?;
+        let value = std::str::from_utf8(value).ok()?;
+        split_unquoted(value, b';').into_iter().skip(1).find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| unquote(value.trim()))
+        })
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.disposition_param("name")
+    }
+
+    pub fn filename(&self) -> Option<String> {
+        self.disposition_param("filename")
+    }
+
+    // None if Content-Type is missing
+    pub fn content_type(&self) -> Option<Result<MediaType, ContentTypeError>> {
+        self.headers.value_of_key(CONTENT_TYPE).map(parse_content_type)
+    }
+
+    // content decoded with the charset of the part, see body_text()
+    pub fn text_content(&self) -> Result<String, TextError> {
+        let media_type = self.content_type().and_then(Result::ok);
+        decode_text(media_type.as_ref(), &self.content)
+    }
+
+    fn write_to(self, boundary: &str, buf: &mut BytesMut) {
+        buf.extend_from_slice(DASHES);
+        buf.extend_from_slice(boundary.as_bytes());
+        buf.extend_from_slice(CRLF);
+        buf.unsplit(self.headers.into_bytes());
+        buf.unsplit(self.content);
+        buf.extend_from_slice(CRLF);
+    }
+}
+
+/* Description:
+ *      Parse a multipart body, RFC 2046 5.1.1.
+ *
+ * Steps:
+ *      1. Find the first delimiter, "--boundary" at the start of body or
+ *         after a CRLF. Preamble is ignored.
+ *      ----- loop -----
+ *      2. "--" after the delimiter ends the body, epilogue is ignored.
+ *      3. Skip transport padding and CRLF after the delimiter.
+ *      4. Part ends at the next CRLF "--boundary".
+ *      5. Split the part headers at the first empty line.
+ *
+ * Error:
+ *      MultipartError::InvalidBoundary         [1]
+ *      MultipartError::MissingDelimiter        [1]
+ *      MultipartError::InvalidPart             [3] [5]
+ *      MultipartError::MissingCloseDelimiter   [4]
+ */
+
+pub fn parse_multipart(
+    body: &[u8],
+    boundary: &str,
+) -> Result<Vec<Part>, MultipartError> {
+    if !is_valid_boundary(boundary) {
+        return Err(MultipartError::InvalidBoundary(boundary.to_string()));
+    }
+    let mut delimiter = CRLF.to_vec();
+    delimiter.extend_from_slice(DASHES);
+    delimiter.extend_from_slice(boundary.as_bytes());
+    // 1. without the leading CRLF at the start of body
+    let mut pos = if body.starts_with(&delimiter[2..]) {
+        delimiter.len() - 2
+    } else {
+        find(body, &delimiter).ok_or(MultipartError::MissingDelimiter)?
+            + delimiter.len()
+    };
+
+    let mut parts = Vec::new();
+    loop {
+        let rest = &body[pos..];
+        // 2. close delimiter
+        if rest.starts_with(DASHES) {
+            return Ok(parts);
+        }
+        // 3. transport padding
+        let padding = rest.iter().take_while(|b| matches!(b, b' ' | b'\t'));
+        let rest = &rest[padding.count()..];
+        if !rest.starts_with(CRLF) {
+            return Err(MultipartError::InvalidPart(parts.len()));
+        }
+        let start = body.len() - rest.len() + CRLF.len();
+        // 4. next delimiter, CRLF belongs to the delimiter
+        let end = find(&body[start..], &delimiter)
+            .ok_or(MultipartError::MissingCloseDelimiter)?
+            + start;
+        // 5. headers, a part without headers starts with CRLF
+        let part = &body[start..end];
+        let header_len = if part.starts_with(CRLF) {
+            CRLF.len()
+        } else {
+            find(part, b"\r\n\r\n")
+                .ok_or(MultipartError::InvalidPart(parts.len()))?
+                + 4
+        };
+        parts.push(Part {
+            headers: OneHeaderMap::from(BytesMut::from(&part[..header_len])),
+            content: BytesMut::from(&part[header_len..]),
+        });
+        pos = end + delimiter.len();
+    }
+}
+
+// boundary of a multipart Content-Type
+fn multipart_boundary(
+    media_type: Option<Result<MediaType, ContentTypeError>>,
+) -> Result<String, MultipartError> {
+    let media_type =
+        media_type.ok_or(MultipartError::MissingContentType)??;
+    if media_type.main_type() != "multipart" {
+        return Err(MultipartError::NotMultipart(media_type.essence()));
+    }
+    media_type
+        .param(BOUNDARY)
+        .map(str::to_string)
+        .ok_or(MultipartError::MissingBoundary)
+}
+
+/* Builds a multipart/form-data body.
+ *
+ *      let (content_type, body) = MultipartBuilder::new()
+ *          .text("user", "admin")
+ *          .file("file", "a.txt", "text/plain", BytesMut::from("hi"))
+ *          .build()?;
+ *
+ * Default boundary is random, see utils::random.
+ */
+#[derive(Debug, Clone)]
+pub struct MultipartBuilder {
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+impl Default for MultipartBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MultipartBuilder {
+    pub fn new() -> Self {
+        MultipartBuilder {
+            boundary: format!(
+                "----http-plz-{:016x}{:016x}",
+                random_u64(),
+                random_u64()
+            ),
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn boundary(mut self, boundary: &str) -> Self {
+        self.boundary = boundary.to_string();
+        self
+    }
+
+    pub fn part(mut self, part: Part) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    pub fn text(self, name: &str, value: &str) -> Self {
+        self.part(Part::text(name, value))
+    }
+
+    pub fn file(
+        self,
+        name: &str,
+        filename: &str,
+        content_type: &str,
+        content: BytesMut,
+    ) -> Self {
+        self.part(Part::file(name, filename, content_type, content))
+    }
+
+    /* Description:
+     *      Content-Type with boundary and the body.
+     *
+     * Error:
+     *      MultipartError::InvalidBoundary
+     *      MultipartError::InvalidHeader, would break the part framing.
+     *      MultipartError::BoundaryInContent, delimiter found in a part.
+     */
+
+    pub fn build(self) -> Result<(MediaType, BytesMut), MultipartError> {
+        if !is_valid_boundary(&self.boundary) {
+            return Err(MultipartError::InvalidBoundary(self.boundary));
+        }
+        for part in self.parts.iter() {
+            validate_part_headers(&part.headers)?;
+        }
+        let delimiter = [DASHES, self.boundary.as_bytes()].concat();
+        if self
+            .parts
+            .iter()
+            .any(|part| find(&part.content, &delimiter).is_some())
+        {
+            return Err(MultipartError::BoundaryInContent);
+        }
+        let mut body = BytesMut::new();
+        for part in self.parts {
+            part.write_to(&self.boundary, &mut body);
+        }
+        body.extend_from_slice(&delimiter);
+        body.extend_from_slice(DASHES);
+        body.extend_from_slice(CRLF);
+        let media_type = MediaType::new("multipart", "form-data")?
+            .with_param(BOUNDARY, &self.boundary)?;
+        Ok((media_type, body))
+    }
+}
+
+// field-name is a token, field-value has no CR, LF or NUL
+fn validate_part_headers(
+    headers: &OneHeaderMap,
+) -> Result<(), MultipartError> {
+    for header in headers.iter().filter(|h| !h.is_empty()) {
+        let key = header.key_as_ref();
+        let value = header.value_as_ref();
+        if !is_token(key)
+            || value.iter().any(|b| matches!(b, b'\0' | b'\r' | b'\n'))
+        {
+            return Err(MultipartError::InvalidHeader(
+                String::from_utf8_lossy(key).to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    /* Description:
+     *      Body parts of a multipart body, boundary is read from
+     *      Content-Type.
+     *
+     * Returns:
+     *      None if the message has no body.
+     *
+     * Error:
+     *      MultipartError::ContentEncoded, call try_decompress() first.
+     *      MultipartError::MissingContentType
+     *      MultipartError::ContentType
+     *      MultipartError::NotMultipart
+     *      MultipartError::MissingBoundary
+     *      parse_multipart() errors
+     */
+
+    pub fn multipart(&self) -> Option<Result<Vec<Part>, MultipartError>> {
+        let body = self.body_data()?;
+        let parts = check_not_encoded(&self.body_encodings())
+            .map_err(MultipartError::ContentEncoded)
+            .and_then(|_| multipart_boundary(self.content_type()))
+            .and_then(|boundary| parse_multipart(&body, &boundary));
+        Some(parts)
+    }
+}
+
+impl<T> Message<T>
+where
+    T: std::fmt::Debug,
+{
+    // OneOne::multipart()
+    pub fn multipart(&self) -> Option<Result<Vec<Part>, MultipartError>> {
+        let body = self.body_as_ref()?;
+        let parts = check_not_encoded(&body_encodings(&self.headers))
+            .map_err(MultipartError::ContentEncoded)
+            .and_then(|_| multipart_boundary(self.content_type()))
+            .and_then(|boundary| parse_multipart(body, &boundary));
+        Some(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header_plz::HeaderMap;
+
+    use crate::{OneRequest, Request};
+
+    use super::*;
+
+    const BODY: &[u8] = b"preamble\r\n\
+                          --xyz\r\n\
+                          Content-Disposition: form-data; name=\"user\"\r\n\r\n\
+                          admin\r\n\
+                          --xyz  \r\n\
+                          Content-Disposition: form-data; name=\"file\"; \
+                          filename=\"a;b.txt\"\r\n\
+                          Content-Type: text/plain; charset=iso-8859-1\r\n\r\n\
+                          caf\xe9\r\n--x\r\n\
+                          --xyz\r\n\
+                          \r\n\
+                          raw\r\n\
+                          --xyz--\r\n\
+                          epilogue";
+
+    #[test]
+    fn test_parse_multipart() {
+        let parts = parse_multipart(BODY, "xyz").unwrap();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].name().unwrap(), "user");
+        assert!(parts[0].filename().is_none());
+        assert_eq!(parts[0].content(), b"admin");
+        assert_eq!(parts[1].name().unwrap(), "file");
+        assert_eq!(parts[1].filename().unwrap(), "a;b.txt");
+        assert_eq!(
+            parts[1].content_type().unwrap().unwrap().essence(),
+            "text/plain"
+        );
+        assert_eq!(parts[1].content(), b"caf\xe9\r\n--x");
+        assert_eq!(parts[1].text_content().unwrap(), "caf\u{e9}\r\n--x");
+        assert!(parts[2].headers().is_empty());
+        assert_eq!(parts[2].content(), b"raw");
+    }
+
+    #[test]
+    fn test_parse_multipart_errors() {
+        assert_eq!(
+            parse_multipart(BODY, "bad boundary "),
+            Err(MultipartError::InvalidBoundary("bad boundary ".to_string()))
+        );
+        assert_eq!(
+            parse_multipart(BODY, "abc"),
+            Err(MultipartError::MissingDelimiter)
+        );
+        assert_eq!(
+            parse_multipart(b"--xyz\r\n\r\nopen", "xyz"),
+            Err(MultipartError::MissingCloseDelimiter)
+        );
+        assert_eq!(
+            parse_multipart(b"--xyz\r\nkey: value\r\n--xyz--", "xyz"),
+            Err(MultipartError::InvalidPart(0))
+        );
+        assert_eq!(
+            parse_multipart(b"--xyzabc\r\n\r\n\r\n--xyz--", "xyz"),
+            Err(MultipartError::InvalidPart(0))
+        );
+    }
+
+    #[test]
+    fn test_multipart_builder() {
+        let (media_type, body) = MultipartBuilder::new()
+            .boundary("xyz")
+            .text("user", "ad\"min")
+            .text("na\"me", "x")
+            .file("file", "a.txt", "text/plain", BytesMut::from("hi"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            media_type.to_header_value(),
+            "multipart/form-data; boundary=xyz"
+        );
+        let verify = "--xyz\r\n\
+                      content-disposition: form-data; name=\"user\"\r\n\r\n\
+                      ad\"min\r\n\
+                      --xyz\r\n\
+                      content-disposition: form-data; name=\"na%22me\"\r\n\r\n\
+                      x\r\n\
+                      --xyz\r\n\
+                      content-disposition: form-data; name=\"file\"; \
+                      filename=\"a.txt\"\r\n\
+                      content-type: text/plain\r\n\r\n\
+                      hi\r\n\
+                      --xyz--\r\n";
+        assert_eq!(body, verify);
+        let parts = parse_multipart(&body, "xyz").unwrap();
+        assert_eq!(parts[0].content(), b"ad\"min");
+        assert_eq!(parts[2].filename().unwrap(), "a.txt");
+    }
+
+    #[test]
+    fn test_multipart_builder_errors() {
+        let result =
+            MultipartBuilder::new().boundary("xyz").text("a", "--xyz").build();
+        assert_eq!(result, Err(MultipartError::BoundaryInContent));
+        let result = MultipartBuilder::new()
+            .file("f", "a.txt", "text/plain\r\nx-injected: 1", "".into())
+            .build();
+        assert_eq!(
+            result,
+            Err(MultipartError::InvalidHeader("content-type".to_string()))
+        );
+        let part = Part::new("".into()).with_header(b"bad key", "v");
+        let result = MultipartBuilder::new().part(part).build();
+        assert_eq!(
+            result,
+            Err(MultipartError::InvalidHeader("bad key".to_string()))
+        );
+        let result = MultipartBuilder::new().boundary("").build();
+        assert_eq!(
+            result,
+            Err(MultipartError::InvalidBoundary(String::new()))
+        );
+        // random boundary
+        let (media_type, _) = MultipartBuilder::new().build().unwrap();
+        let boundary = media_type.param(BOUNDARY).unwrap();
+        assert!(is_valid_boundary(boundary));
+        assert_ne!(
+            MultipartBuilder::new().build().unwrap().0.param(BOUNDARY),
+            Some(boundary)
+        );
+    }
+
+    #[test]
+    fn test_one_multipart() {
+        let mut input = BytesMut::from(
+            &b"POST / HTTP/1.1\r\n\
+                                          Content-Type: multipart/form-data; \
+                                          boundary=\"xyz\"\r\n"[..],
+        );
+        input.extend_from_slice(
+            format!("Content-Length: {}\r\n\r\n", BODY.len()).as_bytes(),
+        );
+        input.extend_from_slice(BODY);
+        let req = OneRequest::try_from(input).unwrap();
+        let parts = req.multipart().unwrap().unwrap();
+        assert_eq!(parts[0].name().unwrap(), "user");
+
+        let input = "POST / HTTP/1.1\r\n\
+                     Content-Type: text/plain\r\n\
+                     Content-Length: 1\r\n\r\n\
+                     a";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.multipart().unwrap(),
+            Err(MultipartError::NotMultipart("text/plain".to_string()))
+        );
+
+        let input = "POST / HTTP/1.1\r\n\
+                     Content-Type: multipart/form-data; boundary=xyz\r\n\
+                     Transfer-Encoding: gzip, chunked\r\n\r\n\
+                     1\r\n\
+                     x\r\n\
+                     0\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.multipart().unwrap(),
+            Err(MultipartError::ContentEncoded("gzip".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_message_multipart() {
+        let req = Request::builder().body(BytesMut::from(BODY)).build();
+        assert_eq!(
+            req.multipart().unwrap(),
+            Err(MultipartError::MissingContentType)
+        );
+        assert!(Request::builder().build().multipart().is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "multipart/form-data; boundary=xyz");
+        headers.insert("transfer-encoding", "br");
+        let req = Request::builder()
+            .headers(headers)
+            .body(BytesMut::from(BODY))
+            .build();
+        assert_eq!(
+            req.multipart().unwrap(),
+            Err(MultipartError::ContentEncoded("br".to_string()))
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use std::net::IpAddr;
+
+use header_plz::{
+    OneRequestLine,
+    const_headers::{FORWARDED, VIA},
+    message_head::header_map::{HMap, Hmap},
+};
+
+use crate::{
+    OneOne, Request, Version,
+    utils::{
+        header::{LIST_DELIMITER, append_list_value, set_value},
+        quoted::{quote, split_unquoted, unquote},
+    },
+};
+
+pub const X_FORWARDED_FOR: &[u8] = b"x-forwarded-for";
+pub const X_FORWARDED_HOST: &[u8] = b"x-forwarded-host";
+pub const X_FORWARDED_PROTO: &[u8] = b"x-forwarded-proto";
+
+// RFC 7239 4, one forwarded-element
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ForwardedElement {
+    pub by_node: Option<String>,
+    pub for_node: Option<String>,
+    pub host: Option<String>,
+    pub proto: Option<String>,
+}
+
+impl ForwardedElement {
+    pub fn with_by(mut self, node: impl Into<String>) -> Self {
+        self.by_node = Some(node.into());
+        self
+    }
+
+    pub fn with_for(mut self, node: impl Into<String>) -> Self {
+        self.for_node = Some(node.into());
+        self
+    }
+
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    pub fn with_proto(mut self, proto: impl Into<String>) -> Self {
+        self.proto = Some(proto.into());
+        self
+    }
+
+    // IP of the for node, None for "unknown" and obfuscated identifiers
+    pub fn for_ip(&self) -> Option<IpAddr> {
+        self.for_node.as_deref().and_then(node_ip)
+    }
+
+    // for=192.0.2.60;proto=http;by="[2001:db8::1]"
+    pub fn to_header_value(&self) -> String {
+        [
+            ("for", &self.for_node),
+            ("by", &self.by_node),
+            ("host", &self.host),
+            ("proto", &self.proto),
+        ]
+        .iter()
+        .filter_map(|(name, value)| {
+            value.as_deref().map(|v| format!("{name}={}", quote(v)))
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+    }
+}
+
+// node as written in Forwarded, IPv6 is bracketed
+pub fn forwarded_node(ip: IpAddr, port: Option<u16>) -> String {
+    let ip = match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    };
+    match port {
+        Some(port) => format!("{ip}:{port}"),
+        None => ip,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViaEntry {
+    pub protocol: String,
+    pub received_by: String,
+    pub comment: Option<String>,
+}
+
+/* Description:
+ *      Parse Forwarded header values into elements, in order.
+ *
+ * Steps:
+ *      1. Split on ',' outside quoted strings.
+ *      2. Split each element on ';' outside quoted strings.
+ *      3. Split each pair at '=' and unquote the value. Unknown parameters
+ *         are ignored.
+ */
+
+pub fn parse_forwarded(value: &str) -> Vec<ForwardedElement> {
+    split_unquoted(value, b',')
+        .into_iter()
+        .filter(|element| !element.trim().is_empty())
+        .map(|element| {
+            let mut parsed = ForwardedElement::default();
+            for pair in split_unquoted(element, b';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = Some(unquote(value.trim()));
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "by" => parsed.by_node = value,
+                    "for" => parsed.for_node = value,
+                    "host" => parsed.host = value,
+                    "proto" => parsed.proto = value,
+                    _ => (),
+                }
+            }
+            parsed
+        })
+        .collect()
+}
+
+// 1.1 proxy.example (squid/3.1), HTTP/2 gateway
+pub fn parse_via(value: &str) -> Vec<ViaEntry> {
+    split_unquoted(value, b',')
+        .into_iter()
+        .filter_map(|entry| {
+            let entry = entry.trim();
+            let (entry, comment) = match entry.find('(') {
+                Some(index) => (
+                    &entry[..index],
+                    Some(entry[index + 1..].trim_end_matches(')').to_string()),
+                ),
+                None => (entry, None),
+            };
+            let mut parts = entry.split_ascii_whitespace();
+            Some(ViaEntry {
+                protocol: parts.next()?.to_string(),
+                received_by: parts.next()?.to_string(),
+                comment,
+            })
+        })
+        .collect()
+}
+
+// generic helpers over OneHeaderMap and HeaderMap
+fn header_values<T>(header_map: &HMap<T>, key: &[u8]) -> String
+where
+    T: Hmap,
+{
+    header_map
+        .iter()
+        .filter(|h| h.key_as_ref().eq_ignore_ascii_case(key))
+        .map(|h| String::from_utf8_lossy(h.value_as_ref().trim_ascii()))
+        .filter(|value| !value.is_empty())
+        .collect::<Vec<_>>()
+        .join(LIST_DELIMITER)
+}
+
+fn append_via<T>(header_map: &mut HMap<T>, version: Version, received_by: &str)
+where
+    T: Hmap + for<'a> From<(&'a [u8], &'a [u8])>,
+{
+    let protocol = version.as_str().trim_start_matches("HTTP/");
+    append_list_value(header_map, VIA, &format!("{protocol} {received_by}"));
+}
+
+fn x_forwarded_for<T>(header_map: &HMap<T>) -> Vec<String>
+where
+    T: Hmap,
+{
+    header_values(header_map, X_FORWARDED_FOR)
+        .split(',')
+        .map(str::trim)
+        .filter(|hop| !hop.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// 192.0.2.43 | 192.0.2.43:47011 | [2001:db8::1] | [2001:db8::1]:4711
+fn node_ip(node: &str) -> Option<IpAddr> {
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    let ip = match node.split_once(':') {
+        Some((ip, port)) if !port.contains(':') => ip,
+        _ => node,
+    };
+    ip.parse().ok()
+}
+
+impl OneOne<OneRequestLine> {
+    pub fn append_via(&mut self, version: Version, received_by: &str) {
+        append_via(self.message_head.header_map_as_mut(), version, received_by)
+    }
+
+    pub fn add_forwarded(&mut self, element: &ForwardedElement) {
+        append_list_value(
+            self.message_head.header_map_as_mut(),
+            FORWARDED,
+            &element.to_header_value(),
+        )
+    }
+
+    pub fn forwarded(&self) -> Vec<ForwardedElement> {
+        parse_forwarded(&header_values(
+            self.message_head.header_map(),
+            FORWARDED,
+        ))
+    }
+
+    pub fn via(&self) -> Vec<ViaEntry> {
+        parse_via(&header_values(self.message_head.header_map(), VIA))
+    }
+
+    pub fn append_x_forwarded_for(&mut self, ip: IpAddr) {
+        append_list_value(
+            self.message_head.header_map_as_mut(),
+            X_FORWARDED_FOR,
+            &ip.to_string(),
+        )
+    }
+
+    pub fn set_x_forwarded_host(&mut self, host: &str) {
+        set_value(
+            self.message_head.header_map_as_mut(),
+            X_FORWARDED_HOST,
+            host,
+        )
+    }
+
+    pub fn set_x_forwarded_proto(&mut self, proto: &str) {
+        set_value(
+            self.message_head.header_map_as_mut(),
+            X_FORWARDED_PROTO,
+            proto,
+        )
+    }
+
+    // hops in order, client first
+    pub fn x_forwarded_for(&self) -> Vec<String> {
+        x_forwarded_for(self.message_head.header_map())
+    }
+}
+
+impl Request {
+    pub fn append_via(&mut self, version: Version, received_by: &str) {
+        append_via(&mut self.headers, version, received_by)
+    }
+
+    pub fn add_forwarded(&mut self, element: &ForwardedElement) {
+        append_list_value(
+            &mut self.headers,
+            FORWARDED,
+            &element.to_header_value(),
+        )
+    }
+
+    pub fn forwarded(&self) -> Vec<ForwardedElement> {
+        parse_forwarded(&header_values(&self.headers, FORWARDED))
+    }
+
+    pub fn via(&self) -> Vec<ViaEntry> {
+        parse_via(&header_values(&self.headers, VIA))
+    }
+
+    pub fn append_x_forwarded_for(&mut self, ip: IpAddr) {
+        append_list_value(&mut self.headers, X_FORWARDED_FOR, &ip.to_string())
+    }
+
+    pub fn set_x_forwarded_host(&mut self, host: &str) {
+        set_value(&mut self.headers, X_FORWARDED_HOST, host)
+    }
+
+    pub fn set_x_forwarded_proto(&mut self, proto: &str) {
+        set_value(&mut self.headers, X_FORWARDED_PROTO, proto)
+    }
+
+    pub fn x_forwarded_for(&self) -> Vec<String> {
+        x_forwarded_for(&self.headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use header_plz::message_head::header_map::HeaderStr;
+
+    use crate::OneRequest;
+
+    use super::*;
+
+    #[test]
+    fn test_forwarded_element_to_header_value() {
+        let element = ForwardedElement::default()
+            .with_for(forwarded_node(
+                "2001:db8:cafe::17".parse().unwrap(),
+                Some(4711),
+            ))
+            .with_by(forwarded_node("203.0.113.43".parse().unwrap(), None))
+            .with_host("example.com:8080")
+            .with_proto("https");
+        assert_eq!(
+            element.to_header_value(),
+            "for=\"[2001:db8:cafe::17]:4711\";by=203.0.113.43;\
+             host=\"example.com:8080\";proto=https"
+        );
+    }
+
+    #[test]
+    fn test_parse_forwarded() {
+        let value = "for=192.0.2.43, for=\"[2001:db8:cafe::17]\";proto=http;\
+                     by=unknown, For=\"_hidden\"";
+        let parsed = parse_forwarded(value);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].for_ip(), Some("192.0.2.43".parse().unwrap()));
+        assert_eq!(parsed[1].for_node.as_deref(), Some("[2001:db8:cafe::17]"));
+        assert_eq!(
+            parsed[1].for_ip(),
+            Some("2001:db8:cafe::17".parse().unwrap())
+        );
+        assert_eq!(parsed[1].proto.as_deref(), Some("http"));
+        assert_eq!(parsed[1].by_node.as_deref(), Some("unknown"));
+        assert_eq!(parsed[2].for_node.as_deref(), Some("_hidden"));
+        assert_eq!(parsed[2].for_ip(), None);
+    }
+
+    #[test]
+    fn test_parse_forwarded_quoted_separators() {
+        let parsed = parse_forwarded("for=\"a,b;c\";host=\"x\\\"y\"");
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].for_node.as_deref(), Some("a,b;c"));
+        assert_eq!(parsed[0].host.as_deref(), Some("x\"y"));
+    }
+
+    #[test]
+    fn test_parse_via() {
+        let parsed = parse_via(
+            "1.0 fred, 1.1 p.example.net (squid/3.1, linux), HTTP/2 gw",
+        );
+        let verify = vec![
+            ViaEntry {
+                protocol: "1.0".to_string(),
+                received_by: "fred".to_string(),
+                comment: None,
+            },
+            ViaEntry {
+                protocol: "1.1".to_string(),
+                received_by: "p.example.net".to_string(),
+                comment: Some("squid/3.1, linux".to_string()),
+            },
+            ViaEntry {
+                protocol: "HTTP/2".to_string(),
+                received_by: "gw".to_string(),
+                comment: None,
+            },
+        ];
+        assert_eq!(parsed, verify);
+    }
+
+    #[test]
+    fn test_one_append_via() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Via: 1.0 fred\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.append_via(Version::H11, "proxy");
+        assert_eq!(req.via().len(), 2);
+        let verify = "GET / HTTP/1.1\r\n\
+                      Host: example.com\r\n\
+                      Via: 1.0 fred, 1.1 proxy\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+
+        let input = "GET / HTTP/1.1\r\n\
+                     Host: example.com\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.append_via(Version::H2, "proxy");
+        assert_eq!(req.via()[0].protocol, "2");
+    }
+
+    #[test]
+    fn test_one_add_forwarded() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Forwarded: for=192.0.2.43\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let element = ForwardedElement::default()
+            .with_for(forwarded_node("::1".parse().unwrap(), None))
+            .with_proto("http");
+        req.add_forwarded(&element);
+        assert_eq!(req.forwarded().len(), 2);
+        let verify = "GET / HTTP/1.1\r\n\
+                      Host: example.com\r\n\
+                      Forwarded: for=192.0.2.43, for=\"[::1]\";proto=http\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_one_x_forwarded() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     X-Forwarded-For: 203.0.113.1\r\n\
+                     X-Forwarded-For: 198.51.100.2, 10.0.0.1\r\n\
+                     X-Forwarded-Proto: http\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.append_x_forwarded_for("2001:db8::2".parse().unwrap());
+        req.set_x_forwarded_proto("https");
+        req.set_x_forwarded_host("example.com");
+        assert_eq!(
+            req.x_forwarded_for(),
+            vec!["203.0.113.1", "198.51.100.2", "10.0.0.1", "2001:db8::2"]
+        );
+        let verify = "GET / HTTP/1.1\r\n\
+                      Host: example.com\r\n\
+                      X-Forwarded-For: 203.0.113.1\r\n\
+                      X-Forwarded-For: 198.51.100.2, 10.0.0.1, 2001:db8::2\r\n\
+                      X-Forwarded-Proto: https\r\n\
+                      x-forwarded-host: example.com\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_request_forwarding_headers() {
+        let mut req = Request::builder().build();
+        req.append_via(Version::H2, "edge");
+        req.append_via(Version::H11, "proxy");
+        req.add_forwarded(&ForwardedElement::default().with_for("192.0.2.1"));
+        req.append_x_forwarded_for("192.0.2.1".parse().unwrap());
+        req.set_x_forwarded_proto("https");
+
+        let headers: Vec<(&str, &str)> = req
+            .headers()
+            .iter()
+            .filter_map(|h| Some((h.key_as_str()?, h.value_as_str()?)))
+            .collect();
+        let verify = vec![
+            ("via", "2 edge, 1.1 proxy"),
+            ("forwarded", "for=192.0.2.1"),
+            ("x-forwarded-for", "192.0.2.1"),
+            ("x-forwarded-proto", "https"),
+        ];
+        assert_eq!(headers, verify);
+        assert_eq!(req.x_forwarded_for(), vec!["192.0.2.1"]);
+        assert_eq!(req.forwarded()[0].for_node.as_deref(), Some("192.0.2.1"));
+    }
+}
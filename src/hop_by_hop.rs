@@ -0,0 +1,202 @@
+use body_plz::variants::Body;
+use header_plz::{
+    OneInfoLine, OneMessageHead,
+    body_headers::{parse::ParseBodyHeaders, transfer_types::TransferType},
+    const_headers::{
+        CONNECTION, CONTENT_LENGTH, HOST, PROXY_AUTHENTICATE,
+        PROXY_AUTHORIZATION, PROXY_CONNECTION, TE, TRAILER, TRANSFER_ENCODING,
+        UPGRADE,
+    },
+    message_head::header_map::{HMap, Hmap},
+};
+
+use crate::{Message, OneOne, utils::token::header_tokens};
+
+// header name, header_plz::const_headers::KEEP_ALIVE is the token
+pub(crate) const KEEP_ALIVE_HEADER: &[u8] = b"keep-alive";
+
+// RFC 9110 7.6.1 and the legacy RFC 2616 13.5.1 set
+pub const HOP_BY_HOP_HEADERS: [&[u8]; 9] = [
+    CONNECTION,
+    KEEP_ALIVE_HEADER,
+    PROXY_AUTHENTICATE,
+    PROXY_AUTHORIZATION,
+    PROXY_CONNECTION,
+    TE,
+    TRAILER,
+    TRANSFER_ENCODING,
+    UPGRADE,
+];
+
+// never removed because of a Connection token, framing and routing
+// depend on them
+const PROTECTED_HEADERS: [&[u8]; 2] = [CONTENT_LENGTH, HOST];
+
+/* Description:
+ *      Remove hop-by-hop headers before forwarding a message.
+ *
+ * Steps:
+ *      1. Collect every token listed in the Connection headers.
+ *      2. Remove headers named by the tokens, except PROTECTED_HEADERS.
+ *      3. Remove HOP_BY_HOP_HEADERS, except the ones in keep.
+ *
+ *      Upgrade is removed as well, a proxy forwarding an upgrade has to add
+ *      it back along with Connection.
+ */
+
+pub(crate) fn strip_hop_by_hop_headers<T>(
+    header_map: &mut HMap<T>,
+    keep: &[&[u8]],
+) where
+    T: Hmap,
+{
+    let is_kept =
+        |key: &[u8]| keep.iter().any(|k| k.eq_ignore_ascii_case(key));
+    let listed: Vec<Vec<u8>> = header_tokens(header_map, CONNECTION)
+        .filter(|token| {
+            !PROTECTED_HEADERS.iter().any(|p| p.eq_ignore_ascii_case(token))
+        })
+        .map(|token| token.to_ascii_lowercase())
+        .collect();
+
+    for key in listed.iter().map(Vec::as_slice).chain(HOP_BY_HOP_HEADERS) {
+        if !is_kept(key) {
+            header_map.remove_header_on_key_all(key);
+        }
+    }
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    /* Transfer-Encoding and Trailer describe the chunked framing that
+     * into_bytes() still writes, so they are kept for a chunked body. The
+     * body headers are parsed again.
+     */
+    pub fn strip_hop_by_hop(&mut self) {
+        let is_chunked = matches!(self.body(), Some(Body::Chunked(_)))
+            || self.body_headers().as_ref().is_some_and(|bh| {
+                bh.transfer_type == Some(TransferType::Chunked)
+            });
+        let keep: &[&[u8]] = if is_chunked {
+            &[TRANSFER_ENCODING, TRAILER]
+        } else {
+            &[]
+        };
+        strip_hop_by_hop_headers(self.message_head.header_map_as_mut(), keep);
+        self.body_headers = self.message_head.parse_body_headers();
+    }
+}
+
+impl<T> Message<T> {
+    pub fn strip_hop_by_hop(&mut self) {
+        strip_hop_by_hop_headers(&mut self.headers, &[]);
+        self.update_body_headers();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use header_plz::{HeaderMap, message_head::header_map::HeaderStr};
+
+    use crate::{OneRequest, OneResponse, Request};
+
+    #[test]
+    fn test_strip_hop_by_hop_one() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: keep-alive, X-Secret, Upgrade\r\n\
+                     Keep-Alive: timeout=5\r\n\
+                     X-Secret: 1\r\n\
+                     Proxy-Connection: keep-alive\r\n\
+                     Proxy-Authorization: Basic YTpi\r\n\
+                     TE: trailers\r\n\
+                     Upgrade: websocket\r\n\
+                     Accept: */*\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.strip_hop_by_hop();
+        let verify = "GET / HTTP/1.1\r\n\
+                      Host: example.com\r\n\
+                      Accept: */*\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_strip_hop_by_hop_protected() {
+        let input = "POST / HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Host, Content-Length, X-Forwarded-For\r\n\
+                     X-Forwarded-For: 10.0.0.1\r\n\
+                     Content-Length: 2\r\n\r\n\
+                     hi";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.strip_hop_by_hop();
+        let verify = "POST / HTTP/1.1\r\n\
+                      Host: example.com\r\n\
+                      Content-Length: 2\r\n\r\n\
+                      hi";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_strip_hop_by_hop_keeps_chunked_framing() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Transfer-Encoding: chunked\r\n\
+                     Trailer: X-Checksum\r\n\
+                     Connection: close\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        res.strip_hop_by_hop();
+        let verify = "HTTP/1.1 200 OK\r\n\
+                      Transfer-Encoding: chunked\r\n\
+                      Trailer: X-Checksum\r\n\r\n";
+        assert_eq!(res.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_strip_hop_by_hop_updates_body_headers() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Transfer-Encoding: gzip\r\n\
+                     Connection: close\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert!(
+            res.body_headers().as_ref().unwrap().transfer_encoding.is_some()
+        );
+        res.strip_hop_by_hop();
+        assert!(
+            res.body_headers()
+                .as_ref()
+                .is_none_or(|bh| bh.transfer_encoding.is_none())
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("connection", "content-encoding");
+        headers.insert("content-encoding", "gzip");
+        let mut req = Request::builder().headers(headers).build();
+        assert!(req.body_headers().unwrap().content_encoding.is_some());
+        req.strip_hop_by_hop();
+        assert!(req.body_headers().is_none());
+    }
+
+    #[test]
+    fn test_strip_hop_by_hop_message() {
+        let mut headers = HeaderMap::new();
+        headers.insert("connection", "x-a");
+        headers.insert("connection", "x-b");
+        headers.insert("x-a", "1");
+        headers.insert("x-b", "2");
+        headers.insert("transfer-encoding", "chunked");
+        headers.insert("accept", "*/*");
+        let mut req = Request::builder().headers(headers).build();
+        req.strip_hop_by_hop();
+        let keys: Vec<&str> = req
+            .headers()
+            .iter()
+            .filter(|h| !h.is_empty())
+            .filter_map(|h| h.key_as_str())
+            .collect();
+        assert_eq!(keys, vec!["accept"]);
+    }
+}
@@ -1,7 +1,44 @@
+mod auth;
+mod compression;
+mod content_type;
+mod forwarded;
+mod hop_by_hop;
 mod message;
 mod one;
+mod proxy_protocol;
+mod rewrite;
+mod sniff;
+mod utils;
+mod websocket;
 
+pub use crate::auth::{
+    AuthError, AuthScheme, Challenge, Credentials, parse_credentials,
+};
+pub use crate::compression::{
+    accept::{
+        EncodingPreference, best_encoding, decodable_encodings,
+        parse_accept_encoding,
+    },
+    encode::compress,
+    error::{CompressError, DecompressError, StreamDecodeError},
+    limits::DecompressLimits,
+    sniff::{EncodingSniff, sniff_encoding},
+    stream::StreamDecoder,
+};
+pub use crate::content_type::{
+    charset::{Charset, sniff_bom},
+    error::{ContentTypeError, FormError, MultipartError, TextError},
+    form::{FORM_URLENCODED, UrlEncodedForm, parse_form_urlencoded},
+    media_type::{MediaType, parse_content_type},
+    multipart::{MultipartBuilder, Part, parse_multipart},
+};
+pub use crate::forwarded::{
+    ForwardedElement, ViaEntry, X_FORWARDED_FOR, X_FORWARDED_HOST,
+    X_FORWARDED_PROTO, forwarded_node, parse_forwarded, parse_via,
+};
+pub use crate::hop_by_hop::HOP_BY_HOP_HEADERS;
 pub use crate::message::Message;
+pub use crate::message::downgrade::DowngradeError;
 pub use crate::message::request::Request;
 pub use crate::message::response::Response;
 
@@ -9,7 +46,38 @@ pub use crate::one::OneOne;
 pub use crate::one::OneRequest;
 pub use crate::one::OneResponse;
 
+pub use one::connect::{ConnectError, ConnectTarget, parse_connect_target};
+pub use one::h2c::{
+    H2_PREFACE, H2Setting, H2cError, PrefaceMatch, detect_h2_preface,
+};
+pub use one::normalize::{NormalizePolicy, NormalizeRule};
 pub use one::parse::ParseMessage;
+pub use one::persistence::{KeepAliveParams, parse_keep_alive};
+pub use one::request_target::{
+    AbsoluteTarget, OriginFormConversion, RequestTargetError,
+    RequestTargetForm, parse_absolute_form,
+};
+pub use one::version::SetVersion;
+
+pub use proxy_protocol::{
+    PP2_TYPE_ALPN, PP2_TYPE_AUTHORITY, PP2_TYPE_CRC32C, PP2_TYPE_NETNS,
+    PP2_TYPE_NOOP, PP2_TYPE_SSL, PP2_TYPE_UNIQUE_ID, ProxyAddr, ProxyCommand,
+    ProxyHeader, ProxyTransport, ProxyVersion, Tlv, error::ProxyProtocolError,
+    parse_proxy_header, parse_with_proxy_header,
+};
+
+pub use rewrite::{ClientInfo, ForwardingHeaders, RewriteError, Upstream};
+
+pub use sniff::{Protocol, sniff_protocol};
+
+pub use websocket::{
+    Role, WEBSOCKET_GUID, WEBSOCKET_VERSION,
+    deflate::{DeflateConfig, PERMESSAGE_DEFLATE},
+    error::WebSocketError,
+    frame::{CloseCode, CloseFrame, Frame, OpCode},
+    message::{WebSocketCodec, WsMessage},
+    websocket_accept,
+};
 
 pub const HTTP_0_9: &str = "HTTP/0.9";
 pub const HTTP_1_0: &str = "HTTP/1.0";
@@ -17,6 +85,7 @@ pub const HTTP_1_1: &str = "HTTP/1.1";
 pub const HTTP_2: &str = "HTTP/2";
 pub const HTTP_3: &str = "HTTP/3";
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Version {
     H09,
     H10,
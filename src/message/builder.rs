@@ -7,6 +7,8 @@ pub struct MessageBuilder<T> {
     pub(super) headers: Option<HeaderMap>,
     pub(super) body: Option<BytesMut>,
     pub(super) trailer: Option<HeaderMap>,
+    // set by RequestBuilder::multipart() and form(), applied on build()
+    pub(super) content_type: Option<String>,
 }
 
 impl<T> MessageBuilder<T> {
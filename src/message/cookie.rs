@@ -0,0 +1,157 @@
+use bytes::BytesMut;
+use header_plz::{
+    HeaderMap, const_headers::COOKIE, message_head::header_map::Hmap,
+};
+
+const CRUMB_DELIMITER: &[u8] = b"; ";
+
+/* Description:
+ *      HTTP/2 allows the cookie header to be split into multiple fields
+ *      (RFC 9113 8.2.3). Split a HTTP/1.1 "Cookie: a=1; b=2" into one field
+ *      per crumb.
+ *
+ * Steps:
+ *      1. If no cookie header contains a ';', return the map as is.
+ *      2. Rebuild the map, replacing each cookie header with one header per
+ *         non-empty crumb.
+ */
+
+pub(crate) fn split_cookie_crumbs(headers: HeaderMap) -> HeaderMap {
+    if !headers.iter().any(|h| {
+        h.key_as_ref().eq_ignore_ascii_case(COOKIE)
+            && h.value_as_ref().contains(&b';')
+    }) {
+        return headers;
+    }
+
+    let mut split = HeaderMap::new();
+    for header in headers.into_iter().filter(|h| !h.is_empty()) {
+        let (key, value) = header.into_inner();
+        if !key.eq_ignore_ascii_case(COOKIE) {
+            split.insert(key, value);
+            continue;
+        }
+        value
+            .split(|b| *b == b';')
+            .map(|crumb| crumb.trim_ascii())
+            .filter(|crumb| !crumb.is_empty())
+            .for_each(|crumb| split.insert(COOKIE, crumb));
+    }
+    split
+}
+
+/* Description:
+ *      HTTP/1.1 requires a single cookie header. Join every cookie field with
+ *      "; " into the position of the first one.
+ *
+ * Steps:
+ *      1. If there is less than two cookie headers, return the map as is.
+ *      2. Join all cookie values.
+ *      3. Rebuild the map, inserting the joined value at the position of the
+ *         first cookie header and dropping the rest.
+ */
+
+pub(crate) fn join_cookie_crumbs(headers: HeaderMap) -> HeaderMap {
+    let is_cookie = |key: &[u8]| key.eq_ignore_ascii_case(COOKIE);
+    let count = headers
+        .iter()
+        .filter(|h| !h.is_empty() && is_cookie(h.key_as_ref()))
+        .count();
+    if count < 2 {
+        return headers;
+    }
+
+    let mut cookie = BytesMut::new();
+    for header in headers.iter().filter(|h| is_cookie(h.key_as_ref())) {
+        if !cookie.is_empty() {
+            cookie.extend_from_slice(CRUMB_DELIMITER);
+        }
+        cookie.extend_from_slice(header.value_as_ref());
+    }
+
+    let mut cookie = Some(cookie.freeze());
+    let mut joined = HeaderMap::new();
+    for header in headers.into_iter().filter(|h| !h.is_empty()) {
+        let (key, value) = header.into_inner();
+        if !is_cookie(&key) {
+            joined.insert(key, value);
+        } else if let Some(cookie) = cookie.take() {
+            joined.insert(key, cookie);
+        }
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_cookie_crumbs() {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "example.com");
+        headers.insert("cookie", "a=1; b=2;c=3");
+        headers.insert("accept", "*/*");
+
+        let mut verify = HeaderMap::new();
+        verify.insert("host", "example.com");
+        verify.insert("cookie", "a=1");
+        verify.insert("cookie", "b=2");
+        verify.insert("cookie", "c=3");
+        verify.insert("accept", "*/*");
+        assert_eq!(split_cookie_crumbs(headers), verify);
+    }
+
+    #[test]
+    fn test_split_cookie_crumbs_single() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", "a=1");
+        let verify = headers.clone();
+        assert_eq!(split_cookie_crumbs(headers), verify);
+    }
+
+    #[test]
+    fn test_split_cookie_crumbs_empty_crumbs() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", "a=1; ; b=2;");
+        let mut verify = HeaderMap::new();
+        verify.insert("cookie", "a=1");
+        verify.insert("cookie", "b=2");
+        assert_eq!(split_cookie_crumbs(headers), verify);
+    }
+
+    #[test]
+    fn test_join_cookie_crumbs() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", "a=1");
+        headers.insert("host", "example.com");
+        headers.insert("cookie", "b=2");
+        headers.insert("cookie", "c=3");
+
+        let mut verify = HeaderMap::new();
+        verify.insert("cookie", "a=1; b=2; c=3");
+        verify.insert("host", "example.com");
+        assert_eq!(join_cookie_crumbs(headers), verify);
+    }
+
+    #[test]
+    fn test_join_cookie_crumbs_single() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", "a=1; b=2");
+        let verify = headers.clone();
+        assert_eq!(join_cookie_crumbs(headers), verify);
+    }
+
+    #[test]
+    fn test_join_cookie_crumbs_skips_removed() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", "a=1");
+        headers.insert("cookie", "b=2");
+        headers.insert("cookie", "c=3");
+        headers.remove_header(("cookie", "b=2"));
+
+        let mut verify = HeaderMap::new();
+        verify.insert("cookie", "a=1; c=3");
+        assert_eq!(join_cookie_crumbs(headers), verify);
+    }
+}
@@ -0,0 +1,178 @@
+use header_plz::{
+    HeaderMap, Method, message_head::header_map::Hmap, uri::Uri,
+};
+use thiserror::Error;
+
+use crate::utils::token::is_token;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DowngradeError {
+    #[error("Invalid Method| {0}")]
+    InvalidMethod(String),
+    #[error("Invalid Path| {0}")]
+    InvalidPath(String),
+    #[error("Invalid Authority| {0}")]
+    InvalidAuthority(String),
+    #[error("Invalid Header Name| {0}")]
+    InvalidHeaderName(String),
+    #[error("Invalid Header Value| {0}")]
+    InvalidHeaderValue(String),
+    #[error("Not Extended CONNECT")]
+    NotExtendedConnect,
+    #[error("Invalid Protocol| {0}")]
+    InvalidProtocol(String),
+    #[error("Invalid Sec-WebSocket-Version| {0}")]
+    InvalidWebSocketVersion(String),
+    #[error("Failed to generate Sec-WebSocket-Key| {0}")]
+    KeyGeneration(String),
+}
+
+/* Description:
+ *      Validate the parts of a HTTP/2 message that are written verbatim when
+ *      converting to HTTP/1.1. A CR, LF or NUL smuggled in any of them would
+ *      allow a second message to be injected after the downgrade.
+ *
+ * Error:
+ *      DowngradeError::InvalidMethod       [method]
+ *      DowngradeError::InvalidPath         [uri]
+ *      DowngradeError::InvalidAuthority    [uri]
+ *      DowngradeError::InvalidHeaderName   [headers]
+ *      DowngradeError::InvalidHeaderValue  [headers]
+ */
+
+pub(crate) fn validate_method(method: &Method) -> Result<(), DowngradeError> {
+    let method = method.as_ref();
+    if !is_token(method) {
+        return Err(DowngradeError::InvalidMethod(lossy(method)));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_uri(uri: &Uri) -> Result<(), DowngradeError> {
+    let path = uri.path_and_query().as_str();
+    if path.bytes().any(is_ctl_or_space) {
+        return Err(DowngradeError::InvalidPath(path.to_string()));
+    }
+    if let Some(authority) = uri.authority()
+        && authority.bytes().any(|b| is_ctl_or_space(b) || b == b'/')
+    {
+        return Err(DowngradeError::InvalidAuthority(authority.to_string()));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_headers(
+    headers: &HeaderMap,
+) -> Result<(), DowngradeError> {
+    for header in headers.iter().filter(|h| !h.is_empty()) {
+        let key = header.key_as_ref();
+        if !is_token(key) {
+            return Err(DowngradeError::InvalidHeaderName(lossy(key)));
+        }
+        if header
+            .value_as_ref()
+            .iter()
+            .any(|b| matches!(b, b'\0' | b'\r' | b'\n'))
+        {
+            return Err(DowngradeError::InvalidHeaderValue(lossy(key)));
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn is_ctl_or_space(b: u8) -> bool {
+    b.is_ascii_control() || b == b' '
+}
+
+fn lossy(src: &[u8]) -> String {
+    String::from_utf8_lossy(src).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn test_validate_method_valid() {
+        assert!(validate_method(&Method::GET).is_ok());
+        assert!(validate_method(&Method::from("PROPFIND")).is_ok());
+    }
+
+    #[test]
+    fn test_validate_method_crlf() {
+        let method = Method::from("GET / HTTP/1.1\r\nHost: evil\r\n\r\nGET");
+        assert!(matches!(
+            validate_method(&method),
+            Err(DowngradeError::InvalidMethod(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_uri_valid() {
+        let uri = Uri::builder()
+            .authority("example.com:8080")
+            .path("/foo?a=1")
+            .build()
+            .unwrap();
+        assert!(validate_uri(&uri).is_ok());
+    }
+
+    #[test]
+    fn test_validate_uri_authority_crlf() {
+        let uri =
+            Uri::builder().authority("example.com\r\nx: y").build().unwrap();
+        assert_eq!(
+            validate_uri(&uri),
+            Err(DowngradeError::InvalidAuthority(
+                "example.com\r\nx: y".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_validate_headers_valid() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-custom", "value with spaces");
+        assert!(validate_headers(&headers).is_ok());
+    }
+
+    #[test]
+    fn test_validate_headers_value_crlf() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-custom", "a\r\n\r\nGET /admin HTTP/1.1");
+        assert_eq!(
+            validate_headers(&headers),
+            Err(DowngradeError::InvalidHeaderValue("x-custom".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_validate_headers_value_nul() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-custom", "a\0b");
+        assert!(validate_headers(&headers).is_err());
+    }
+
+    #[test]
+    fn test_validate_headers_name_invalid() {
+        let mut headers = HeaderMap::new();
+        headers.insert(Bytes::from("x-bad\r\nname"), Bytes::from("value"));
+        assert_eq!(
+            validate_headers(&headers),
+            Err(DowngradeError::InvalidHeaderName(
+                "x-bad\r\nname".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_validate_headers_removed_ignored() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-custom", "value");
+        headers.remove_header_on_key("x-custom");
+        assert!(validate_headers(&headers).is_ok());
+    }
+}
@@ -1,5 +1,6 @@
-use crate::message::Message;
+use crate::{compression::decode::chunk_data, message::Message};
 use body_plz::variants::Body;
+use bytes::BytesMut;
 use decompression_plz::DecompressTrait;
 use header_plz::{
     Header, body_headers::BodyHeader, message_head::header_map::HMap,
@@ -10,23 +11,34 @@ impl<T> DecompressTrait for Message<T> {
     type HmapType = Header;
 
     fn get_body(&mut self) -> Body {
-        Body::Raw(self.body.take().unwrap())
+        Body::Raw(self.body.take().unwrap_or_default())
     }
 
     fn get_extra_body(&mut self) -> Option<bytes::BytesMut> {
         None
     }
 
-    fn set_body(&mut self, _body: Body) {
-        todo!()
+    // no chunked framing in HTTP/2 and HTTP/3, chunk data is concatenated
+    fn set_body(&mut self, body: Body) {
+        let body = match body {
+            Body::Raw(body) => body,
+            Body::Chunked(chunks) => chunks
+                .iter()
+                .filter_map(chunk_data)
+                .fold(BytesMut::new(), |mut body, data| {
+                    body.extend_from_slice(data);
+                    body
+                }),
+        };
+        self.body = Some(body);
     }
 
     fn body_headers(&self) -> Option<&BodyHeader> {
-        todo!()
+        self.body_headers.as_ref()
     }
 
     fn body_headers_as_mut(&mut self) -> Option<&mut BodyHeader> {
-        todo!()
+        self.body_headers.as_mut()
     }
 
     fn header_map(&self) -> &HMap<Self::HmapType> {
@@ -37,3 +49,28 @@ impl<T> DecompressTrait for Message<T> {
         &mut self.headers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use body_plz::variants::chunked::ChunkType;
+
+    use crate::Response;
+
+    use super::*;
+
+    #[test]
+    fn test_message_set_body_chunked() {
+        let mut res = Response::builder().build();
+        DecompressTrait::set_body(
+            &mut res,
+            Body::Chunked(vec![
+                ChunkType::Size("5\r\n".into()),
+                ChunkType::Chunk("hello\r\n".into()),
+                ChunkType::Size("6\r\n".into()),
+                ChunkType::Chunk(" world\r\n".into()),
+                ChunkType::LastChunk("0\r\n".into()),
+            ]),
+        );
+        assert_eq!(res.body_as_ref().unwrap(), "hello world");
+    }
+}
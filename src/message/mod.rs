@@ -4,7 +4,11 @@ use decompression_plz::MultiDecompressErrorReason;
 use decompression_plz::decompress;
 use header_plz::OneHeaderMap;
 use header_plz::{HeaderMap, body_headers::BodyHeader};
+
+use crate::message::downgrade::{DowngradeError, validate_headers};
 mod builder;
+pub(crate) mod cookie;
+pub(crate) mod downgrade;
 mod impl_decompress;
 pub(crate) mod request;
 pub(crate) mod response;
@@ -26,17 +30,18 @@ impl<T> Message<T> {
         trailer: Option<HeaderMap>,
     ) -> Message<T> {
         Self {
+            body_headers: Option::<BodyHeader>::from(&headers),
             info_line,
             headers,
             body,
             trailers: trailer,
-            body_headers: None,
         }
     }
 
     // setters
     pub fn set_headers(&mut self, headers: HeaderMap) {
         self.headers = headers;
+        self.update_body_headers();
     }
 
     pub fn set_body(&mut self, body: BytesMut) {
@@ -72,17 +77,42 @@ impl<T> Message<T> {
     pub fn into_message_head(self) -> (T, HeaderMap) {
         (self.info_line, self.headers)
     }
+
+    pub fn body_headers(&self) -> Option<&BodyHeader> {
+        self.body_headers.as_ref()
+    }
+
+    // headers can be edited in place, parse them again
+    pub(crate) fn update_body_headers(&mut self) {
+        self.body_headers = Option::<BodyHeader>::from(&self.headers);
+    }
+
+    // validate headers and trailers before converting to HTTP/1.1
+    pub(crate) fn validate_fields(&self) -> Result<(), DowngradeError> {
+        validate_headers(&self.headers)?;
+        if let Some(trailers) = self.trailers.as_ref() {
+            validate_headers(trailers)?;
+        }
+        Ok(())
+    }
 }
 
 impl<T> Message<T>
 where
     T: std::fmt::Debug,
 {
+    // no-op without a body
     pub fn try_decompress(
         &mut self,
         buf: &mut BytesMut,
     ) -> Result<(), MultiDecompressErrorReason> {
-        decompress(self, buf)
+        if self.body.is_none() {
+            return Ok(());
+        }
+        self.update_body_headers();
+        let result = decompress(self, buf);
+        self.update_body_headers();
+        result
     }
 }
 
@@ -100,10 +130,14 @@ pub(crate) fn process_one_headers_and_body(
 
 #[cfg(test)]
 mod tests {
-    use crate::message::{request::Request, response::Response};
+    use crate::{
+        compress,
+        message::{request::Request, response::Response},
+    };
     use bytes::Bytes;
     use header_plz::{
         Method,
+        body_headers::content_encoding::ContentEncoding,
         uri::{Uri, scheme::Scheme},
     };
 
@@ -189,4 +223,64 @@ mod tests {
         assert_eq!(*response.body_as_ref().unwrap(), body);
         assert_eq!(response.trailers().unwrap(), &headers);
     }
+
+    fn build_encoded_response(encoding: &str, body: BytesMut) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", encoding);
+        headers.insert("content-length", body.len().to_string().as_str());
+        Response::builder().headers(headers).body(body).build()
+    }
+
+    #[test]
+    fn test_message_body_headers() {
+        let response = build_encoded_response("gzip", BytesMut::new());
+        let content_encoding = response
+            .body_headers()
+            .and_then(|bh| bh.content_encoding.as_ref())
+            .unwrap();
+        assert_eq!(content_encoding[0].encodings(), [ContentEncoding::Gzip]);
+
+        let mut response = Response::builder().build();
+        assert!(response.body_headers().is_none());
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", "br");
+        response.set_headers(headers);
+        assert!(response.body_headers().is_some());
+    }
+
+    #[test]
+    fn test_message_try_decompress() {
+        let body = compress(b"hello world", &ContentEncoding::Gzip).unwrap();
+        let body = compress(&body, &ContentEncoding::Zstd).unwrap();
+        let mut response = build_encoded_response("gzip, zstd", body);
+        response.try_decompress(&mut BytesMut::new()).unwrap();
+        assert_eq!(response.body_as_ref().unwrap().as_ref(), b"hello world");
+        assert!(!response.headers().has_key("content-encoding"));
+        assert_eq!(
+            response.headers().value_of_key("content-length").unwrap(),
+            b"11"
+        );
+        assert!(
+            response
+                .body_headers()
+                .is_none_or(|bh| { bh.content_encoding.is_none() })
+        );
+    }
+
+    #[test]
+    fn test_message_try_decompress_no_body() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", "gzip");
+        let mut response = Response::builder().headers(headers).build();
+        response.try_decompress(&mut BytesMut::new()).unwrap();
+        assert!(response.body_as_ref().is_none());
+        assert!(response.headers().has_key("content-encoding"));
+    }
+
+    #[test]
+    fn test_message_try_decompress_corrupt() {
+        let mut response =
+            build_encoded_response("gzip", BytesMut::from("not gzip"));
+        assert!(response.try_decompress(&mut BytesMut::new()).is_err());
+    }
 }
@@ -1,17 +1,39 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
 use bytes::Bytes;
 use header_plz::{
     Method, RequestLine,
+    const_headers::{
+        CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_VERSION, UPGRADE,
+    },
     uri::{Uri, path::PathAndQuery, scheme::Scheme},
 };
 
 use crate::{
-    Version,
+    Version, WEBSOCKET_VERSION,
+    content_type::{
+        error::MultipartError,
+        form::{FORM_URLENCODED, UrlEncodedForm},
+        multipart::MultipartBuilder,
+    },
     message::{
-        Message, builder::MessageBuilder, process_one_headers_and_body,
+        Message,
+        builder::MessageBuilder,
+        cookie::split_cookie_crumbs,
+        downgrade::{DowngradeError, validate_method, validate_uri},
+        process_one_headers_and_body,
     },
     one::OneRequest,
+    utils::{
+        header::{append_list_value, set_value},
+        random::secure_random,
+        token::{has_token, is_token},
+    },
 };
 
+const UPGRADE_TOKEN: &str = "upgrade";
+const WEBSOCKET: &str = "websocket";
+
 pub type Request = Message<RequestLine>;
 pub type RequestBuilder = MessageBuilder<RequestLine>;
 
@@ -31,14 +53,40 @@ impl RequestBuilder {
         self
     }
 
+    /* Description:
+     *      Set the body to a multipart/form-data body. Content-Type and
+     *      Content-Length are merged into the headers on build(), in any
+     *      order with headers().
+     *
+     * Error:
+     *      MultipartBuilder::build() errors
+     */
+
+    pub fn multipart(
+        mut self,
+        form: MultipartBuilder,
+    ) -> Result<Self, MultipartError> {
+        let (media_type, body) = form.build()?;
+        self.content_type = Some(media_type.to_header_value());
+        self.body = Some(body);
+        Ok(self)
+    }
+
+    // application/x-www-form-urlencoded body, see multipart()
+    pub fn form(mut self, form: UrlEncodedForm) -> Self {
+        self.content_type = Some(FORM_URLENCODED.to_string());
+        self.body = Some(form.to_bytes());
+        self
+    }
+
     pub fn build(self) -> Request {
-        Request {
-            info_line: self.info_line,
-            headers: self.headers.unwrap_or_default(),
-            body: self.body,
-            trailers: self.trailer,
-            body_headers: None,
+        let mut headers = self.headers.unwrap_or_default();
+        if let Some(content_type) = self.content_type {
+            let len = self.body.as_ref().map_or(0, |body| body.len());
+            set_value(&mut headers, CONTENT_TYPE, &content_type);
+            set_value(&mut headers, CONTENT_LENGTH, &len.to_string());
         }
+        Request::new(self.info_line, headers, self.body, self.trailer)
     }
 }
 
@@ -71,8 +119,98 @@ impl Request {
         self.info_line.uri().authority()
     }
 
-    pub fn into_one_rep(self) -> OneRequest {
-        OneRequest::from((self, Version::H2))
+    // RFC 8441 :protocol pseudo-header
+    pub fn protocol(&self) -> Option<&Bytes> {
+        self.info_line.extension()
+    }
+
+    pub fn is_extended_connect(&self) -> bool {
+        matches!(self.method(), Method::CONNECT) && self.protocol().is_some()
+    }
+
+    pub fn into_one_rep(self) -> Result<OneRequest, DowngradeError> {
+        self.try_into_one(Version::H2)
+    }
+
+    // Convert to HTTP/1.x, rejecting fields that would break message framing
+    pub fn try_into_one(
+        self,
+        version: Version,
+    ) -> Result<OneRequest, DowngradeError> {
+        validate_method(self.method())?;
+        validate_uri(self.info_line.uri())?;
+        self.validate_fields()?;
+        Ok(OneRequest::from_two(self, version))
+    }
+
+    /* Description:
+     *      Convert an RFC 8441 extended CONNECT to the HTTP/1.1 upgrade it
+     *      stands for, a GET with Upgrade: <protocol>. try_into_one() keeps
+     *      the CONNECT.
+     *
+     * Steps:
+     *      1. Validate as try_into_one(), :protocol must be a token.
+     *      2. Add the upgrade token to Connection and set Upgrade.
+     *      3. websocket, RFC 6455 4.1: Sec-WebSocket-Version must be 13 and
+     *         is added if missing, a Sec-WebSocket-Key is generated if
+     *         missing.
+     *
+     * Error:
+     *      DowngradeError::NotExtendedConnect
+     *      DowngradeError::InvalidProtocol             [1]
+     *      DowngradeError::InvalidWebSocketVersion     [3]
+     *      DowngradeError::KeyGeneration               [3]
+     *      try_into_one() errors                       [1]
+     */
+
+    pub fn try_into_one_upgrade(
+        mut self,
+    ) -> Result<OneRequest, DowngradeError> {
+        let protocol = match self.protocol() {
+            Some(protocol) if self.is_extended_connect() => protocol.clone(),
+            _ => return Err(DowngradeError::NotExtendedConnect),
+        };
+        let protocol = std::str::from_utf8(&protocol)
+            .ok()
+            .filter(|p| is_token(p.as_bytes()))
+            .ok_or_else(|| {
+                DowngradeError::InvalidProtocol(
+                    String::from_utf8_lossy(&protocol).to_string(),
+                )
+            })?;
+        validate_uri(self.info_line.uri())?;
+        self.validate_fields()?;
+
+        if !has_token(&self.headers, CONNECTION, UPGRADE_TOKEN) {
+            append_list_value(&mut self.headers, CONNECTION, UPGRADE_TOKEN);
+        }
+        set_value(&mut self.headers, UPGRADE, protocol);
+        if protocol.eq_ignore_ascii_case(WEBSOCKET) {
+            match self.headers.value_of_key(SEC_WEBSOCKET_VERSION) {
+                None => self
+                    .headers
+                    .insert(SEC_WEBSOCKET_VERSION, WEBSOCKET_VERSION),
+                Some(version) if version == WEBSOCKET_VERSION.as_bytes() => {}
+                Some(version) => {
+                    return Err(DowngradeError::InvalidWebSocketVersion(
+                        String::from_utf8_lossy(version).to_string(),
+                    ));
+                }
+            }
+            if !self.headers.has_key(SEC_WEBSOCKET_KEY) {
+                let nonce: [u8; 16] = secure_random().map_err(|e| {
+                    DowngradeError::KeyGeneration(e.to_string())
+                })?;
+                self.headers.insert(
+                    SEC_WEBSOCKET_KEY,
+                    STANDARD.encode(nonce).as_str(),
+                );
+            }
+        }
+
+        let (_, uri, _) = self.info_line.into_parts();
+        self.info_line = RequestLine::new(Method::GET, uri);
+        Ok(OneRequest::from_two(self, Version::H11))
     }
 }
 
@@ -81,9 +219,19 @@ impl From<OneRequest> for Request {
         let body = req.take_body();
         let (info_line, headers) = req.message_head.into_parts();
         let (headers, body) = process_one_headers_and_body(headers, body);
+        let headers = split_cookie_crumbs(headers);
         let (raw_method, raw_uri, _) = info_line.into_parts();
         let method = Method::from(raw_method.trim_ascii_end());
-        let uri = Uri::builder().path(raw_uri.as_ref()).build().unwrap();
+        // CONNECT target is in authority-form, invalid UTF-8 is replaced
+        let uri = match method {
+            Method::CONNECT => {
+                let authority = String::from_utf8_lossy(&raw_uri);
+                Uri::builder().authority(authority.as_ref())
+            }
+            _ => Uri::builder().path(raw_uri.as_ref()),
+        }
+        .build()
+        .unwrap();
         let info_line = RequestLine::new(method, uri);
         Request::new(info_line, headers, body, None)
     }
@@ -94,7 +242,7 @@ mod tests {
     use super::*;
     use bytes::BytesMut;
 
-    use header_plz::{HeaderMap, const_headers::CONTENT_LENGTH};
+    use header_plz::HeaderMap;
 
     #[test]
     fn test_one_to_two_request_minimal() {
@@ -207,6 +355,194 @@ mod tests {
             .headers(headers)
             .body(BytesMut::from("Hello"))
             .build();
-        assert_eq!(req.into_one_rep().into_bytes(), expected);
+        assert_eq!(req.into_one_rep().unwrap().into_bytes(), expected);
+    }
+
+    #[test]
+    fn test_one_to_two_request_cookie_crumbs() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", "a=1");
+        headers.insert("cookie", "b=2");
+        let verify =
+            Request::builder().method(Method::GET).headers(headers).build();
+        let input = "GET / HTTP/1.1\r\n\
+                     Cookie: a=1; b=2\r\n\r\n";
+        let one = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(Request::from(one), verify);
+    }
+
+    #[test]
+    fn test_two_try_into_one_request() {
+        let expected = "GET /foo HTTP/1.1\r\n\
+                        key: value\r\n\
+                        host: example.com\r\n\r\n";
+        let mut headers = HeaderMap::new();
+        headers.insert("key", "value");
+        let uri = Uri::builder()
+            .authority("example.com")
+            .path("/foo")
+            .build()
+            .unwrap();
+        let req = Request::builder().uri(uri).headers(headers).build();
+        let one = req.try_into_one(Version::H11).unwrap();
+        assert_eq!(one.into_bytes(), expected);
+    }
+
+    #[test]
+    fn test_two_try_into_one_request_header_injection() {
+        let mut headers = HeaderMap::new();
+        headers.insert("key", "value\r\n\r\nGET /admin HTTP/1.1");
+        let req = Request::builder().headers(headers).build();
+        assert_eq!(
+            req.try_into_one(Version::H11).unwrap_err(),
+            DowngradeError::InvalidHeaderValue("key".to_string())
+        );
+    }
+
+    #[test]
+    fn test_two_try_into_one_request_trailer_injection() {
+        let mut trailer = HeaderMap::new();
+        trailer.insert("key", "a\nb");
+        let req = Request::builder().trailer(trailer).build();
+        assert!(req.try_into_one(Version::H11).is_err());
+    }
+
+    #[test]
+    fn test_two_try_into_one_request_method_injection() {
+        let req = Request::builder()
+            .method(Method::from("GET / HTTP/1.1\r\n\r\nGET"))
+            .build();
+        assert!(matches!(
+            req.try_into_one(Version::H11),
+            Err(DowngradeError::InvalidMethod(_))
+        ));
+    }
+
+    #[test]
+    fn test_one_to_two_request_connect() {
+        let input = "CONNECT example.com:443 HTTP/1.1\r\n\
+                     Host: example.com:443\r\n\r\n";
+        let one = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let req = Request::from(one);
+        assert_eq!(*req.method(), Method::CONNECT);
+        assert_eq!(req.authority(), Some("example.com:443"));
+        assert!(!req.is_extended_connect());
+
+        let input: &[u8] = b"CONNECT exa\xffmple.com:443 HTTP/1.1\r\n\r\n";
+        let one = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let req = Request::from(one);
+        assert_eq!(req.authority(), Some("exa\u{fffd}mple.com:443"));
+    }
+
+    #[test]
+    fn test_request_extended_connect() {
+        let req = Request::builder()
+            .method(Method::CONNECT)
+            .extension(Bytes::from_static(b"websocket"))
+            .build();
+        assert!(req.is_extended_connect());
+        assert_eq!(req.protocol().unwrap(), "websocket");
+    }
+
+    #[test]
+    fn test_request_builder_multipart() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "text/plain");
+        let form = MultipartBuilder::new().boundary("xyz").text("a", "1");
+        let req = Request::builder()
+            .method(Method::POST)
+            .multipart(form)
+            .unwrap()
+            .headers(headers)
+            .build();
+        let verify = "POST / HTTP/1.1\r\n\
+                      content-type: multipart/form-data; boundary=xyz\r\n\
+                      content-length: 63\r\n\r\n\
+                      --xyz\r\n\
+                      content-disposition: form-data; name=\"a\"\r\n\r\n\
+                      1\r\n\
+                      --xyz--\r\n";
+        let parts = req.multipart().unwrap().unwrap();
+        assert_eq!(parts[0].name().unwrap(), "a");
+        let one = req.try_into_one(Version::H11).unwrap();
+        assert_eq!(one.into_bytes(), verify);
+    }
+
+    fn build_extended_connect(headers: HeaderMap) -> Request {
+        let uri = Uri::builder()
+            .scheme("https")
+            .authority("example.com")
+            .path("/chat")
+            .build()
+            .unwrap();
+        Request::builder()
+            .method(Method::CONNECT)
+            .uri(uri)
+            .extension(Bytes::from_static(b"websocket"))
+            .headers(headers)
+            .build()
+    }
+
+    #[test]
+    fn test_request_try_into_one_upgrade_websocket() {
+        let mut headers = HeaderMap::new();
+        headers.insert("connection", "keep-alive");
+        let req = build_extended_connect(headers);
+        let one = req.try_into_one_upgrade().unwrap();
+        let header_map = one.message_head().header_map();
+        let key = header_map.value_of_key(SEC_WEBSOCKET_KEY).unwrap();
+        assert_eq!(STANDARD.decode(key).unwrap().len(), 16);
+        let verify = format!(
+            "GET /chat HTTP/1.1\r\n\
+             connection: keep-alive, upgrade\r\n\
+             upgrade: websocket\r\n\
+             sec-websocket-version: 13\r\n\
+             sec-websocket-key: {}\r\n\
+             host: example.com\r\n\r\n",
+            String::from_utf8_lossy(key)
+        );
+        assert_eq!(one.into_bytes(), verify.as_str());
+    }
+
+    #[test]
+    fn test_request_try_into_one_upgrade_keeps_handshake() {
+        let mut headers = HeaderMap::new();
+        headers.insert("connection", "Upgrade");
+        headers.insert("sec-websocket-version", "13");
+        headers.insert("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==");
+        let req = build_extended_connect(headers);
+        let verify = "GET /chat HTTP/1.1\r\n\
+                      connection: Upgrade\r\n\
+                      sec-websocket-version: 13\r\n\
+                      sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                      upgrade: websocket\r\n\
+                      host: example.com\r\n\r\n";
+        assert_eq!(req.try_into_one_upgrade().unwrap().into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_request_try_into_one_upgrade_errors() {
+        let req = Request::builder().method(Method::CONNECT).build();
+        assert_eq!(
+            req.try_into_one_upgrade().unwrap_err(),
+            DowngradeError::NotExtendedConnect
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("sec-websocket-version", "8");
+        let req = build_extended_connect(headers);
+        assert_eq!(
+            req.try_into_one_upgrade().unwrap_err(),
+            DowngradeError::InvalidWebSocketVersion("8".to_string())
+        );
+
+        let req = Request::builder()
+            .method(Method::CONNECT)
+            .extension(Bytes::from_static(b"web socket\r\n"))
+            .build();
+        assert!(matches!(
+            req.try_into_one_upgrade(),
+            Err(DowngradeError::InvalidProtocol(_))
+        ));
     }
 }
@@ -3,7 +3,8 @@ use header_plz::{ResponseLine, status::StatusCode};
 use crate::{
     Version,
     message::{
-        Message, builder::MessageBuilder, process_one_headers_and_body,
+        Message, builder::MessageBuilder, downgrade::DowngradeError,
+        process_one_headers_and_body,
     },
     one::OneResponse,
 };
@@ -18,13 +19,12 @@ impl ResponseBuilder {
     }
 
     pub fn build(self) -> Response {
-        Response {
-            info_line: self.info_line,
-            headers: self.headers.unwrap_or_default(),
-            body: self.body,
-            trailers: self.trailer,
-            body_headers: None,
-        }
+        Response::new(
+            self.info_line,
+            self.headers.unwrap_or_default(),
+            self.body,
+            self.trailer,
+        )
     }
 }
 
@@ -37,8 +37,17 @@ impl Response {
         self.info_line.status()
     }
 
-    pub fn into_one_rep(self) -> OneResponse {
-        OneResponse::from((self, Version::H2))
+    pub fn into_one_rep(self) -> Result<OneResponse, DowngradeError> {
+        self.try_into_one(Version::H2)
+    }
+
+    // Convert to HTTP/1.x, rejecting fields that would break message framing
+    pub fn try_into_one(
+        self,
+        version: Version,
+    ) -> Result<OneResponse, DowngradeError> {
+        self.validate_fields()?;
+        Ok(OneResponse::from_two(self, version))
     }
 }
 
@@ -180,6 +189,28 @@ mod tests {
             .headers(headers)
             .body(BytesMut::from("Hello"))
             .build();
-        assert_eq!(resp.into_one_rep().into_bytes(), expected);
+        assert_eq!(resp.into_one_rep().unwrap().into_bytes(), expected);
+    }
+
+    #[test]
+    fn test_two_try_into_one_response() {
+        let expected = "HTTP/1.1 200 OK\r\n\
+                        key: value\r\n\r\n";
+        let mut headers = HeaderMap::new();
+        headers.insert("key", "value");
+        let resp = Response::builder().headers(headers).build();
+        let one = resp.try_into_one(Version::H11).unwrap();
+        assert_eq!(one.into_bytes(), expected);
+    }
+
+    #[test]
+    fn test_two_try_into_one_response_header_injection() {
+        let mut headers = HeaderMap::new();
+        headers.insert("set-cookie", "a=b\r\nLocation: http://evil");
+        let resp = Response::builder().headers(headers).build();
+        assert_eq!(
+            resp.try_into_one(Version::H11).unwrap_err(),
+            DowngradeError::InvalidHeaderValue("set-cookie".to_string())
+        );
     }
 }
@@ -0,0 +1,287 @@
+use std::{fmt, net::Ipv6Addr};
+
+use bytes::BytesMut;
+use header_plz::{
+    HeaderMap, MessageHead, OneHeaderMap, OneRequestLine, OneResponseLine,
+    const_headers::{CONTENT_LENGTH, CONTENT_TYPE, PROXY_AUTHENTICATE},
+    status::StatusCode,
+};
+use thiserror::Error;
+
+use crate::{
+    OneOne, Response, Version,
+    auth::{AuthError, Challenge},
+};
+
+const CONNECTION_ESTABLISHED: &str = " Connection Established\r\n";
+const TEXT_PLAIN: &str = "text/plain";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ConnectError {
+    #[error("Not a CONNECT request")]
+    NotConnect,
+    #[error("Invalid Authority| {0}")]
+    InvalidAuthority(String),
+    #[error("Invalid Host| {0}")]
+    InvalidHost(String),
+    #[error("Invalid Port| {0}")]
+    InvalidPort(String),
+}
+
+// host is stored without brackets for IPv6
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectTarget {
+    pub host: String,
+    pub port: u16,
+}
+
+impl fmt::Display for ConnectTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/* Description:
+ *      Parse an authority-form request-target, RFC 9110 9.3.6.
+ *
+ *      host:port | [IPv6]:port
+ *
+ * Steps:
+ *      1. Reject userinfo, CONNECT targets carry no credentials.
+ *      2. Split host and port at the last ':', IPv6 hosts must be bracketed.
+ *      3. Port is mandatory, 1..=65535.
+ *      4. Host must be a valid IPv6 literal or a non-empty reg-name/IPv4.
+ *
+ * Error:
+ *      ConnectError::InvalidAuthority  [1, 2]
+ *      ConnectError::InvalidPort       [3]
+ *      ConnectError::InvalidHost       [4]
+ */
+
+pub fn parse_connect_target(
+    target: &[u8],
+) -> Result<ConnectTarget, ConnectError> {
+    let target = std::str::from_utf8(target.trim_ascii()).map_err(|_| {
+        ConnectError::InvalidAuthority(
+            String::from_utf8_lossy(target).to_string(),
+        )
+    })?;
+    let invalid_authority =
+        || ConnectError::InvalidAuthority(target.to_string());
+    if target.contains('@') {
+        return Err(invalid_authority());
+    }
+
+    let (host, port) = if let Some(rest) = target.strip_prefix('[') {
+        let (host, port) =
+            rest.split_once("]:").ok_or_else(invalid_authority)?;
+        host.parse::<Ipv6Addr>()
+            .map_err(|_| ConnectError::InvalidHost(host.to_string()))?;
+        (host, port)
+    } else {
+        target.rsplit_once(':').ok_or_else(invalid_authority)?
+    };
+
+    let port = port
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port != 0)
+        .filter(|_| port.bytes().all(|b| b.is_ascii_digit()))
+        .ok_or_else(|| ConnectError::InvalidPort(port.to_string()))?;
+
+    if host.is_empty()
+        || (!target.starts_with('[') && !host.bytes().all(is_reg_name_char))
+    {
+        return Err(ConnectError::InvalidHost(host.to_string()));
+    }
+
+    Ok(ConnectTarget {
+        host: host.to_ascii_lowercase(),
+        port,
+    })
+}
+
+// RFC 3986 reg-name, unreserved / pct-encoded / sub-delims
+#[inline]
+fn is_reg_name_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~%!$&'()*+,;=".contains(&b)
+}
+
+impl OneOne<OneRequestLine> {
+    pub fn connect_target(&self) -> Result<ConnectTarget, ConnectError> {
+        if !self.is_connect_request() {
+            return Err(ConnectError::NotConnect);
+        }
+        parse_connect_target(
+            self.message_head.infoline().uri_as_string().as_bytes(),
+        )
+    }
+}
+
+impl OneOne<OneResponseLine> {
+    // HTTP/1.1 200 Connection Established
+    pub fn connection_established() -> Self {
+        let info_line = OneResponseLine::new(
+            Version::H11.for_response_line().into(),
+            StatusCode::OK.as_str().into(),
+            CONNECTION_ESTABLISHED.into(),
+        );
+        let message_head =
+            MessageHead::new(info_line, OneHeaderMap::from(HeaderMap::new()));
+        let mut one = Self::new(message_head, None);
+        one.version = Some(Version::H11);
+        one
+    }
+
+    // 407 with one Proxy-Authenticate header per challenge
+    pub fn proxy_auth_required(
+        challenges: &[Challenge],
+    ) -> Result<Self, AuthError> {
+        let mut headers = HeaderMap::new();
+        for challenge in challenges {
+            headers.insert(PROXY_AUTHENTICATE, challenge.to_header_value()?);
+        }
+        headers.insert(CONTENT_LENGTH, "0");
+        let res = Response::builder()
+            .status(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+            .headers(headers)
+            .build();
+        Ok(Self::from_two(res, Version::H11))
+    }
+
+    // 502 with an optional plain text reason as body
+    pub fn bad_gateway(reason: Option<&str>) -> Self {
+        let mut headers = HeaderMap::new();
+        let mut builder = Response::builder().status(StatusCode::BAD_GATEWAY);
+        match reason {
+            Some(reason) => {
+                headers.insert(CONTENT_TYPE, TEXT_PLAIN);
+                builder = builder.body(BytesMut::from(reason));
+            }
+            None => headers.insert(CONTENT_LENGTH, "0"),
+        }
+        Self::from_two(builder.headers(headers).build(), Version::H11)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OneRequest, OneResponse, auth::AuthScheme};
+
+    use super::*;
+
+    fn target(host: &str, port: u16) -> ConnectTarget {
+        ConnectTarget {
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    fn test_parse_connect_target() {
+        assert_eq!(
+            parse_connect_target(b"Example.com:443").unwrap(),
+            target("example.com", 443)
+        );
+        assert_eq!(
+            parse_connect_target(b"127.0.0.1:8080").unwrap(),
+            target("127.0.0.1", 8080)
+        );
+        let ipv6 = parse_connect_target(b"[2001:db8::1]:443").unwrap();
+        assert_eq!(ipv6, target("2001:db8::1", 443));
+        assert_eq!(ipv6.to_string(), "[2001:db8::1]:443");
+    }
+
+    #[test]
+    fn test_parse_connect_target_invalid() {
+        assert_eq!(
+            parse_connect_target(b"example.com"),
+            Err(ConnectError::InvalidAuthority("example.com".to_string()))
+        );
+        assert_eq!(
+            parse_connect_target(b"user:pass@example.com:443"),
+            Err(ConnectError::InvalidAuthority(
+                "user:pass@example.com:443".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_connect_target(b"example.com:0"),
+            Err(ConnectError::InvalidPort("0".to_string()))
+        );
+        assert_eq!(
+            parse_connect_target(b"example.com:+443"),
+            Err(ConnectError::InvalidPort("+443".to_string()))
+        );
+        assert_eq!(
+            parse_connect_target(b"example.com:65536"),
+            Err(ConnectError::InvalidPort("65536".to_string()))
+        );
+        assert_eq!(
+            parse_connect_target(b":443"),
+            Err(ConnectError::InvalidHost("".to_string()))
+        );
+        assert_eq!(
+            parse_connect_target(b"exa mple.com:443"),
+            Err(ConnectError::InvalidHost("exa mple.com".to_string()))
+        );
+        assert_eq!(
+            parse_connect_target(b"[::g]:443"),
+            Err(ConnectError::InvalidHost("::g".to_string()))
+        );
+        assert!(parse_connect_target(b"2001:db8::1:443").is_err());
+    }
+
+    #[test]
+    fn test_connect_target() {
+        let input = "CONNECT example.com:443 HTTP/1.1\r\n\
+                     Host: example.com:443\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(req.connect_target().unwrap(), target("example.com", 443));
+
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(req.connect_target(), Err(ConnectError::NotConnect));
+    }
+
+    #[test]
+    fn test_connection_established() {
+        assert_eq!(
+            OneResponse::connection_established().into_bytes(),
+            "HTTP/1.1 200 Connection Established\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_proxy_auth_required() {
+        let res = OneResponse::proxy_auth_required(&[
+            Challenge::basic("proxy"),
+            Challenge::new(AuthScheme::Bearer),
+        ])
+        .unwrap();
+        let verify = "HTTP/1.1 407 Proxy Authentication Required\r\n\
+                      proxy-authenticate: Basic realm=\"proxy\"\r\n\
+                      proxy-authenticate: Bearer\r\n\
+                      content-length: 0\r\n\r\n";
+        assert_eq!(res.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_bad_gateway() {
+        let verify = "HTTP/1.1 502 Bad Gateway\r\n\
+                      content-length: 0\r\n\r\n";
+        assert_eq!(OneResponse::bad_gateway(None).into_bytes(), verify);
+
+        let verify = "HTTP/1.1 502 Bad Gateway\r\n\
+                      content-type: text/plain\r\n\
+                      content-length: 18\r\n\r\n\
+                      connection refused";
+        assert_eq!(
+            OneResponse::bad_gateway(Some("connection refused")).into_bytes(),
+            verify
+        );
+    }
+}
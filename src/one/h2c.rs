@@ -0,0 +1,360 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use header_plz::{
+    HeaderMap, OneRequestLine, OneResponseLine,
+    const_headers::{CONNECTION, HOST, UPGRADE},
+    status::StatusCode,
+    uri::scheme::Scheme,
+};
+use thiserror::Error;
+
+use crate::{
+    Request, Response, Version, one::OneOne, utils::token::has_token,
+};
+
+pub const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+pub const HTTP2_SETTINGS: &[u8] = b"http2-settings";
+const H2C: &str = "h2c";
+const UPGRADE_TOKEN: &str = "upgrade";
+const SETTING_LEN: usize = 6;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum H2cError {
+    #[error("Not a h2c upgrade")]
+    NotUpgrade,
+    #[error("Missing HTTP2-Settings")]
+    MissingSettings,
+    #[error("Invalid HTTP2-Settings| {0}")]
+    InvalidSettings(String),
+    #[error("Invalid Host| {0}")]
+    InvalidHost(String),
+    #[error("Invalid Scheme| {0}")]
+    InvalidScheme(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct H2Setting {
+    pub id: u16,
+    pub value: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PrefaceMatch {
+    Complete,
+    Partial,
+    None,
+}
+
+/* Description:
+ *      Check whether buf starts with the HTTP/2 connection preface.
+ *
+ * Returns:
+ *      PrefaceMatch::Complete  => buf contains the full preface.
+ *      PrefaceMatch::Partial   => buf is a prefix of the preface, read more.
+ *      PrefaceMatch::None      => not HTTP/2 with prior knowledge.
+ */
+
+pub fn detect_h2_preface(buf: &[u8]) -> PrefaceMatch {
+    if buf.starts_with(H2_PREFACE) {
+        PrefaceMatch::Complete
+    } else if !buf.is_empty() && H2_PREFACE.starts_with(buf) {
+        PrefaceMatch::Partial
+    } else {
+        PrefaceMatch::None
+    }
+}
+
+impl OneOne<OneRequestLine> {
+    // Connection has to list both Upgrade and HTTP2-Settings, RFC 7540 3.2
+    pub fn is_h2c_upgrade(&self) -> bool {
+        let header_map = self.message_head.header_map();
+        has_token(header_map, UPGRADE, H2C)
+            && has_token(header_map, CONNECTION, UPGRADE_TOKEN)
+            && has_token(header_map, CONNECTION, HTTP2_SETTINGS)
+            && header_map.has_key(HTTP2_SETTINGS)
+    }
+
+    /* Description:
+     *      Decode the base64url SETTINGS payload in the HTTP2-Settings header
+     *      (RFC 7540 3.2.1).
+     *
+     *      base64url without padding, trailing bits must be zero.
+     *
+     * Error:
+     *      H2cError::MissingSettings   [header not present]
+     *      H2cError::InvalidSettings   [not base64url or len % 6 != 0]
+     */
+
+    pub fn h2c_settings(&self) -> Result<Vec<H2Setting>, H2cError> {
+        let value = self
+            .message_head
+            .header_map()
+            .value_of_key(HTTP2_SETTINGS)
+            .ok_or(H2cError::MissingSettings)?;
+        let invalid = || {
+            H2cError::InvalidSettings(
+                String::from_utf8_lossy(value).to_string(),
+            )
+        };
+        let payload = URL_SAFE_NO_PAD
+            .decode(value.trim_ascii())
+            .map_err(|_| invalid())?;
+        if payload.len() % SETTING_LEN != 0 {
+            return Err(invalid());
+        }
+        let settings = payload
+            .chunks_exact(SETTING_LEN)
+            .map(|s| H2Setting {
+                id: u16::from_be_bytes([s[0], s[1]]),
+                value: u32::from_be_bytes([s[2], s[3], s[4], s[5]]),
+            })
+            .collect();
+        Ok(settings)
+    }
+
+    /* Description:
+     *      Convert the upgrade request into the HTTP/2 request on stream 1.
+     *
+     * Steps:
+     *      1. Verify it is a h2c upgrade.
+     *      2. Convert a chunked body to raw, trailers are merged.
+     *      3. Convert to Request.
+     *      4. Remove connection specific headers, RFC 9113 8.2.2.
+     *         HTTP2-Settings goes along as Connection lists it.
+     *      5. Set scheme to http and authority from Host.
+     *
+     * Error:
+     *      H2cError::NotUpgrade    [1]
+     *      H2cError::InvalidScheme [5]
+     *      H2cError::InvalidHost   [5]
+     */
+
+    pub fn into_h2c_stream(mut self) -> Result<Request, H2cError> {
+        if !self.is_h2c_upgrade() {
+            return Err(H2cError::NotUpgrade);
+        }
+        self.chunked_body_to_raw();
+        let mut req = Request::from(self);
+        req.strip_hop_by_hop();
+        req.info_line.try_set_scheme(Scheme::HTTP).map_err(|_| {
+            H2cError::InvalidScheme(Scheme::HTTP.as_str().to_string())
+        })?;
+        if let Some(host) = req.headers.value_of_key(HOST) {
+            let host = host.to_vec();
+            req.info_line.try_set_authority(host.as_slice()).map_err(
+                |_| {
+                    H2cError::InvalidHost(
+                        String::from_utf8_lossy(&host).to_string(),
+                    )
+                },
+            )?;
+        }
+        Ok(req)
+    }
+}
+
+impl OneOne<OneResponseLine> {
+    // HTTP/1.1 101 Switching Protocols
+    pub fn h2c_switching_protocols() -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, "Upgrade");
+        headers.insert(UPGRADE, H2C);
+        let res = Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .headers(headers)
+            .build();
+        Self::from_two(res, Version::H11)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use body_plz::variants::{Body, chunked::ChunkType};
+    use bytes::BytesMut;
+    use header_plz::{Method, message_head::header_map::HeaderStr};
+
+    use crate::{OneRequest, OneResponse};
+
+    use super::*;
+
+    #[test]
+    fn test_detect_h2_preface() {
+        assert_eq!(detect_h2_preface(H2_PREFACE), PrefaceMatch::Complete);
+        let mut buf = H2_PREFACE.to_vec();
+        buf.extend_from_slice(b"\x00\x00\x00\x04");
+        assert_eq!(detect_h2_preface(&buf), PrefaceMatch::Complete);
+        assert_eq!(detect_h2_preface(b"PRI * HT"), PrefaceMatch::Partial);
+        assert_eq!(detect_h2_preface(b"GET / HTTP/1.1"), PrefaceMatch::None);
+        assert_eq!(detect_h2_preface(b""), PrefaceMatch::None);
+    }
+
+    #[test]
+    fn test_is_h2c_upgrade() {
+        // SETTINGS_MAX_CONCURRENT_STREAMS = 100,
+        // SETTINGS_INITIAL_WINDOW_SIZE = 65535
+        let input = "GET /index HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Upgrade, HTTP2-Settings\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: AAMAAABkAAQAAP__\r\n\
+                     Accept: */*\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.is_h2c_upgrade());
+
+        let input = "GET / HTTP/1.1\r\n\
+                     Upgrade: h2c\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(!req.is_h2c_upgrade());
+
+        let input = "GET / HTTP/1.1\r\n\
+                     Connection: Upgrade\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: \r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(!req.is_h2c_upgrade());
+    }
+
+    #[test]
+    fn test_h2c_settings() {
+        let input = "GET /index HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Upgrade, HTTP2-Settings\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: AAMAAABkAAQAAP__\r\n\
+                     Accept: */*\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let verify = vec![
+            H2Setting {
+                id: 3,
+                value: 100,
+            },
+            H2Setting {
+                id: 4,
+                value: 65535,
+            },
+        ];
+        assert_eq!(req.h2c_settings().unwrap(), verify);
+    }
+
+    #[test]
+    fn test_h2c_settings_empty() {
+        let input = "GET /index HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Upgrade, HTTP2-Settings\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: \r\n\
+                     Accept: */*\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.h2c_settings().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_h2c_settings_invalid_len() {
+        let input = "GET /index HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Upgrade, HTTP2-Settings\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: AAMAAABkAA\r\n\
+                     Accept: */*\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(matches!(
+            req.h2c_settings(),
+            Err(H2cError::InvalidSettings(_))
+        ));
+    }
+
+    #[test]
+    fn test_h2c_settings_non_canonical() {
+        let input = "GET /index HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Upgrade, HTTP2-Settings\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: AAMAAABkAAQAAP__=\r\n\
+                     Accept: */*\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.h2c_settings(),
+            Err(H2cError::InvalidSettings("AAMAAABkAAQAAP__=".to_string()))
+        );
+
+        let input = "GET /index HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Upgrade, HTTP2-Settings\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: AAMAAABkAAQAAP//\r\n\
+                     Accept: */*\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.h2c_settings(),
+            Err(H2cError::InvalidSettings("AAMAAABkAAQAAP//".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_h2c_switching_protocols() {
+        let verify = "HTTP/1.1 101 Switching Protocols\r\n\
+                      connection: Upgrade\r\n\
+                      upgrade: h2c\r\n\r\n";
+        assert_eq!(
+            OneResponse::h2c_switching_protocols().into_bytes(),
+            verify
+        );
+    }
+
+    #[test]
+    fn test_into_h2c_stream() {
+        let input = "GET /index HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Upgrade, HTTP2-Settings\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: AAMAAABkAAQAAP__\r\n\
+                     Accept: */*\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let req = req.into_h2c_stream().unwrap();
+        assert_eq!(*req.method(), Method::GET);
+        assert_eq!(req.path(), "/index");
+        assert_eq!(req.scheme(), Some(&Scheme::HTTP));
+        assert_eq!(req.authority(), Some("example.com"));
+        let keys: Vec<&str> = req
+            .headers()
+            .iter()
+            .filter(|h| !h.is_empty())
+            .filter_map(|h| h.key_as_str())
+            .collect();
+        assert_eq!(keys, vec!["host", "accept"]);
+    }
+
+    #[test]
+    fn test_into_h2c_stream_chunked() {
+        let input = "POST /upload HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Connection: Upgrade, HTTP2-Settings, Keep-Alive\r\n\
+                     Keep-Alive: timeout=5\r\n\
+                     Upgrade: h2c\r\n\
+                     HTTP2-Settings: AAMAAABkAAQAAP__\r\n\
+                     Transfer-Encoding: chunked\r\n\
+                     TE: trailers\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.body = Some(Body::Chunked(vec![
+            ChunkType::Size("5\r\n".into()),
+            ChunkType::Chunk("hello\r\n".into()),
+            ChunkType::LastChunk("0\r\n".into()),
+            ChunkType::EndCRLF("\r\n".into()),
+        ]));
+        let req = req.into_h2c_stream().unwrap();
+        assert_eq!(req.body_as_ref().unwrap().as_ref(), b"hello");
+        let keys: Vec<&str> = req
+            .headers()
+            .iter()
+            .filter(|h| !h.is_empty())
+            .filter_map(|h| h.key_as_str())
+            .collect();
+        assert_eq!(keys, vec!["host"]);
+    }
+
+    #[test]
+    fn test_into_h2c_stream_not_upgrade() {
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(req.into_h2c_stream().unwrap_err(), H2cError::NotUpgrade);
+    }
+}
@@ -1,3 +1,10 @@
+use std::borrow::Cow;
+
+use crate::{
+    Version,
+    compression::decode::{body_encodings, chunk_data, header_encodings},
+    one::version::parse_line_version,
+};
 use body_plz::variants::{Body, chunked::ChunkType};
 use bytes::BytesMut;
 use decompression_plz::{MultiDecompressErrorReason, decompress};
@@ -5,20 +12,28 @@ use header_plz::{
     HeaderMap, OneHeaderMap, OneInfoLine, OneMessageHead, OneRequestLine,
     OneResponseLine,
     body_headers::{
-        BodyHeader, parse::ParseBodyHeaders, transfer_types::TransferType,
+        BodyHeader, content_encoding::ContentEncoding,
+        parse::ParseBodyHeaders, transfer_types::TransferType,
     },
     const_headers::{
-        CLOSE, CONNECTION, CONTENT_LENGTH, KEEP_ALIVE, PROXY_CONNECTION,
-        SEC_WEBSOCKET_EXTENSIONS, TRAILER,
+        CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, KEEP_ALIVE,
+        PROXY_CONNECTION, TRAILER,
     },
     error::HeaderReadError,
 };
+
+pub mod connect;
+pub mod h2c;
 pub mod impl_decompress;
 
 pub mod impl_try_from_bytes;
+pub mod normalize;
 pub mod parse;
+pub mod persistence;
 mod request;
+pub mod request_target;
 mod response;
+pub mod version;
 
 pub type OneRequest = OneOne<OneRequestLine>;
 pub type OneResponse = OneOne<OneResponseLine>;
@@ -29,9 +44,13 @@ where
     T: OneInfoLine,
 {
     pub(crate) message_head: OneMessageHead<T>,
-    body_headers: Option<BodyHeader>,
-    body: Option<Body>,
-    extra_body: Option<BytesMut>,
+    pub(crate) body_headers: Option<BodyHeader>,
+    pub(crate) body: Option<Body>,
+    pub(crate) extra_body: Option<BytesMut>,
+    // content-codings removed by try_decompress(), in header order
+    decoded_encodings: Vec<ContentEncoding>,
+    // read from the info line at parse time, see version()
+    version: Option<Version>,
 }
 
 impl<T> OneOne<T>
@@ -48,6 +67,8 @@ where
             body_headers,
             body: None,
             extra_body: None,
+            decoded_encodings: Vec::new(),
+            version: None,
         }
     }
 
@@ -55,9 +76,20 @@ where
     pub fn try_from_message_head_buf(
         buf: BytesMut,
     ) -> Result<Self, HeaderReadError> {
+        let version = parse_line_version(&buf);
         let message_head = OneMessageHead::<T>::try_from(buf)?;
         let body_headers = message_head.parse_body_headers();
-        Ok(OneOne::<T>::new(message_head, body_headers))
+        let mut one = OneOne::<T>::new(message_head, body_headers);
+        one.version = version;
+        Ok(one)
+    }
+
+    /* HTTP version of the info line, recorded when parsed or built from a
+     * Request/Response and kept by set_version(). None for an unknown
+     * version or a message built with new().
+     */
+    pub fn version(&self) -> Option<Version> {
+        self.version
     }
 
     // Header Related methods
@@ -123,6 +155,22 @@ where
         self.body.as_mut()
     }
 
+    // chunk data is copied, None for a missing or empty body
+    pub(crate) fn body_data(&self) -> Option<Cow<'_, [u8]>> {
+        let data = match self.body.as_ref()? {
+            Body::Raw(raw) => Cow::Borrowed(raw.as_ref()),
+            Body::Chunked(chunks) => Cow::Owned(
+                chunks
+                    .iter()
+                    .filter_map(chunk_data)
+                    .flatten()
+                    .copied()
+                    .collect(),
+            ),
+        };
+        (!data.is_empty()).then_some(data)
+    }
+
     pub fn set_extra_body(&mut self, extra_body: BytesMut) {
         self.extra_body = Some(extra_body);
     }
@@ -131,6 +179,15 @@ where
         self.body.take()
     }
 
+    // bytes received after the message, e.g. websocket frames after a 101
+    pub fn extra_body(&self) -> Option<&BytesMut> {
+        self.extra_body.as_ref()
+    }
+
+    pub fn take_extra_body(&mut self) -> Option<BytesMut> {
+        self.extra_body.take()
+    }
+
     // checkers
     pub fn has_connection_keep_alive(&self) -> Option<usize> {
         self.message_head
@@ -142,22 +199,44 @@ where
         self.message_head.header_map().header_key_position(PROXY_CONNECTION)
     }
 
-    // Normalize
-    pub fn normalize(&mut self) {
-        if let Some(pos) = self.has_connection_keep_alive() {
-            self.update_header_value_on_position(pos, CLOSE);
-        }
-        if let Some(pos) = self.has_proxy_connection() {
-            self.remove_header_on_position(pos);
-        }
-        self.remove_header_on_key(SEC_WEBSOCKET_EXTENSIONS);
-    }
-
+    /* Content-codings that were decoded, also on a partial error, are kept
+     * for reencode(). Decoding starts from the last coding, the removed
+     * ones are the tail of the original chain.
+     */
     pub fn try_decompress(
         &mut self,
         buf: &mut BytesMut,
     ) -> Result<(), MultiDecompressErrorReason> {
-        decompress(self, buf)
+        let before = self.content_encodings();
+        let result = decompress(self, buf);
+        let remaining = self.content_encodings().len().min(before.len());
+        self.decoded_encodings = before[remaining..].to_vec();
+        result
+    }
+
+    pub fn decoded_encodings(&self) -> &[ContentEncoding] {
+        &self.decoded_encodings
+    }
+
+    pub(crate) fn take_decoded_encodings(&mut self) -> Vec<ContentEncoding> {
+        std::mem::take(&mut self.decoded_encodings)
+    }
+
+    pub(crate) fn set_decoded_encodings(
+        &mut self,
+        encodings: Vec<ContentEncoding>,
+    ) {
+        self.decoded_encodings = encodings;
+    }
+
+    // Content-Encoding chain, without identity
+    pub(crate) fn content_encodings(&self) -> Vec<ContentEncoding> {
+        header_encodings(self.message_head.header_map(), CONTENT_ENCODING)
+    }
+
+    // codings applied to the body, Content-Encoding and Transfer-Encoding
+    pub(crate) fn body_encodings(&self) -> Vec<ContentEncoding> {
+        body_encodings(self.message_head.header_map())
     }
 
     pub fn into_bytes(self) -> BytesMut {
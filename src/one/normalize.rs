@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+use header_plz::{
+    OneInfoLine, OneMessageHead,
+    body_headers::parse::ParseBodyHeaders,
+    const_headers::{CLOSE, PROXY_CONNECTION, SEC_WEBSOCKET_EXTENSIONS},
+    message_head::header_map::Hmap,
+};
+
+use crate::{
+    OneOne, Version,
+    compression::accept::{decodable_encodings, restrict_accept_encoding},
+    one::version::SetVersion,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NormalizeRule {
+    // Connection: keep-alive => Connection: close
+    ConnectionClose,
+    RemoveProxyConnection,
+    RemoveWebSocketExtensions,
+    ForceHttp11,
+    LowercaseHeaderNames,
+    // drop repeated headers with the same name and value
+    DedupeHeaders,
+    // drop Accept-Encoding codings that cannot be decoded
+    FilterAcceptEncoding,
+    RemoveHeader(Bytes),
+}
+
+/* Description:
+ *      Ordered set of rules applied by OneOne::normalize_with().
+ *
+ *      Default is the proxy preset, ConnectionClose, RemoveProxyConnection
+ *      and RemoveWebSocketExtensions.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizePolicy {
+    rules: Vec<NormalizeRule>,
+}
+
+impl Default for NormalizePolicy {
+    fn default() -> Self {
+        NormalizePolicy {
+            rules: vec![
+                NormalizeRule::ConnectionClose,
+                NormalizeRule::RemoveProxyConnection,
+                NormalizeRule::RemoveWebSocketExtensions,
+            ],
+        }
+    }
+}
+
+impl NormalizePolicy {
+    // no rules
+    pub fn empty() -> Self {
+        NormalizePolicy {
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, rule: NormalizeRule) -> Self {
+        if !self.rules.contains(&rule) {
+            self.rules.push(rule);
+        }
+        self
+    }
+
+    pub fn without(mut self, rule: &NormalizeRule) -> Self {
+        self.rules.retain(|r| r != rule);
+        self
+    }
+
+    pub fn contains(&self, rule: &NormalizeRule) -> bool {
+        self.rules.contains(rule)
+    }
+
+    pub fn rules(&self) -> &[NormalizeRule] {
+        &self.rules
+    }
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + SetVersion + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    pub fn normalize(&mut self) {
+        self.normalize_with(&NormalizePolicy::default())
+    }
+
+    pub fn normalize_with(&mut self, policy: &NormalizePolicy) {
+        for rule in policy.rules() {
+            self.apply_rule(rule);
+        }
+    }
+
+    fn apply_rule(&mut self, rule: &NormalizeRule) {
+        match rule {
+            NormalizeRule::ConnectionClose => {
+                if let Some(pos) = self.has_connection_keep_alive() {
+                    self.update_header_value_on_position(pos, CLOSE);
+                }
+            }
+            NormalizeRule::RemoveProxyConnection => {
+                self.message_head
+                    .header_map_as_mut()
+                    .remove_header_on_key_all(PROXY_CONNECTION);
+            }
+            NormalizeRule::RemoveWebSocketExtensions => {
+                self.remove_header_on_key(SEC_WEBSOCKET_EXTENSIONS);
+            }
+            NormalizeRule::ForceHttp11 => {
+                self.message_head.infoline_as_mut().set_version(Version::H11);
+                self.version = Some(Version::H11);
+            }
+            NormalizeRule::LowercaseHeaderNames => {
+                for header in self.message_head.header_map_as_mut().iter_mut()
+                {
+                    let key = header.key_as_ref();
+                    if key.iter().any(u8::is_ascii_uppercase) {
+                        let key = key.to_ascii_lowercase();
+                        header.change_key(&key);
+                    }
+                }
+            }
+            NormalizeRule::DedupeHeaders => self.dedupe_headers(),
+            NormalizeRule::FilterAcceptEncoding => restrict_accept_encoding(
+                self.message_head.header_map_as_mut(),
+                &decodable_encodings(),
+            ),
+            NormalizeRule::RemoveHeader(key) => {
+                self.message_head
+                    .header_map_as_mut()
+                    .remove_header_on_key_all(key.as_ref());
+            }
+        }
+    }
+
+    // keep the first occurrence
+    fn dedupe_headers(&mut self) {
+        let mut seen = HashSet::new();
+        let duplicates: Vec<usize> = self
+            .message_head
+            .header_map()
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| !h.key_as_ref().is_empty())
+            .filter(|(_, h)| {
+                !seen.insert((
+                    h.key_as_ref().to_ascii_lowercase(),
+                    h.value_as_ref().trim_ascii().to_vec(),
+                ))
+            })
+            .map(|(pos, _)| pos)
+            .collect();
+        self.message_head
+            .header_map_as_mut()
+            .remove_header_multiple_positions(duplicates.into_iter());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use crate::{OneRequest, OneResponse};
+
+    use super::*;
+
+    #[test]
+    fn test_normalize_default() {
+        let input = "GET / HTTP/1.0\r\n\
+                     Connection: keep-alive\r\n\
+                     Proxy-Connection: keep-alive\r\n\
+                     Sec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.normalize();
+        let verify = "GET / HTTP/1.0\r\n\
+                      Connection: close\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_normalize_without_rule() {
+        let input = "GET / HTTP/1.0\r\n\
+                     Connection: keep-alive\r\n\
+                     Proxy-Connection: keep-alive\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let policy = NormalizePolicy::default()
+            .without(&NormalizeRule::ConnectionClose);
+        assert!(!policy.contains(&NormalizeRule::ConnectionClose));
+        req.normalize_with(&policy);
+        let verify = "GET / HTTP/1.0\r\n\
+                      Connection: keep-alive\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_normalize_empty_policy() {
+        let input = "GET / HTTP/1.0\r\n\
+                     Proxy-Connection: keep-alive\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.normalize_with(&NormalizePolicy::empty());
+        assert_eq!(req.into_bytes(), input);
+    }
+
+    #[test]
+    fn test_normalize_force_http11() {
+        let policy = NormalizePolicy::empty().with(NormalizeRule::ForceHttp11);
+        let input = "GET / HTTP/1.0\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.normalize_with(&policy);
+        assert_eq!(req.into_bytes(), "GET / HTTP/1.1\r\n\r\n");
+
+        let input = "HTTP/1.0 200 OK\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(res.version(), Some(Version::H10));
+        res.normalize_with(&policy);
+        assert_eq!(res.version(), Some(Version::H11));
+        assert_eq!(res.into_bytes(), "HTTP/1.1 200 OK\r\n\r\n");
+    }
+
+    #[test]
+    fn test_normalize_lowercase_and_dedupe() {
+        let input = "GET / HTTP/1.0\r\n\
+                     Host: example.com\r\n\
+                     X-Custom: a\r\n\
+                     x-custom: a\r\n\
+                     X-Custom: b\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let policy = NormalizePolicy::empty()
+            .with(NormalizeRule::LowercaseHeaderNames)
+            .with(NormalizeRule::DedupeHeaders);
+        req.normalize_with(&policy);
+        let verify = "GET / HTTP/1.0\r\n\
+                      host: example.com\r\n\
+                      x-custom: a\r\n\
+                      x-custom: b\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_normalize_filter_accept_encoding() {
+        let input = "GET / HTTP/1.0\r\n\
+                     Accept-Encoding: gzip, sdch;q=0.5, br;q=0.8, *\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let policy =
+            NormalizePolicy::empty().with(NormalizeRule::FilterAcceptEncoding);
+        req.normalize_with(&policy);
+        let verify = "GET / HTTP/1.0\r\n\
+                      Accept-Encoding: gzip, br;q=0.8\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+
+        let input = "GET / HTTP/1.0\r\n\
+                     Accept-Encoding: sdch, compress\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        req.normalize_with(&policy);
+        let verify = "GET / HTTP/1.0\r\n\
+                      Accept-Encoding: identity\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_normalize_remove_header() {
+        let input = "GET / HTTP/1.0\r\n\
+                     X-Forwarded-For: 10.0.0.1\r\n\
+                     Accept: */*\r\n\
+                     x-forwarded-for: 10.0.0.2\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let policy = NormalizePolicy::empty()
+            .with(NormalizeRule::RemoveHeader(Bytes::from("x-forwarded-for")));
+        req.normalize_with(&policy);
+        let verify = "GET / HTTP/1.0\r\n\
+                      Accept: */*\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+}
@@ -0,0 +1,219 @@
+use header_plz::{
+    OneInfoLine, OneMessageHead, OneResponseLine,
+    body_headers::{parse::ParseBodyHeaders, transfer_types::TransferType},
+    const_headers::{CLOSE, CONNECTION, KEEP_ALIVE},
+    status::StatusCode,
+};
+
+use crate::{
+    OneOne, OneRequest, Version, hop_by_hop::KEEP_ALIVE_HEADER,
+    utils::token::has_token,
+};
+
+// Keep-Alive: timeout=5, max=100
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAliveParams {
+    pub timeout: Option<u64>,
+    pub max: Option<u64>,
+}
+
+// unknown and malformed params are ignored
+pub fn parse_keep_alive(value: &[u8]) -> KeepAliveParams {
+    let mut params = KeepAliveParams::default();
+    for param in value.split(|b| *b == b',') {
+        let mut parts = param.splitn(2, |b| *b == b'=');
+        let name = parts.next().unwrap_or_default().trim_ascii();
+        let Some(value) = parts
+            .next()
+            .and_then(|v| std::str::from_utf8(v.trim_ascii()).ok())
+            .and_then(|v| v.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        if name.eq_ignore_ascii_case(b"timeout") {
+            params.timeout = Some(value);
+        } else if name.eq_ignore_ascii_case(b"max") {
+            params.max = Some(value);
+        }
+    }
+    params
+}
+
+impl<T> OneOne<T>
+where
+    T: OneInfoLine + std::fmt::Debug,
+    OneMessageHead<T>: ParseBodyHeaders,
+{
+    pub fn keep_alive_params(&self) -> Option<KeepAliveParams> {
+        self.message_head
+            .header_map()
+            .value_of_key(KEEP_ALIVE_HEADER)
+            .map(parse_keep_alive)
+    }
+
+    /* Description:
+     *      Whether the connection may be reused after this message,
+     *      RFC 9112 9.3.
+     *
+     * Steps:
+     *      1. Body delimited by the connection close => false.
+     *      2. Connection: close => false.
+     *      3. Keep-Alive: max=0 => false.
+     *      4. HTTP/1.1 and later persist by default, HTTP/1.0 only with
+     *         Connection: keep-alive. HTTP/0.9 and unknown versions never.
+     */
+
+    pub fn is_persistent(&self) -> bool {
+        let reads_until_close = self
+            .body_headers()
+            .as_ref()
+            .is_some_and(|bh| bh.transfer_type == Some(TransferType::Close));
+        let header_map = self.message_head.header_map();
+        if reads_until_close
+            || has_token(header_map, CONNECTION, CLOSE)
+            || self.keep_alive_params().is_some_and(|p| p.max == Some(0))
+        {
+            return false;
+        }
+        let keep_alive = has_token(header_map, CONNECTION, KEEP_ALIVE);
+        match self.version() {
+            Some(Version::H11 | Version::H2 | Version::H3) => true,
+            Some(Version::H10) => keep_alive,
+            Some(Version::H09) | None => false,
+        }
+    }
+}
+
+impl OneOne<OneResponseLine> {
+    /* Description:
+     *      Whether the connection may carry another request after this
+     *      response to req.
+     *
+     *      Both messages have to be persistent. A 101 or a 2xx to CONNECT
+     *      turns the connection into a tunnel, it is no longer HTTP.
+     */
+
+    pub fn is_persistent_for(&self, req: &OneRequest) -> bool {
+        let switched = match self.status_code() {
+            Ok(status) => {
+                status == StatusCode::SWITCHING_PROTOCOLS
+                    || (req.is_connect_request() && status.is_success())
+            }
+            Err(_) => return false,
+        };
+        !switched && req.is_persistent() && self.is_persistent()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use crate::OneResponse;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_keep_alive() {
+        assert_eq!(
+            parse_keep_alive(b"timeout=5, max=100"),
+            KeepAliveParams {
+                timeout: Some(5),
+                max: Some(100)
+            }
+        );
+        assert_eq!(
+            parse_keep_alive(b"Timeout = 7, max, foo=1, max=x"),
+            KeepAliveParams {
+                timeout: Some(7),
+                max: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_is_persistent_version() {
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.is_persistent());
+
+        let input = "GET / HTTP/1.0\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(!req.is_persistent());
+
+        let input = "GET / HTTP/1.0\r\n\
+                     Connection: Keep-Alive\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.is_persistent());
+
+        let input = "GET / HTTP/0.9\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(!req.is_persistent());
+    }
+
+    #[test]
+    fn test_is_persistent_connection_close() {
+        let input = "GET / HTTP/1.1\r\n\
+                     Connection: upgrade, close\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(!req.is_persistent());
+
+        let input = "GET / HTTP/1.0\r\n\
+                     Connection: keep-alive\r\n\
+                     Keep-Alive: timeout=5, max=0\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(!req.is_persistent());
+    }
+
+    #[test]
+    fn test_is_persistent_transfer_type_close() {
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 0\r\n\r\n";
+        let mut res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert!(res.is_persistent());
+        res.set_transfer_type_close();
+        assert!(!res.is_persistent());
+    }
+
+    #[test]
+    fn test_is_persistent_for() {
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let input = "HTTP/1.1 200 OK\r\n\
+                     Content-Length: 0\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert!(res.is_persistent_for(&req));
+
+        let input = "GET / HTTP/1.1\r\n\
+                     Connection: close\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(!res.is_persistent_for(&req));
+
+        let input = "GET / HTTP/1.0\r\n\
+                     Connection: keep-alive\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let input = "HTTP/1.0 200 OK\r\n\
+                     Connection: keep-alive\r\n\
+                     Keep-Alive: timeout=5\r\n\
+                     Content-Length: 0\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert!(res.is_persistent_for(&req));
+        assert_eq!(res.keep_alive_params().unwrap().timeout, Some(5));
+    }
+
+    #[test]
+    fn test_is_persistent_for_tunnel() {
+        let input = "CONNECT example.com:443 HTTP/1.1\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let res = OneResponse::connection_established();
+        assert!(!res.is_persistent_for(&req));
+
+        let input = "HTTP/1.1 101 Switching Protocols\r\n\
+                     Connection: upgrade\r\n\
+                     Upgrade: websocket\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(!res.is_persistent_for(&req));
+    }
+}
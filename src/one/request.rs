@@ -1,11 +1,12 @@
 use crate::{
     Request, Version,
+    message::{cookie::join_cookie_crumbs, downgrade::DowngradeError},
     one::{OneRequest, process_two_headers_and_body},
 };
 use body_plz::variants::Body;
 use bytes::{BufMut, BytesMut};
 use decompression_plz::DecompressTrait;
-use header_plz::{MessageHead, const_headers::HOST, uri::Uri};
+use header_plz::{MessageHead, const_headers::HOST};
 use std::borrow::Cow;
 
 use header_plz::{
@@ -36,7 +37,7 @@ impl OneOne<OneRequestLine> {
 #[inline]
 fn build_one_request_line_with_version(
     method: Method,
-    uri: &Uri,
+    target: &str,
     version: Version,
 ) -> OneRequestLine {
     let mut method_bytes = BytesMut::with_capacity(method.len() + 1);
@@ -44,23 +45,36 @@ fn build_one_request_line_with_version(
     method_bytes.put_u8(b' ');
     OneRequestLine::new(
         method_bytes,
-        uri.path_and_query().as_str().into(),
+        target.into(),
         version.for_request_line().into(),
     )
 }
 
-impl From<(Request, Version)> for OneRequest {
-    fn from((mut req, version): (Request, Version)) -> Self {
+impl OneRequest {
+    // Unchecked, fields must already be validated, see Request::try_into_one()
+    pub(crate) fn from_two(mut req: Request, version: Version) -> Self {
         let body = req.take_body();
         let trailer = req.take_trailers();
 
+        let headers = join_cookie_crumbs(req.headers);
         let mut header_map =
-            process_two_headers_and_body(req.headers, body.as_ref(), trailer);
+            process_two_headers_and_body(headers, body.as_ref(), trailer);
 
-        let (method, uri, _) = req.info_line.into_parts();
+        let (method, uri, extension) = req.info_line.into_parts();
+
+        /* CONNECT
+         *      classic     => authority-form target.
+         *      extended    => kept as is, see Request::try_into_one_upgrade()
+         */
+        let (method, target) = match (method, uri.authority(), extension) {
+            (Method::CONNECT, Some(authority), None) => {
+                (Method::CONNECT, authority)
+            }
+            (method, ..) => (method, uri.path_and_query().as_str()),
+        };
 
         let info_line =
-            build_one_request_line_with_version(method, &uri, version);
+            build_one_request_line_with_version(method, target, version);
 
         if let Some(host) = uri.authority()
             && !header_map.has_key(HOST)
@@ -70,6 +84,7 @@ impl From<(Request, Version)> for OneRequest {
 
         let message_head = MessageHead::new(info_line, header_map);
         let mut one = OneRequest::new(message_head, req.body_headers);
+        one.version = Some(version);
 
         if let Some(body) = body
             && !body.is_empty()
@@ -81,16 +96,28 @@ impl From<(Request, Version)> for OneRequest {
     }
 }
 
-impl From<Request> for OneRequest {
-    fn from(req: Request) -> OneRequest {
-        OneRequest::from((req, Version::H11))
+impl TryFrom<(Request, Version)> for OneRequest {
+    type Error = DowngradeError;
+
+    fn try_from(
+        (req, version): (Request, Version),
+    ) -> Result<Self, Self::Error> {
+        req.try_into_one(version)
+    }
+}
+
+impl TryFrom<Request> for OneRequest {
+    type Error = DowngradeError;
+
+    fn try_from(req: Request) -> Result<Self, Self::Error> {
+        req.try_into_one(Version::H11)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use bytes::BytesMut;
-    use header_plz::uri::path::PathAndQuery;
+    use bytes::{Bytes, BytesMut};
+    use header_plz::uri::{Uri, path::PathAndQuery};
     use header_plz::{HeaderMap, OneInfoLine, const_headers::CONTENT_LENGTH};
 
     use super::*;
@@ -100,7 +127,7 @@ mod tests {
         let uri = Uri::builder().path("/foo?a=1&b=2#23").build().unwrap();
         let line = build_one_request_line_with_version(
             Method::GET,
-            &uri,
+            uri.path_and_query().as_str(),
             Version::H11,
         );
         let input = "GET /foo?a=1&b=2 HTTP/1.1\r\n";
@@ -113,7 +140,7 @@ mod tests {
         let uri = Uri::default();
         let line = build_one_request_line_with_version(
             Method::GET,
-            &uri,
+            uri.path_and_query().as_str(),
             Version::H2,
         );
         let input = "GET / HTTP/2\r\n";
@@ -129,8 +156,11 @@ mod tests {
         )
         .unwrap();
         let uri = Uri::builder().path(path).build().unwrap();
-        let line =
-            build_one_request_line_with_version(method, &uri, Version::H3);
+        let line = build_one_request_line_with_version(
+            method,
+            uri.path_and_query().as_str(),
+            Version::H3,
+        );
 
         let input = "GET /search?q=hello%20world&lang=en HTTP/3\r\n";
         let verify = OneRequestLine::try_build_infoline(input.into()).unwrap();
@@ -143,7 +173,7 @@ mod tests {
             Request::builder().method(Method::GET).uri(Uri::default()).build();
         let input = "GET / HTTP/1.1\r\n\r\n";
         let one = OneRequest::try_from(BytesMut::from(input)).unwrap();
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -156,7 +186,7 @@ mod tests {
         let input = "GET / HTTP/1.1\r\n\
                    key: value\r\n\r\n";
         let one = OneRequest::try_from(BytesMut::from(input)).unwrap();
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -177,7 +207,23 @@ mod tests {
                      key4: value4\r\n\
                      key5: value5\r\n\r\n";
         let one = OneRequest::try_from(BytesMut::from(input)).unwrap();
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
+    }
+
+    #[test]
+    fn test_two_to_one_request_cookie_crumbs() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", "a=1");
+        headers.insert("key", "value");
+        headers.insert("cookie", "b=2");
+        let verify =
+            Request::builder().method(Method::GET).headers(headers).build();
+
+        let input = "GET / HTTP/1.1\r\n\
+                     cookie: a=1; b=2\r\n\
+                     key: value\r\n\r\n";
+        let one = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -192,7 +238,7 @@ mod tests {
                    Hello";
         let mut one = OneRequest::try_from(BytesMut::from(input)).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -206,7 +252,7 @@ mod tests {
                    content-length: 0\r\n\r\n";
         let mut one = OneRequest::try_from(BytesMut::from(input)).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -230,6 +276,44 @@ mod tests {
         let mut one =
             OneRequest::try_from(BytesMut::from(input.as_bytes())).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
+    }
+
+    #[test]
+    fn test_two_to_one_request_connect() {
+        let uri = Uri::builder().authority("example.com:443").build().unwrap();
+        let req = Request::builder().method(Method::CONNECT).uri(uri).build();
+        let verify = "CONNECT example.com:443 HTTP/1.1\r\n\
+                      host: example.com:443\r\n\r\n";
+        assert_eq!(OneRequest::try_from(req).unwrap().into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_two_to_one_request_extended_connect() {
+        let uri = Uri::builder()
+            .scheme("https")
+            .authority("example.com")
+            .path("/chat")
+            .build()
+            .unwrap();
+        let req = Request::builder()
+            .method(Method::CONNECT)
+            .uri(uri)
+            .extension(Bytes::from_static(b"websocket"))
+            .build();
+        let verify = "CONNECT /chat HTTP/1.1\r\n\
+                      host: example.com\r\n\r\n";
+        assert_eq!(OneRequest::try_from(req).unwrap().into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_two_to_one_request_header_injection() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-key", "a\r\n\r\nGET /admin HTTP/1.1");
+        let req = Request::builder().headers(headers).build();
+        assert_eq!(
+            OneRequest::try_from(req).unwrap_err(),
+            DowngradeError::InvalidHeaderValue("x-key".to_string())
+        );
     }
 }
@@ -9,6 +9,7 @@ use header_plz::{
 
 use crate::{
     Response, Version,
+    message::downgrade::DowngradeError,
     one::{OneResponse, process_two_headers_and_body},
 };
 
@@ -37,8 +38,12 @@ fn build_one_response_line_with_version(
     )
 }
 
-impl From<(Response, Version)> for OneResponse {
-    fn from((mut res, version): (Response, Version)) -> Self {
+impl OneResponse {
+    /* Unchecked, headers and trailers must already be validated, see
+     * Response::try_into_one(). The status line is built from StatusCode and
+     * is always well formed.
+     */
+    pub(crate) fn from_two(mut res: Response, version: Version) -> Self {
         let body = res.take_body();
         let trailer = res.take_trailers();
         let header_map =
@@ -49,6 +54,7 @@ impl From<(Response, Version)> for OneResponse {
 
         let message_head = MessageHead::new(info_line, header_map);
         let mut one = OneResponse::new(message_head, res.body_headers);
+        one.version = Some(version);
 
         if let Some(body) = body
             && !body.is_empty()
@@ -59,9 +65,21 @@ impl From<(Response, Version)> for OneResponse {
     }
 }
 
-impl From<Response> for OneResponse {
-    fn from(res: Response) -> Self {
-        OneResponse::from((res, Version::H11))
+impl TryFrom<(Response, Version)> for OneResponse {
+    type Error = DowngradeError;
+
+    fn try_from(
+        (res, version): (Response, Version),
+    ) -> Result<Self, Self::Error> {
+        res.try_into_one(version)
+    }
+}
+
+impl TryFrom<Response> for OneResponse {
+    type Error = DowngradeError;
+
+    fn try_from(res: Response) -> Result<Self, Self::Error> {
+        res.try_into_one(Version::H11)
     }
 }
 
@@ -100,7 +118,7 @@ mod tests {
         let input = "HTTP/1.1 200 OK\r\n\r\n";
         let mut one = OneResponse::try_from(BytesMut::from(input)).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -118,7 +136,7 @@ mod tests {
                      Hello";
         let mut one = OneResponse::try_from(BytesMut::from(input)).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -134,7 +152,7 @@ mod tests {
                      key: value\r\n\r\n";
         let mut one = OneResponse::try_from(BytesMut::from(input)).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -159,7 +177,7 @@ mod tests {
 
         let mut one = OneResponse::try_from(BytesMut::from(input)).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -174,7 +192,7 @@ mod tests {
                      content-length: 0\r\n\r\n";
         let mut one = OneResponse::try_from(BytesMut::from(input)).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 
     #[test]
@@ -197,6 +215,6 @@ mod tests {
         let mut one =
             OneResponse::try_from(BytesMut::from(input.as_bytes())).unwrap();
         one.body_headers = None;
-        assert_eq!(one, verify.into());
+        assert_eq!(one, verify.try_into().unwrap());
     }
 }
@@ -0,0 +1,117 @@
+use bytes::BytesMut;
+use header_plz::{OneRequestLine, OneResponseLine};
+
+use crate::Version;
+
+/* Description:
+ *      header-plz keeps the raw version bytes of a HTTP/1.x info line
+ *      without a setter. Replace them by rebuilding the line from its
+ *      parts.
+ *
+ *      request     => " HTTP/1.1\r\n"
+ *      response    => "HTTP/1.1 "
+ */
+
+pub trait SetVersion {
+    fn set_version(&mut self, version: Version);
+}
+
+/* Description:
+ *      Read the version from the raw info line, before it is split into
+ *      parts that header-plz gives no accessor for.
+ *
+ *      request     => last token, "GET / HTTP/1.1"
+ *      response    => first token, "HTTP/1.1 200 OK"
+ *
+ * Returns:
+ *      None if the version is not one of the known HTTP versions.
+ */
+
+pub(crate) fn parse_line_version(raw: &[u8]) -> Option<Version> {
+    let line = raw.split(|b| *b == b'\n').next().unwrap_or_default();
+    let mut tokens = line.trim_ascii().split(|b| *b == b' ');
+    let token = if line.starts_with(b"HTTP/") {
+        tokens.next()
+    } else {
+        tokens.next_back()
+    };
+    token.and_then(parse_version)
+}
+
+fn parse_version(raw: &[u8]) -> Option<Version> {
+    use Version::*;
+    [H09, H10, H11, H2, H3]
+        .into_iter()
+        .find(|version| raw.trim_ascii() == version.as_str().as_bytes())
+}
+
+impl SetVersion for OneRequestLine {
+    fn set_version(&mut self, version: Version) {
+        let empty = OneRequestLine::new(
+            BytesMut::new(),
+            BytesMut::new(),
+            BytesMut::new(),
+        );
+        let (method, uri, _) = std::mem::replace(self, empty).into_parts();
+        *self = OneRequestLine::new(
+            method,
+            uri,
+            version.for_request_line().into(),
+        );
+    }
+}
+
+impl SetVersion for OneResponseLine {
+    fn set_version(&mut self, version: Version) {
+        let empty = OneResponseLine::new(
+            BytesMut::new(),
+            BytesMut::new(),
+            BytesMut::new(),
+        );
+        let (_, status, reason) = std::mem::replace(self, empty).into_parts();
+        *self = OneResponseLine::new(
+            version.for_response_line().into(),
+            status,
+            reason,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header_plz::OneInfoLine;
+
+    use super::*;
+
+    #[test]
+    fn test_set_version_request_line() {
+        let input = "GET /index HTTP/1.0\r\n";
+        let mut line =
+            OneRequestLine::try_build_infoline(input.into()).unwrap();
+        line.set_version(Version::H11);
+        assert_eq!(line.into_bytes(), "GET /index HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn test_set_version_response_line() {
+        let input = "HTTP/1.0 404 Not Found\r\n";
+        let mut line =
+            OneResponseLine::try_build_infoline(input.into()).unwrap();
+        line.set_version(Version::H11);
+        assert_eq!(line.into_bytes(), "HTTP/1.1 404 Not Found\r\n");
+    }
+
+    #[test]
+    fn test_parse_line_version() {
+        assert_eq!(
+            parse_line_version(b"GET /index HTTP/1.0\r\nHost: a\r\n"),
+            Some(Version::H10)
+        );
+        assert_eq!(
+            parse_line_version(b"HTTP/1.1 200 OK\r\n"),
+            Some(Version::H11)
+        );
+        assert_eq!(parse_line_version(b"HTTP/7.0 200 OK\r\n"), None);
+        assert_eq!(parse_line_version(b""), None);
+    }
+}
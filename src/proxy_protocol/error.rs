@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+use crate::one::parse::error::BuildMessageError;
+
+#[derive(Debug, Error)]
+pub enum ProxyProtocolError {
+    #[error("Incomplete PROXY header")]
+    Incomplete,
+    #[error("Invalid v1 header| {0}")]
+    InvalidV1(String),
+    #[error("v1 header exceeds 107 bytes")]
+    V1TooLong,
+    #[error("Invalid v2 version| {0}")]
+    InvalidV2Version(u8),
+    #[error("Invalid v2 command| {0}")]
+    InvalidV2Command(u8),
+    #[error("Invalid v2 family| {0}")]
+    InvalidV2Family(u8),
+    #[error("Invalid v2 address length| {0}")]
+    InvalidV2Length(usize),
+    #[error("Invalid v2 TLV")]
+    InvalidTlv,
+    #[error("Failed to ParseMessage| {0}")]
+    Message(#[from] BuildMessageError),
+}
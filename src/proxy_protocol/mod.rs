@@ -0,0 +1,168 @@
+use std::net::SocketAddr;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::ParseMessage;
+pub mod error;
+use error::*;
+mod v1;
+mod v2;
+
+pub const PROXY_V1_SIGNATURE: &[u8] = b"PROXY ";
+pub const PROXY_V2_SIGNATURE: &[u8] = b"\r\n\r\n\x00\r\nQUIT\n";
+
+// TLV types
+pub const PP2_TYPE_ALPN: u8 = 0x01;
+pub const PP2_TYPE_AUTHORITY: u8 = 0x02;
+pub const PP2_TYPE_CRC32C: u8 = 0x03;
+pub const PP2_TYPE_NOOP: u8 = 0x04;
+pub const PP2_TYPE_UNIQUE_ID: u8 = 0x05;
+pub const PP2_TYPE_SSL: u8 = 0x20;
+pub const PP2_TYPE_NETNS: u8 = 0x30;
+
+#[derive(Debug, PartialEq)]
+pub enum ProxyVersion {
+    V1,
+    V2,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProxyCommand {
+    Local,
+    Proxy,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProxyTransport {
+    Unknown,
+    Tcp4,
+    Tcp6,
+    Udp4,
+    Udp6,
+    UnixStream,
+    UnixDgram,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProxyAddr {
+    Inet(SocketAddr),
+    Unix(Bytes),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Tlv {
+    pub kind: u8,
+    pub value: Bytes,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProxyHeader {
+    pub version: ProxyVersion,
+    pub command: ProxyCommand,
+    pub transport: ProxyTransport,
+    pub source: Option<ProxyAddr>,
+    pub destination: Option<ProxyAddr>,
+    pub tlvs: Vec<Tlv>,
+}
+
+impl ProxyHeader {
+    pub fn tlv(&self, kind: u8) -> Option<&Bytes> {
+        self.tlvs.iter().find(|tlv| tlv.kind == kind).map(|tlv| &tlv.value)
+    }
+}
+
+/* Description:
+ *      Strip a PROXY protocol v1/v2 prefix from buf.
+ *
+ * Returns:
+ *      Ok(Some(header))    => prefix parsed and removed from buf.
+ *      Ok(None)            => buf does not start with a PROXY signature.
+ *      Err(_)              => buf is left untouched.
+ */
+
+pub fn parse_proxy_header(
+    buf: &mut BytesMut,
+) -> Result<Option<ProxyHeader>, ProxyProtocolError> {
+    if buf.starts_with(PROXY_V2_SIGNATURE) {
+        v2::parse_v2(buf).map(Some)
+    } else if buf.starts_with(PROXY_V1_SIGNATURE) {
+        v1::parse_v1(buf).map(Some)
+    } else if PROXY_V2_SIGNATURE.starts_with(buf)
+        || PROXY_V1_SIGNATURE.starts_with(buf)
+    {
+        Err(ProxyProtocolError::Incomplete)
+    } else {
+        Ok(None)
+    }
+}
+
+// Strip the PROXY prefix, if any, and parse the remaining bytes as T
+pub fn parse_with_proxy_header<T>(
+    mut buf: BytesMut,
+) -> Result<(Option<ProxyHeader>, T), ProxyProtocolError>
+where
+    T: ParseMessage,
+{
+    let header = parse_proxy_header(&mut buf)?;
+    let message = T::parse(buf)?;
+    Ok((header, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OneRequest, OneResponse};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_proxy_header_none() {
+        let input = "GET / HTTP/1.1\r\n\r\n";
+        let mut buf = BytesMut::from(input);
+        assert!(parse_proxy_header(&mut buf).unwrap().is_none());
+        assert_eq!(buf, input);
+    }
+
+    #[test]
+    fn test_parse_proxy_header_partial_signature() {
+        let mut buf = BytesMut::from("PROX");
+        assert!(matches!(
+            parse_proxy_header(&mut buf),
+            Err(ProxyProtocolError::Incomplete)
+        ));
+    }
+
+    #[test]
+    fn test_parse_with_proxy_header_request() {
+        let input = "PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n\
+                     GET /index HTTP/1.1\r\n\
+                     Host: example.com\r\n\r\n";
+        let (header, req) =
+            parse_with_proxy_header::<OneRequest>(BytesMut::from(input))
+                .unwrap();
+        let header = header.unwrap();
+        assert_eq!(header.version, ProxyVersion::V1);
+        assert_eq!(
+            header.source,
+            Some(ProxyAddr::Inet("192.168.0.1:56324".parse().unwrap()))
+        );
+        assert_eq!(req.uri_as_string(), "/index");
+    }
+
+    #[test]
+    fn test_parse_with_proxy_header_without_prefix() {
+        let input = "HTTP/1.1 200 OK\r\n\r\n";
+        let (header, res) =
+            parse_with_proxy_header::<OneResponse>(BytesMut::from(input))
+                .unwrap();
+        assert!(header.is_none());
+        assert_eq!(res.into_bytes(), input);
+    }
+
+    #[test]
+    fn test_parse_with_proxy_header_invalid_message() {
+        let input = "PROXY UNKNOWN\r\nGET / HTTP/1.1\r\n";
+        let result =
+            parse_with_proxy_header::<OneRequest>(BytesMut::from(input));
+        assert!(matches!(result, Err(ProxyProtocolError::Message(_))));
+    }
+}
@@ -0,0 +1,179 @@
+use std::net::{IpAddr, SocketAddr};
+
+use bytes::BytesMut;
+
+use super::*;
+
+const MAX_V1_LEN: usize = 107;
+const TCP4: &str = "TCP4";
+const TCP6: &str = "TCP6";
+const UNKNOWN: &str = "UNKNOWN";
+
+/* Description:
+ *      PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n
+ *      PROXY UNKNOWN\r\n
+ *
+ * Steps:
+ *      1. Find CRLF within the first 107 bytes.
+ *      2. Split line by SP.
+ *      3. For UNKNOWN, ignore the rest of the line.
+ *      4. For TCP4/TCP6, parse addresses and ports, check address family
+ *         matches protocol.
+ *      5. Split the header off buf.
+ *
+ * Error:
+ *      ProxyProtocolError::Incomplete  [1]
+ *      ProxyProtocolError::V1TooLong   [1]
+ *      ProxyProtocolError::InvalidV1   [2-4]
+ */
+
+pub(super) fn parse_v1(
+    buf: &mut BytesMut,
+) -> Result<ProxyHeader, ProxyProtocolError> {
+    let window = &buf[..buf.len().min(MAX_V1_LEN)];
+    let Some(index) = window.windows(2).position(|w| w == b"\r\n") else {
+        return Err(if buf.len() >= MAX_V1_LEN {
+            ProxyProtocolError::V1TooLong
+        } else {
+            ProxyProtocolError::Incomplete
+        });
+    };
+
+    let line = std::str::from_utf8(&buf[PROXY_V1_SIGNATURE.len()..index])
+        .map_err(|_| invalid(&buf[..index]))?;
+    let mut parts = line.split(' ');
+    let header = match parts.next() {
+        Some(UNKNOWN) => ProxyHeader {
+            version: ProxyVersion::V1,
+            command: ProxyCommand::Proxy,
+            transport: ProxyTransport::Unknown,
+            source: None,
+            destination: None,
+            tlvs: Vec::new(),
+        },
+        Some(protocol @ (TCP4 | TCP6)) => {
+            let parts: Vec<&str> = parts.collect();
+            let [src, dst, sport, dport] = parts[..] else {
+                return Err(invalid(line.as_bytes()));
+            };
+            let parse_addr = |addr: &str, port: &str| {
+                let ip: IpAddr = addr.parse().ok()?;
+                if ip.is_ipv4() != (protocol == TCP4) {
+                    return None;
+                }
+                // leading zeros are not allowed
+                if port.len() > 1 && port.starts_with('0') {
+                    return None;
+                }
+                Some(SocketAddr::new(ip, port.parse().ok()?))
+            };
+            let (Some(source), Some(destination)) =
+                (parse_addr(src, sport), parse_addr(dst, dport))
+            else {
+                return Err(invalid(line.as_bytes()));
+            };
+            ProxyHeader {
+                version: ProxyVersion::V1,
+                command: ProxyCommand::Proxy,
+                transport: if protocol == TCP4 {
+                    ProxyTransport::Tcp4
+                } else {
+                    ProxyTransport::Tcp6
+                },
+                source: Some(ProxyAddr::Inet(source)),
+                destination: Some(ProxyAddr::Inet(destination)),
+                tlvs: Vec::new(),
+            }
+        }
+        _ => return Err(invalid(line.as_bytes())),
+    };
+    let _ = buf.split_to(index + 2);
+    Ok(header)
+}
+
+fn invalid(line: &[u8]) -> ProxyProtocolError {
+    ProxyProtocolError::InvalidV1(String::from_utf8_lossy(line).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_v1_tcp4() {
+        let mut buf = BytesMut::from(
+            "PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET / HTTP/1.1\r\n\r\n",
+        );
+        let header = parse_v1(&mut buf).unwrap();
+        assert_eq!(header.transport, ProxyTransport::Tcp4);
+        assert_eq!(
+            header.source,
+            Some(ProxyAddr::Inet("192.168.0.1:56324".parse().unwrap()))
+        );
+        assert_eq!(
+            header.destination,
+            Some(ProxyAddr::Inet("192.168.0.11:443".parse().unwrap()))
+        );
+        assert_eq!(buf, "GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_proxy_v1_tcp6() {
+        let mut buf = BytesMut::from("PROXY TCP6 2001:db8::1 ::1 4000 80\r\n");
+        let header = parse_v1(&mut buf).unwrap();
+        assert_eq!(header.transport, ProxyTransport::Tcp6);
+        assert_eq!(
+            header.source,
+            Some(ProxyAddr::Inet("[2001:db8::1]:4000".parse().unwrap()))
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_proxy_v1_unknown() {
+        let mut buf = BytesMut::from("PROXY UNKNOWN ffff::1 ::1 1 2\r\nabc");
+        let header = parse_v1(&mut buf).unwrap();
+        assert_eq!(header.transport, ProxyTransport::Unknown);
+        assert!(header.source.is_none());
+        assert_eq!(buf, "abc");
+    }
+
+    #[test]
+    fn test_proxy_v1_incomplete() {
+        let mut buf = BytesMut::from("PROXY TCP4 192.168.0.1");
+        assert!(matches!(
+            parse_v1(&mut buf),
+            Err(ProxyProtocolError::Incomplete)
+        ));
+        assert_eq!(buf, "PROXY TCP4 192.168.0.1");
+    }
+
+    #[test]
+    fn test_proxy_v1_too_long() {
+        let mut buf =
+            BytesMut::from(format!("PROXY {}", "A".repeat(120)).as_str());
+        assert!(matches!(
+            parse_v1(&mut buf),
+            Err(ProxyProtocolError::V1TooLong)
+        ));
+    }
+
+    #[test]
+    fn test_proxy_v1_family_mismatch() {
+        let mut buf = BytesMut::from("PROXY TCP4 ::1 ::1 1 2\r\n");
+        assert!(matches!(
+            parse_v1(&mut buf),
+            Err(ProxyProtocolError::InvalidV1(_))
+        ));
+    }
+
+    #[test]
+    fn test_proxy_v1_invalid_port() {
+        let mut buf =
+            BytesMut::from("PROXY TCP4 1.1.1.1 2.2.2.2 080 70000\r\n");
+        assert!(matches!(
+            parse_v1(&mut buf),
+            Err(ProxyProtocolError::InvalidV1(_))
+        ));
+    }
+}
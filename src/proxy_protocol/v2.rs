@@ -0,0 +1,308 @@
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::Range,
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use super::*;
+
+// signature(12) + ver_cmd(1) + family(1) + len(2)
+const V2_HEADER_LEN: usize = 16;
+const V2_VERSION: u8 = 0x2;
+const LOCAL: u8 = 0x0;
+const PROXY: u8 = 0x1;
+const INET_ADDR_LEN: usize = 12;
+const INET6_ADDR_LEN: usize = 36;
+const UNIX_ADDR_LEN: usize = 216;
+const UNIX_PATH_LEN: usize = 108;
+const TLV_HEADER_LEN: usize = 3;
+
+/* Description:
+ *      Binary header, https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+ *
+ * Steps:
+ *      1. Read fixed 16 byte header and verify version and command.
+ *      2. Wait till len bytes of addresses + TLVs are received.
+ *      3. Verify family and length, validate the bytes after the
+ *         addresses as TLVs.
+ *      4. Split the complete header off buf, buf is untouched on error.
+ *      5. Parse addresses based on family, for LOCAL command addresses are
+ *         ignored.
+ *
+ * Error:
+ *      ProxyProtocolError::Incomplete          [1, 2]
+ *      ProxyProtocolError::InvalidV2Version    [1]
+ *      ProxyProtocolError::InvalidV2Command    [1]
+ *      ProxyProtocolError::InvalidV2Family     [3]
+ *      ProxyProtocolError::InvalidV2Length     [3]
+ *      ProxyProtocolError::InvalidTlv          [3]
+ */
+
+pub(super) fn parse_v2(
+    buf: &mut BytesMut,
+) -> Result<ProxyHeader, ProxyProtocolError> {
+    if buf.len() < V2_HEADER_LEN {
+        return Err(ProxyProtocolError::Incomplete);
+    }
+    let ver_cmd = buf[12];
+    if ver_cmd >> 4 != V2_VERSION {
+        return Err(ProxyProtocolError::InvalidV2Version(ver_cmd >> 4));
+    }
+    let command = match ver_cmd & 0x0f {
+        LOCAL => ProxyCommand::Local,
+        PROXY => ProxyCommand::Proxy,
+        cmd => return Err(ProxyProtocolError::InvalidV2Command(cmd)),
+    };
+    let family = buf[13];
+    let len = u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if buf.len() < V2_HEADER_LEN + len {
+        return Err(ProxyProtocolError::Incomplete);
+    }
+
+    let transport = match family {
+        0x00 => ProxyTransport::Unknown,
+        0x11 => ProxyTransport::Tcp4,
+        0x12 => ProxyTransport::Udp4,
+        0x21 => ProxyTransport::Tcp6,
+        0x22 => ProxyTransport::Udp6,
+        0x31 => ProxyTransport::UnixStream,
+        0x32 => ProxyTransport::UnixDgram,
+        _ => return Err(ProxyProtocolError::InvalidV2Family(family)),
+    };
+    let addr_len = match transport {
+        ProxyTransport::Unknown => 0,
+        ProxyTransport::Tcp4 | ProxyTransport::Udp4 => INET_ADDR_LEN,
+        ProxyTransport::Tcp6 | ProxyTransport::Udp6 => INET6_ADDR_LEN,
+        ProxyTransport::UnixStream | ProxyTransport::UnixDgram => {
+            UNIX_ADDR_LEN
+        }
+    };
+    if len < addr_len {
+        return Err(ProxyProtocolError::InvalidV2Length(len));
+    }
+
+    let end = V2_HEADER_LEN + len;
+    let tlvs = parse_tlvs(&buf[V2_HEADER_LEN + addr_len..end])?;
+
+    let mut payload = buf.split_to(end).freeze();
+    payload.advance(V2_HEADER_LEN);
+    let mut addresses = payload.split_to(addr_len);
+    let tlvs = tlvs
+        .into_iter()
+        .map(|(kind, range)| Tlv {
+            kind,
+            value: payload.slice(range),
+        })
+        .collect();
+
+    let (source, destination) = match (&command, &transport) {
+        (ProxyCommand::Local, _) | (_, ProxyTransport::Unknown) => {
+            (None, None)
+        }
+        (_, ProxyTransport::Tcp4 | ProxyTransport::Udp4) => {
+            let src = Ipv4Addr::from(addresses.get_u32());
+            let dst = Ipv4Addr::from(addresses.get_u32());
+            (
+                inet(src.into(), addresses.get_u16()),
+                inet(dst.into(), addresses.get_u16()),
+            )
+        }
+        (_, ProxyTransport::Tcp6 | ProxyTransport::Udp6) => {
+            let src = Ipv6Addr::from(addresses.get_u128());
+            let dst = Ipv6Addr::from(addresses.get_u128());
+            (
+                inet(src.into(), addresses.get_u16()),
+                inet(dst.into(), addresses.get_u16()),
+            )
+        }
+        (_, ProxyTransport::UnixStream | ProxyTransport::UnixDgram) => {
+            let src = unix(addresses.split_to(UNIX_PATH_LEN));
+            let dst = unix(addresses);
+            (src, dst)
+        }
+    };
+
+    Ok(ProxyHeader {
+        version: ProxyVersion::V2,
+        command,
+        transport,
+        source,
+        destination,
+        tlvs,
+    })
+}
+
+// type(1) | len(2) | value(len), kind and range of each value in payload
+fn parse_tlvs(
+    payload: &[u8],
+) -> Result<Vec<(u8, Range<usize>)>, ProxyProtocolError> {
+    let mut tlvs = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        if rest.len() < TLV_HEADER_LEN {
+            return Err(ProxyProtocolError::InvalidTlv);
+        }
+        let kind = rest.get_u8();
+        let len = rest.get_u16() as usize;
+        if rest.len() < len {
+            return Err(ProxyProtocolError::InvalidTlv);
+        }
+        let start = payload.len() - rest.len();
+        tlvs.push((kind, start..start + len));
+        rest.advance(len);
+    }
+    Ok(tlvs)
+}
+
+#[inline]
+fn inet(ip: std::net::IpAddr, port: u16) -> Option<ProxyAddr> {
+    Some(ProxyAddr::Inet(SocketAddr::new(ip, port)))
+}
+
+// null terminated path
+#[inline]
+fn unix(mut path: Bytes) -> Option<ProxyAddr> {
+    if let Some(end) = path.iter().position(|b| *b == 0) {
+        path.truncate(end);
+    }
+    Some(ProxyAddr::Unix(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_v2(ver_cmd: u8, family: u8, payload: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::from(PROXY_V2_SIGNATURE);
+        buf.extend_from_slice(&[ver_cmd, family]);
+        buf.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    fn inet4_payload() -> Vec<u8> {
+        let mut payload = vec![127, 0, 0, 1, 10, 0, 0, 1];
+        payload.extend_from_slice(&56324u16.to_be_bytes());
+        payload.extend_from_slice(&443u16.to_be_bytes());
+        payload
+    }
+
+    #[test]
+    fn test_proxy_v2_tcp4() {
+        let mut buf = build_v2(0x21, 0x11, &inet4_payload());
+        buf.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+        let header = parse_v2(&mut buf).unwrap();
+        assert_eq!(header.version, ProxyVersion::V2);
+        assert_eq!(header.command, ProxyCommand::Proxy);
+        assert_eq!(header.transport, ProxyTransport::Tcp4);
+        assert_eq!(
+            header.source,
+            Some(ProxyAddr::Inet("127.0.0.1:56324".parse().unwrap()))
+        );
+        assert_eq!(
+            header.destination,
+            Some(ProxyAddr::Inet("10.0.0.1:443".parse().unwrap()))
+        );
+        assert!(header.tlvs.is_empty());
+        assert_eq!(buf, "GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_proxy_v2_tcp6_tlvs() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        payload.extend_from_slice(
+            &"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets(),
+        );
+        payload.extend_from_slice(&1000u16.to_be_bytes());
+        payload.extend_from_slice(&80u16.to_be_bytes());
+        payload.extend_from_slice(&[PP2_TYPE_ALPN, 0x00, 0x02]);
+        payload.extend_from_slice(b"h2");
+        payload.extend_from_slice(&[PP2_TYPE_AUTHORITY, 0x00, 0x0b]);
+        payload.extend_from_slice(b"example.com");
+        let mut buf = build_v2(0x21, 0x21, &payload);
+        let header = parse_v2(&mut buf).unwrap();
+        assert_eq!(header.transport, ProxyTransport::Tcp6);
+        assert_eq!(
+            header.destination,
+            Some(ProxyAddr::Inet("[2001:db8::1]:80".parse().unwrap()))
+        );
+        assert_eq!(header.tlvs.len(), 2);
+        assert_eq!(header.tlv(PP2_TYPE_ALPN).unwrap().as_ref(), b"h2");
+        assert_eq!(
+            header.tlv(PP2_TYPE_AUTHORITY).unwrap().as_ref(),
+            b"example.com"
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_proxy_v2_unix() {
+        let mut payload = vec![0u8; UNIX_ADDR_LEN];
+        payload[..8].copy_from_slice(b"/tmp/src");
+        payload[UNIX_PATH_LEN..UNIX_PATH_LEN + 8].copy_from_slice(b"/tmp/dst");
+        let mut buf = build_v2(0x21, 0x31, &payload);
+        let header = parse_v2(&mut buf).unwrap();
+        assert_eq!(header.transport, ProxyTransport::UnixStream);
+        assert_eq!(
+            header.source,
+            Some(ProxyAddr::Unix(Bytes::from_static(b"/tmp/src")))
+        );
+        assert_eq!(
+            header.destination,
+            Some(ProxyAddr::Unix(Bytes::from_static(b"/tmp/dst")))
+        );
+    }
+
+    #[test]
+    fn test_proxy_v2_local() {
+        let mut buf = build_v2(0x20, 0x11, &inet4_payload());
+        let header = parse_v2(&mut buf).unwrap();
+        assert_eq!(header.command, ProxyCommand::Local);
+        assert!(header.source.is_none());
+        assert!(header.destination.is_none());
+    }
+
+    #[test]
+    fn test_proxy_v2_incomplete() {
+        let buf = build_v2(0x21, 0x11, &inet4_payload());
+        let mut partial = BytesMut::from(&buf[..20]);
+        assert!(matches!(
+            parse_v2(&mut partial),
+            Err(ProxyProtocolError::Incomplete)
+        ));
+        assert_eq!(partial, buf[..20]);
+    }
+
+    #[test]
+    fn test_proxy_v2_invalid_version() {
+        let mut buf = build_v2(0x11, 0x11, &inet4_payload());
+        assert!(matches!(
+            parse_v2(&mut buf),
+            Err(ProxyProtocolError::InvalidV2Version(1))
+        ));
+    }
+
+    #[test]
+    fn test_proxy_v2_short_addresses() {
+        let mut buf = build_v2(0x21, 0x21, &inet4_payload());
+        assert!(matches!(
+            parse_v2(&mut buf),
+            Err(ProxyProtocolError::InvalidV2Length(12))
+        ));
+    }
+
+    #[test]
+    fn test_proxy_v2_truncated_tlv() {
+        let mut payload = inet4_payload();
+        payload.extend_from_slice(&[PP2_TYPE_ALPN, 0x00, 0x05, b'h']);
+        let mut buf = build_v2(0x21, 0x11, &payload);
+        let len = buf.len();
+        assert!(matches!(
+            parse_v2(&mut buf),
+            Err(ProxyProtocolError::InvalidTlv)
+        ));
+        assert_eq!(buf.len(), len);
+    }
+}
@@ -0,0 +1,591 @@
+use std::net::IpAddr;
+
+use header_plz::{
+    OneRequestLine,
+    const_headers::{FORWARDED, HOST},
+    message_head::header_map::{HMap, Hmap},
+    uri::{InvalidUri, Uri},
+};
+use thiserror::Error;
+
+use crate::{
+    OneOne, Request,
+    forwarded::{
+        ForwardedElement, X_FORWARDED_FOR, X_FORWARDED_HOST,
+        X_FORWARDED_PROTO, forwarded_node,
+    },
+    message::downgrade::{DowngradeError, validate_uri},
+    one::request_target::{
+        RequestTargetError, RequestTargetForm, parse_absolute_form,
+    },
+    utils::header::{append_list_value, set_value},
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RewriteError {
+    #[error("Invalid Upstream| {0}")]
+    InvalidUpstream(String),
+    #[error("Invalid Target| {0}")]
+    InvalidTarget(#[from] RequestTargetError),
+    #[error("Unsupported Target| {0}")]
+    UnsupportedTarget(String),
+    #[error("Invalid Authority| {0}")]
+    InvalidAuthority(String),
+    #[error("Invalid Path| {0}")]
+    InvalidPath(String),
+    #[error("Invalid Proto| {0}")]
+    InvalidProto(String),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardingHeaders {
+    #[default]
+    None,
+    // RFC 7239 Forwarded
+    Forwarded,
+    // X-Forwarded-For, X-Forwarded-Host and X-Forwarded-Proto
+    XForwarded,
+    Both,
+}
+
+// downstream side of the connection, used for the forwarding headers
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ClientInfo {
+    pub ip: Option<IpAddr>,
+    pub proto: Option<String>,
+}
+
+/* Description:
+ *      Upstream a request is retargeted at by rewrite().
+ *
+ *      https://backend:8443/api + strip_prefix("/public")
+ *
+ *      /public/users?id=1  => https://backend:8443/api/users?id=1
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upstream {
+    scheme: String,
+    authority: String,
+    base_path: String,
+    strip_prefix: Option<String>,
+    preserve_host: bool,
+    forwarding: ForwardingHeaders,
+}
+
+impl Upstream {
+    /* Error:
+     *      RewriteError::InvalidUpstream   not absolute-form, or has a
+     *                                      query
+     */
+    pub fn parse(url: &str) -> Result<Self, RewriteError> {
+        let target = parse_absolute_form(url)
+            .map_err(|_| RewriteError::InvalidUpstream(url.to_string()))?;
+        if target.path_and_query.contains('?') {
+            return Err(RewriteError::InvalidUpstream(url.to_string()));
+        }
+        Ok(Upstream {
+            scheme: target.scheme,
+            authority: target.authority,
+            base_path: target.path_and_query.trim_end_matches('/').to_string(),
+            strip_prefix: None,
+            preserve_host: false,
+            forwarding: ForwardingHeaders::default(),
+        })
+    }
+
+    // removed from the path, only on a segment boundary
+    pub fn with_strip_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_end_matches('/');
+        self.strip_prefix = (!prefix.is_empty()).then(|| prefix.to_string());
+        self
+    }
+
+    // keep the Host sent by the client instead of the upstream authority
+    pub fn with_preserve_host(mut self, preserve_host: bool) -> Self {
+        self.preserve_host = preserve_host;
+        self
+    }
+
+    pub fn with_forwarding(mut self, forwarding: ForwardingHeaders) -> Self {
+        self.forwarding = forwarding;
+        self
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    pub fn authority(&self) -> &str {
+        &self.authority
+    }
+
+    /* Steps:
+     *      1. Split off the query.
+     *      2. Remove strip_prefix if the path is the prefix or continues
+     *         with a '/' after it.
+     *      3. Prepend the base path of the upstream.
+     */
+
+    pub fn rewrite_path(&self, path_and_query: &str) -> String {
+        let (path, query) = match path_and_query.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path_and_query, None),
+        };
+        let path = match self.strip_prefix.as_deref() {
+            Some(prefix) => match path.strip_prefix(prefix) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+                _ => path,
+            },
+            None => path,
+        };
+        let mut rewritten = format!("{}{path}", self.base_path);
+        if rewritten.is_empty() {
+            rewritten.push('/');
+        }
+        if let Some(query) = query {
+            rewritten.push('?');
+            rewritten.push_str(query);
+        }
+        rewritten
+    }
+
+    fn host<'a>(&'a self, original: Option<&'a str>) -> &'a str {
+        match original {
+            Some(host) if self.preserve_host => host,
+            _ => &self.authority,
+        }
+    }
+}
+
+/* Description:
+ *      Build the uri of the retargeted request and check that the parts
+ *      written to the request line and Host are free of CTLs and spaces.
+ *
+ * Error:
+ *      RewriteError::InvalidAuthority
+ *      RewriteError::InvalidPath
+ */
+
+fn upstream_uri(
+    upstream: &Upstream,
+    host: &str,
+    path: &str,
+) -> Result<Uri, RewriteError> {
+    let authority_err = || RewriteError::InvalidAuthority(host.to_string());
+    let path_err = || RewriteError::InvalidPath(path.to_string());
+    let uri = Uri::builder()
+        .scheme(upstream.scheme())
+        .authority(host.as_bytes())
+        .path(path)
+        .build()
+        .map_err(|e| match e {
+            InvalidUri::InvalidPath => path_err(),
+            _ => authority_err(),
+        })?;
+    validate_uri(&uri).map_err(|e| match e {
+        DowngradeError::InvalidPath(_) => path_err(),
+        _ => authority_err(),
+    })?;
+    Ok(uri)
+}
+
+/* Description:
+ *      Check the original host and proto before they are copied into the
+ *      forwarding headers. Nothing is copied, and nothing checked, with
+ *      ForwardingHeaders::None.
+ *
+ *      proto is a scheme, ALPHA *( ALPHA / DIGIT / "+" / "-" / "." ).
+ *
+ * Error:
+ *      RewriteError::InvalidAuthority
+ *      RewriteError::InvalidProto
+ */
+
+fn validate_forwarding(
+    forwarding: ForwardingHeaders,
+    host: Option<&str>,
+    proto: Option<&str>,
+) -> Result<(), RewriteError> {
+    if forwarding == ForwardingHeaders::None {
+        return Ok(());
+    }
+    if let Some(host) = host {
+        let invalid = || RewriteError::InvalidAuthority(host.to_string());
+        let uri = Uri::builder()
+            .authority(host.as_bytes())
+            .build()
+            .map_err(|_| invalid())?;
+        validate_uri(&uri).map_err(|_| invalid())?;
+    }
+    if let Some(proto) = proto {
+        let is_scheme = proto.starts_with(|c: char| c.is_ascii_alphabetic())
+            && proto.bytes().all(|b| {
+                b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.')
+            });
+        if !is_scheme {
+            return Err(RewriteError::InvalidProto(proto.to_string()));
+        }
+    }
+    Ok(())
+}
+
+fn add_forwarding_headers<T>(
+    header_map: &mut HMap<T>,
+    forwarding: ForwardingHeaders,
+    client: &ClientInfo,
+    host: Option<&str>,
+    proto: Option<&str>,
+) where
+    T: Hmap + for<'a> From<(&'a [u8], &'a [u8])>,
+{
+    if matches!(
+        forwarding,
+        ForwardingHeaders::Forwarded | ForwardingHeaders::Both
+    ) {
+        let element = ForwardedElement {
+            by_node: None,
+            for_node: client.ip.map(|ip| forwarded_node(ip, None)),
+            host: host.map(str::to_string),
+            proto: proto.map(str::to_string),
+        };
+        append_list_value(header_map, FORWARDED, &element.to_header_value());
+    }
+    if matches!(
+        forwarding,
+        ForwardingHeaders::XForwarded | ForwardingHeaders::Both
+    ) {
+        if let Some(ip) = client.ip {
+            append_list_value(header_map, X_FORWARDED_FOR, &ip.to_string());
+        }
+        if let Some(host) = host {
+            set_value(header_map, X_FORWARDED_HOST, host);
+        }
+        if let Some(proto) = proto {
+            set_value(header_map, X_FORWARDED_PROTO, proto);
+        }
+    }
+}
+
+impl OneOne<OneRequestLine> {
+    /* Description:
+     *      Retarget the request at upstream.
+     *
+     * Steps:
+     *      1. Take the path and the original host from an origin-form or
+     *         absolute-form target.
+     *      2. Validate the rewritten path and host, and the original host and
+     *         proto copied into the forwarding headers.
+     *      3. Replace the target with the rewritten origin-form path.
+     *      4. Set Host to the upstream authority, or the original host if
+     *         preserved.
+     *      5. Add the forwarding headers, proto defaults to the scheme of an
+     *         absolute-form target.
+     *
+     *      The request is left untouched on error.
+     *
+     * Error:
+     *      RewriteError::InvalidTarget         [1]
+     *      RewriteError::UnsupportedTarget     [1] authority / asterisk form
+     *      RewriteError::InvalidAuthority      [2]
+     *      RewriteError::InvalidPath           [2]
+     *      RewriteError::InvalidProto          [2]
+     */
+
+    pub fn rewrite(
+        &mut self,
+        upstream: &Upstream,
+        client: &ClientInfo,
+    ) -> Result<(), RewriteError> {
+        let uri = self.uri_as_string().to_string();
+        let (path, authority, scheme) = match self.target_form() {
+            RequestTargetForm::Origin => (uri, None, None),
+            RequestTargetForm::Absolute => {
+                let target = parse_absolute_form(&uri)?;
+                (
+                    target.path_and_query,
+                    Some(target.authority),
+                    Some(target.scheme),
+                )
+            }
+            _ => return Err(RewriteError::UnsupportedTarget(uri)),
+        };
+        let original_host = authority.or_else(|| self.host());
+        let proto = client.proto.as_deref().or(scheme.as_deref());
+        let path = upstream.rewrite_path(&path);
+        let host = upstream.host(original_host.as_deref());
+        upstream_uri(upstream, host, &path)?;
+        validate_forwarding(
+            upstream.forwarding,
+            original_host.as_deref(),
+            proto,
+        )?;
+
+        self.set_target(&path);
+        if !self.update_header_value_on_key(HOST, host.as_bytes()) {
+            self.add_header(HOST, host.as_bytes());
+        }
+        add_forwarding_headers(
+            self.message_head.header_map_as_mut(),
+            upstream.forwarding,
+            client,
+            original_host.as_deref(),
+            proto,
+        );
+        Ok(())
+    }
+}
+
+impl Request {
+    /* Description:
+     *      Retarget the request at upstream, the uri gets the upstream
+     *      scheme and the rewritten path. The authority, and a Host header
+     *      if present, follow Upstream::with_preserve_host().
+     *
+     *      The request is left untouched on error.
+     *
+     * Error:
+     *      RewriteError::InvalidAuthority
+     *      RewriteError::InvalidPath
+     *      RewriteError::InvalidProto
+     */
+
+    pub fn rewrite(
+        &mut self,
+        upstream: &Upstream,
+        client: &ClientInfo,
+    ) -> Result<(), RewriteError> {
+        let original_host = self
+            .authority()
+            .map(str::to_string)
+            .or_else(|| {
+                self.headers
+                    .value_of_key(HOST)
+                    .map(|h| String::from_utf8_lossy(h).to_string())
+            })
+            .filter(|host| !host.is_empty());
+        let proto = client
+            .proto
+            .clone()
+            .or_else(|| self.scheme().map(|s| s.as_str().to_string()))
+            .filter(|proto| !proto.is_empty());
+
+        let path = upstream.rewrite_path(self.path_and_query().as_str());
+        let host = upstream.host(original_host.as_deref());
+        let uri = upstream_uri(upstream, host, &path)?;
+        validate_forwarding(
+            upstream.forwarding,
+            original_host.as_deref(),
+            proto.as_deref(),
+        )?;
+        self.info_line.set_uri(uri);
+        self.headers.update_header_value_on_key(HOST, host.as_bytes());
+        add_forwarding_headers(
+            &mut self.headers,
+            upstream.forwarding,
+            client,
+            original_host.as_deref(),
+            proto.as_deref(),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use header_plz::{HeaderMap, message_head::header_map::HeaderStr};
+
+    use crate::OneRequest;
+
+    use super::*;
+
+    fn client() -> ClientInfo {
+        ClientInfo {
+            ip: Some("192.0.2.1".parse().unwrap()),
+            proto: None,
+        }
+    }
+
+    #[test]
+    fn test_upstream_parse() {
+        let upstream = Upstream::parse("HTTPS://backend:8443/api/").unwrap();
+        assert_eq!(upstream.scheme(), "https");
+        assert_eq!(upstream.authority(), "backend:8443");
+        assert_eq!(
+            Upstream::parse("/api"),
+            Err(RewriteError::InvalidUpstream("/api".to_string()))
+        );
+        assert_eq!(
+            Upstream::parse("http://backend/?a=1"),
+            Err(RewriteError::InvalidUpstream(
+                "http://backend/?a=1".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_upstream_rewrite_path() {
+        let upstream = Upstream::parse("http://backend/api")
+            .unwrap()
+            .with_strip_prefix("/public/");
+        assert_eq!(
+            upstream.rewrite_path("/public/users?id=1"),
+            "/api/users?id=1"
+        );
+        assert_eq!(upstream.rewrite_path("/public"), "/api");
+        assert_eq!(upstream.rewrite_path("/publicity"), "/api/publicity");
+
+        let upstream = Upstream::parse("http://backend")
+            .unwrap()
+            .with_strip_prefix("/v1");
+        assert_eq!(upstream.rewrite_path("/v1"), "/");
+        assert_eq!(upstream.rewrite_path("/v1?q"), "/?q");
+        assert_eq!(upstream.rewrite_path("/v2/a"), "/v2/a");
+    }
+
+    #[test]
+    fn test_one_rewrite() {
+        let input = "GET /public/users?id=1 HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     Accept: */*\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let upstream = Upstream::parse("http://backend:8080/api")
+            .unwrap()
+            .with_strip_prefix("/public")
+            .with_forwarding(ForwardingHeaders::Both);
+        let client = ClientInfo {
+            proto: Some("https".to_string()),
+            ..client()
+        };
+        req.rewrite(&upstream, &client).unwrap();
+        let verify = "GET /api/users?id=1 HTTP/1.1\r\n\
+                      Host: backend:8080\r\n\
+                      Accept: */*\r\n\
+                      forwarded: for=192.0.2.1;host=example.com;proto=https\r\n\
+                      x-forwarded-for: 192.0.2.1\r\n\
+                      x-forwarded-host: example.com\r\n\
+                      x-forwarded-proto: https\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_one_rewrite_absolute_form_preserve_host() {
+        let input = "GET http://example.com/a HTTP/1.1\r\n\
+                     Host: example.com\r\n\
+                     X-Forwarded-For: 10.0.0.1\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let upstream = Upstream::parse("http://backend")
+            .unwrap()
+            .with_preserve_host(true)
+            .with_forwarding(ForwardingHeaders::XForwarded);
+        req.rewrite(&upstream, &client()).unwrap();
+        let verify = "GET /a HTTP/1.1\r\n\
+                      Host: example.com\r\n\
+                      X-Forwarded-For: 10.0.0.1, 192.0.2.1\r\n\
+                      x-forwarded-host: example.com\r\n\
+                      x-forwarded-proto: http\r\n\r\n";
+        assert_eq!(req.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_one_rewrite_unsupported_target() {
+        let input = "CONNECT example.com:443 HTTP/1.1\r\n\r\n";
+        let mut req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let upstream = Upstream::parse("http://backend").unwrap();
+        assert_eq!(
+            req.rewrite(&upstream, &ClientInfo::default()),
+            Err(RewriteError::UnsupportedTarget(
+                "example.com:443".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_request_rewrite() {
+        let uri = Uri::builder()
+            .scheme("https")
+            .authority("example.com".as_bytes())
+            .path("/public/users")
+            .build()
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("accept", "*/*");
+        let mut req = Request::builder().uri(uri).headers(headers).build();
+        let upstream = Upstream::parse("http://backend:8080/api")
+            .unwrap()
+            .with_strip_prefix("/public")
+            .with_forwarding(ForwardingHeaders::Forwarded);
+        req.rewrite(&upstream, &client()).unwrap();
+
+        assert_eq!(req.scheme().unwrap().as_str(), "http");
+        assert_eq!(req.authority(), Some("backend:8080"));
+        assert_eq!(req.path_and_query().as_str(), "/api/users");
+        let headers: Vec<(&str, &str)> = req
+            .headers()
+            .iter()
+            .filter_map(|h| Some((h.key_as_str()?, h.value_as_str()?)))
+            .collect();
+        let verify = vec![
+            ("accept", "*/*"),
+            ("forwarded", "for=192.0.2.1;host=example.com;proto=https"),
+        ];
+        assert_eq!(headers, verify);
+    }
+
+    #[test]
+    fn test_request_rewrite_invalid_host_untouched() {
+        let uri = Uri::builder().path("/a").build().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "example.com\r\nx: y");
+        let mut req = Request::builder().uri(uri).headers(headers).build();
+        let upstream = Upstream::parse("http://backend")
+            .unwrap()
+            .with_preserve_host(true);
+        assert_eq!(
+            req.rewrite(&upstream, &client()),
+            Err(RewriteError::InvalidAuthority(
+                "example.com\r\nx: y".to_string()
+            ))
+        );
+        assert_eq!(req.path_and_query().as_str(), "/a");
+        assert_eq!(req.scheme(), None);
+
+        // original host is only copied into the forwarding headers
+        let upstream = Upstream::parse("http://backend")
+            .unwrap()
+            .with_forwarding(ForwardingHeaders::Both);
+        assert_eq!(
+            req.rewrite(&upstream, &client()),
+            Err(RewriteError::InvalidAuthority(
+                "example.com\r\nx: y".to_string()
+            ))
+        );
+        assert_eq!(req.path_and_query().as_str(), "/a");
+        assert!(req.headers().value_of_key(FORWARDED).is_none());
+        assert!(req.headers().value_of_key(X_FORWARDED_HOST).is_none());
+
+        let upstream = Upstream::parse("http://backend").unwrap();
+        req.rewrite(&upstream, &client()).unwrap();
+        assert_eq!(req.authority(), Some("backend"));
+    }
+
+    #[test]
+    fn test_request_rewrite_invalid_proto_untouched() {
+        let uri = Uri::builder().path("/a").build().unwrap();
+        let mut req = Request::builder().uri(uri).build();
+        let upstream = Upstream::parse("http://backend")
+            .unwrap()
+            .with_forwarding(ForwardingHeaders::XForwarded);
+        let client = ClientInfo {
+            proto: Some("https\r\nx: y".to_string()),
+            ..client()
+        };
+        assert_eq!(
+            req.rewrite(&upstream, &client),
+            Err(RewriteError::InvalidProto("https\r\nx: y".to_string()))
+        );
+        assert_eq!(req.path_and_query().as_str(), "/a");
+        assert!(req.headers().value_of_key(X_FORWARDED_PROTO).is_none());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{
+    HTTP_0_9, HTTP_1_0, HTTP_1_1, Version,
+    one::h2c::{PrefaceMatch, detect_h2_preface},
+    proxy_protocol::{PROXY_V1_SIGNATURE, PROXY_V2_SIGNATURE},
+    utils::token::{is_tchar, is_token},
+};
+
+const HTTP_PREFIX: &[u8] = b"HTTP/";
+const TLS_HANDSHAKE: u8 = 0x16;
+const TLS_MAJOR_VERSION: u8 = 0x03;
+const TLS_CLIENT_HELLO: u8 = 0x01;
+const TLS_HEADER_LEN: usize = 6;
+const MAX_REQUEST_LINE: usize = 8192;
+
+#[derive(Debug, PartialEq)]
+pub enum Protocol {
+    OneRequest(Version),
+    OneResponse(Version),
+    H2Preface,
+    TlsClientHello,
+    ProxyProtocol,
+    Incomplete,
+    Unknown,
+}
+
+impl Protocol {
+    pub fn version(&self) -> Option<Version> {
+        match self {
+            Protocol::OneRequest(version) | Protocol::OneResponse(version) => {
+                Some(*version)
+            }
+            Protocol::H2Preface => Some(Version::H2),
+            _ => None,
+        }
+    }
+}
+
+/* Description:
+ *      Classify the start of a raw connection buffer.
+ *
+ * Steps:
+ *      1. Match fixed signatures, HTTP/2 preface, PROXY v1/v2, TLS record
+ *         header with ClientHello handshake type.
+ *      2. If buf starts with "HTTP/", response status line.
+ *      3. Else try to parse a request line, "METHOD SP target [SP version]".
+ *      4. If buf is a prefix of any of the above and no match is found,
+ *         Incomplete.
+ */
+
+pub fn sniff_protocol(buf: &[u8]) -> Protocol {
+    if buf.is_empty() {
+        return Protocol::Incomplete;
+    }
+
+    let mut incomplete = false;
+    match detect_h2_preface(buf) {
+        PrefaceMatch::Complete => return Protocol::H2Preface,
+        PrefaceMatch::Partial => incomplete = true,
+        PrefaceMatch::None => (),
+    }
+
+    for signature in [PROXY_V1_SIGNATURE, PROXY_V2_SIGNATURE] {
+        match starts_with_signature(buf, signature) {
+            Some(true) => return Protocol::ProxyProtocol,
+            Some(false) => incomplete = true,
+            None => (),
+        }
+    }
+
+    match sniff_tls(buf) {
+        Some(true) => return Protocol::TlsClientHello,
+        Some(false) => incomplete = true,
+        None => (),
+    }
+
+    match starts_with_signature(buf, HTTP_PREFIX) {
+        Some(true) => {
+            if let Some(protocol) = sniff_response(buf) {
+                return protocol;
+            }
+            incomplete = true
+        }
+        Some(false) => incomplete = true,
+        None => (),
+    }
+
+    match sniff_request(buf) {
+        Some(Protocol::Unknown) | None if incomplete => Protocol::Incomplete,
+        Some(protocol) => protocol,
+        None => Protocol::Unknown,
+    }
+}
+
+// Some(true) => match, Some(false) => prefix of signature, None => no match
+fn starts_with_signature(buf: &[u8], signature: &[u8]) -> Option<bool> {
+    if buf.starts_with(signature) {
+        Some(true)
+    } else if signature.starts_with(buf) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+// ContentType(1) | Version(2) | Length(2) | HandshakeType(1)
+fn sniff_tls(buf: &[u8]) -> Option<bool> {
+    if buf[0] != TLS_HANDSHAKE {
+        return None;
+    }
+    if buf.get(1).is_some_and(|b| *b != TLS_MAJOR_VERSION) {
+        return None;
+    }
+    match buf.get(TLS_HEADER_LEN - 1) {
+        Some(&TLS_CLIENT_HELLO) => Some(true),
+        Some(_) => None,
+        None => Some(false),
+    }
+}
+
+fn sniff_response(buf: &[u8]) -> Option<Protocol> {
+    let version = buf.split(|b| *b == b' ').next()?;
+    if version.len() == buf.len() {
+        return None;
+    }
+    match parse_version(version) {
+        Some(version) => Some(Protocol::OneResponse(version)),
+        None => Some(Protocol::Unknown),
+    }
+}
+
+/* Steps:
+ *      1. Split at first line end, if not found and buf is still a plausible
+ *         request line prefix, return None (Incomplete).
+ *      2. Method must be a token followed by SP.
+ *      3. Without a version, it is a HTTP/0.9 request.
+ *      4. Else the last part must be a known version.
+ */
+
+fn sniff_request(buf: &[u8]) -> Option<Protocol> {
+    let line = match buf.iter().position(|b| *b == b'\n') {
+        Some(index) => {
+            buf[..index].strip_suffix(b"\r").unwrap_or(&buf[..index])
+        }
+        None => {
+            return (buf.len() < MAX_REQUEST_LINE
+                && is_request_line_prefix(buf))
+            .then_some(Protocol::Incomplete);
+        }
+    };
+
+    let mut parts = line.split(|b| *b == b' ');
+    let method = parts.next()?;
+    if !is_token(method) {
+        return Some(Protocol::Unknown);
+    }
+    let Some(target) = parts.next().filter(|t| !t.is_empty()) else {
+        return Some(Protocol::Unknown);
+    };
+    if target.iter().any(u8::is_ascii_control) {
+        return Some(Protocol::Unknown);
+    }
+    let protocol = match (parts.next(), parts.next()) {
+        (None, _) => Protocol::OneRequest(Version::H09),
+        (Some(version), None) => parse_version(version)
+            .map(Protocol::OneRequest)
+            .unwrap_or(Protocol::Unknown),
+        _ => Protocol::Unknown,
+    };
+    Some(protocol)
+}
+
+fn is_request_line_prefix(buf: &[u8]) -> bool {
+    let mut parts = buf.splitn(2, |b| *b == b' ');
+    let method = parts.next().unwrap_or_default();
+    method.iter().all(|b| is_tchar(*b))
+        && parts.next().is_none_or(|rest| {
+            !rest.iter().any(|b| b.is_ascii_control() && *b != b'\r')
+        })
+}
+
+fn parse_version(version: &[u8]) -> Option<Version> {
+    match version {
+        v if v == HTTP_1_1.as_bytes() => Some(Version::H11),
+        v if v == HTTP_1_0.as_bytes() => Some(Version::H10),
+        v if v == HTTP_0_9.as_bytes() => Some(Version::H09),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::H2_PREFACE;
+
+    use super::*;
+
+    #[test]
+    fn test_sniff_request() {
+        let input = b"GET /index HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::OneRequest(Version::H11));
+        let input = b"POST / HTTP/1.0\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::OneRequest(Version::H10));
+        let input = b"CONNECT example.com:443 HTTP/1.1\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::OneRequest(Version::H11));
+    }
+
+    #[test]
+    fn test_sniff_request_http_09() {
+        let input = b"GET /index.html\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::OneRequest(Version::H09));
+    }
+
+    #[test]
+    fn test_sniff_request_lf_only() {
+        let input = b"GET / HTTP/1.1\n";
+        assert_eq!(sniff_protocol(input), Protocol::OneRequest(Version::H11));
+    }
+
+    #[test]
+    fn test_sniff_request_incomplete() {
+        assert_eq!(sniff_protocol(b"GE"), Protocol::Incomplete);
+        assert_eq!(sniff_protocol(b"GET /ind"), Protocol::Incomplete);
+        assert_eq!(sniff_protocol(b"GET / HTTP/1.1\r"), Protocol::Incomplete);
+    }
+
+    #[test]
+    fn test_sniff_request_unknown_version() {
+        let input = b"GET / HTTP/3.0\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::Unknown);
+    }
+
+    #[test]
+    fn test_sniff_response() {
+        let input = b"HTTP/1.1 200 OK\r\n\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::OneResponse(Version::H11));
+        let input = b"HTTP/1.0 404 Not Found\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::OneResponse(Version::H10));
+        assert_eq!(sniff_protocol(b"HTTP/1."), Protocol::Incomplete);
+        assert_eq!(sniff_protocol(b"HT"), Protocol::Incomplete);
+    }
+
+    #[test]
+    fn test_sniff_h2_preface() {
+        let mut input = H2_PREFACE.to_vec();
+        input.extend_from_slice(b"\x00\x00\x12\x04");
+        assert_eq!(sniff_protocol(&input), Protocol::H2Preface);
+        assert_eq!(sniff_protocol(&input).version(), Some(Version::H2));
+        assert_eq!(sniff_protocol(b"PRI * HTTP/2"), Protocol::Incomplete);
+    }
+
+    #[test]
+    fn test_sniff_tls_client_hello() {
+        let input = [0x16, 0x03, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0xfc];
+        assert_eq!(sniff_protocol(&input), Protocol::TlsClientHello);
+        assert_eq!(sniff_protocol(&input[..3]), Protocol::Incomplete);
+        assert_eq!(sniff_protocol(&input).version(), None);
+    }
+
+    #[test]
+    fn test_sniff_tls_server_hello() {
+        let input = [0x16, 0x03, 0x03, 0x00, 0x7a, 0x02];
+        assert_eq!(sniff_protocol(&input), Protocol::Unknown);
+    }
+
+    #[test]
+    fn test_sniff_proxy_protocol() {
+        let input = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::ProxyProtocol);
+        let mut input = PROXY_V2_SIGNATURE.to_vec();
+        input.extend_from_slice(b"\x21\x11\x00\x0c");
+        assert_eq!(sniff_protocol(&input), Protocol::ProxyProtocol);
+        assert_eq!(sniff_protocol(b"\r\n\r\n\x00"), Protocol::Incomplete);
+    }
+
+    #[test]
+    fn test_sniff_proxy_prefix_vs_method() {
+        assert_eq!(sniff_protocol(b"PRO"), Protocol::Incomplete);
+        let input = b"PROPFIND /dav HTTP/1.1\r\n";
+        assert_eq!(sniff_protocol(input), Protocol::OneRequest(Version::H11));
+    }
+
+    #[test]
+    fn test_sniff_unknown() {
+        assert_eq!(sniff_protocol(b"\x00\x01\x02\x03"), Protocol::Unknown);
+        assert_eq!(sniff_protocol(b"{\"json\": 1}\n"), Protocol::Unknown);
+        assert_eq!(sniff_protocol(b""), Protocol::Incomplete);
+    }
+}
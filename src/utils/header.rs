@@ -0,0 +1,38 @@
+use header_plz::message_head::header_map::{HMap, Hmap};
+
+pub(crate) const LIST_DELIMITER: &str = ", ";
+
+// append to the last header with key, else insert
+pub(crate) fn append_list_value<T>(
+    header_map: &mut HMap<T>,
+    key: &[u8],
+    value: &str,
+) where
+    T: Hmap + for<'a> From<(&'a [u8], &'a [u8])>,
+{
+    let last = header_map
+        .iter()
+        .rposition(|h| h.key_as_ref().eq_ignore_ascii_case(key));
+    match last {
+        Some(pos) => {
+            let current = header_map.iter().nth(pos).unwrap().value_as_ref();
+            let mut merged = current.trim_ascii().to_vec();
+            if !merged.is_empty() {
+                merged.extend_from_slice(LIST_DELIMITER.as_bytes());
+            }
+            merged.extend_from_slice(value.as_bytes());
+            header_map.update_header_value_on_position(pos, merged);
+        }
+        None => header_map.insert(key, value.as_bytes()),
+    }
+}
+
+// replace the first header with key, else insert
+pub(crate) fn set_value<T>(header_map: &mut HMap<T>, key: &[u8], value: &str)
+where
+    T: Hmap + for<'a> From<(&'a [u8], &'a [u8])>,
+{
+    if !header_map.update_header_value_on_key(key, value.as_bytes()) {
+        header_map.insert(key, value.as_bytes());
+    }
+}
@@ -0,0 +1,5 @@
+pub(crate) mod header;
+pub(crate) mod percent;
+pub(crate) mod quoted;
+pub(crate) mod random;
+pub(crate) mod token;
@@ -0,0 +1,62 @@
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+// Invalid escapes are kept as is, RFC 3986 2.1
+pub(crate) fn decode(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut index = 0;
+    while index < input.len() {
+        let escaped = match input[index..] {
+            [b'%', hi, lo, ..] => hex_value(hi).zip(hex_value(lo)),
+            _ => None,
+        };
+        match escaped {
+            Some((hi, lo)) => {
+                out.push(hi << 4 | lo);
+                index += 3;
+            }
+            None => {
+                out.push(input[index]);
+                index += 1;
+            }
+        }
+    }
+    out
+}
+
+// Bytes not kept are escaped as uppercase %XX
+pub(crate) fn encode(input: &[u8], keep: impl Fn(u8) -> bool) -> String {
+    let mut out = String::with_capacity(input.len());
+    for b in input {
+        if keep(*b) {
+            out.push(*b as char);
+        } else {
+            out.push('%');
+            out.push(HEX[(b >> 4) as usize] as char);
+            out.push(HEX[(b & 0x0f) as usize] as char);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(decode(b"a%20b%2fc"), b"a b/c");
+        assert_eq!(decode(b"%zz%4"), b"%zz%4");
+        assert_eq!(decode(b"%E2%82%AC"), "\u{20ac}".as_bytes());
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        let keep = |b: u8| b.is_ascii_alphanumeric();
+        assert_eq!(encode(b"a b/c", keep), "a%20b%2Fc");
+        assert_eq!(encode("\u{20ac}".as_bytes(), keep), "%E2%82%AC");
+    }
+}
@@ -0,0 +1,100 @@
+use crate::utils::token::is_token;
+
+// split on sep outside of quoted strings and comments
+pub(crate) fn split_unquoted(value: &str, sep: u8) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut quoted, mut escaped, mut depth, mut start) = (false, false, 0, 0);
+    for (index, b) in value.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            b'(' if !quoted => depth += 1,
+            b')' if !quoted && depth > 0 => depth -= 1,
+            _ if b == sep && !quoted && depth == 0 => {
+                parts.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+pub(crate) fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => {
+            let mut out = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => out.extend(chars.next()),
+                    c => out.push(c),
+                }
+            }
+            out
+        }
+        None => value.to_string(),
+    }
+}
+
+// quoted-string unless value is a token, RFC 9110 5.6.4
+pub(crate) fn quote(value: &str) -> String {
+    if is_token(value.as_bytes()) {
+        return value.to_string();
+    }
+    quoted_string(value)
+}
+
+/* qdtext and quoted-pair allow HTAB, SP, VCHAR and obs-text, RFC 9110
+ * 5.6.4. Any other control, CR and LF included, can't be sent.
+ */
+pub(crate) fn is_quotable(value: &str) -> bool {
+    !value.bytes().any(|b| b.is_ascii_control() && b != b'\t')
+}
+
+// always a quoted-string, '"' and '\' are escaped, check is_quotable() first
+pub(crate) fn quoted_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_unquoted() {
+        assert_eq!(
+            split_unquoted("a=\"x,y\", b (c, d), e", b','),
+            vec!["a=\"x,y\"", " b (c, d)", " e"]
+        );
+        assert_eq!(split_unquoted("a=\"\\\",\"", b','), vec!["a=\"\\\",\""]);
+    }
+
+    #[test]
+    fn test_quote_unquote() {
+        assert_eq!(quote("token"), "token");
+        assert_eq!(quote("a b\"c"), "\"a b\\\"c\"");
+        assert_eq!(unquote(&quote("a b\"c")), "a b\"c");
+        assert_eq!(unquote("token"), "token");
+        assert_eq!(quoted_string("token"), "\"token\"");
+    }
+
+    #[test]
+    fn test_is_quotable() {
+        assert!(is_quotable("a b\t\"c\" é"));
+        assert!(!is_quotable("x\r\nSet-Cookie: a=b"));
+        assert!(!is_quotable("a\0b"));
+        assert!(!is_quotable("a\x7fb"));
+    }
+}
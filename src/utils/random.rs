@@ -0,0 +1,48 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/* Description:
+ *      Non cryptographic random u64, seeded from the randomly keyed std
+ *      hasher. Only for values that must be distinct, like multipart
+ *      boundaries. Use secure_random() for anything an attacker must not
+ *      predict.
+ */
+
+pub(crate) fn random_u64() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    RandomState::new().hash_one((nanos, count))
+}
+
+// bytes from the OS CSPRNG, websocket masking keys RFC 6455 10.3
+pub(crate) fn secure_random<const N: usize>()
+-> Result<[u8; N], getrandom::Error> {
+    let mut buf = [0; N];
+    getrandom::fill(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_u64_distinct() {
+        assert_ne!(random_u64(), random_u64());
+    }
+
+    #[test]
+    fn test_secure_random_distinct() {
+        let a: [u8; 16] = secure_random().unwrap();
+        let b: [u8; 16] = secure_random().unwrap();
+        assert_ne!(a, b);
+    }
+}
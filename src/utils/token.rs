@@ -0,0 +1,79 @@
+use header_plz::message_head::header_map::{HMap, Hmap};
+
+// RFC 9110 5.6.2
+#[inline]
+pub(crate) fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// non-empty sequence of tchar
+pub(crate) fn is_token(value: &[u8]) -> bool {
+    !value.is_empty() && value.iter().all(|b| is_tchar(*b))
+}
+
+// Comma separated tokens of every header with the given key
+pub(crate) fn header_tokens<'a, T>(
+    header_map: &'a HMap<T>,
+    key: &'a [u8],
+) -> impl Iterator<Item = &'a [u8]> + 'a
+where
+    T: Hmap,
+{
+    header_map
+        .iter()
+        .filter(move |h| h.key_as_ref().eq_ignore_ascii_case(key))
+        .flat_map(|h| h.value_as_ref().split(|b| *b == b','))
+        .map(|token| token.trim_ascii())
+        .filter(|token| !token.is_empty())
+}
+
+pub(crate) fn has_token<T>(
+    header_map: &HMap<T>,
+    key: &[u8],
+    token: impl AsRef<[u8]>,
+) -> bool
+where
+    T: Hmap,
+{
+    header_tokens(header_map, key)
+        .any(|t| t.eq_ignore_ascii_case(token.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use header_plz::OneHeaderMap;
+
+    use super::*;
+
+    fn build_header_map() -> OneHeaderMap {
+        let input = "Connection: keep-alive, Upgrade\r\n\
+                     Upgrade: h2c\r\n\
+                     connection: ,TE\r\n\r\n";
+        OneHeaderMap::from(BytesMut::from(input))
+    }
+
+    #[test]
+    fn test_header_tokens() {
+        let map = build_header_map();
+        let tokens: Vec<&[u8]> = header_tokens(&map, b"connection").collect();
+        let verify: Vec<&[u8]> = vec![b"keep-alive", b"Upgrade", b"TE"];
+        assert_eq!(tokens, verify);
+    }
+
+    #[test]
+    fn test_is_token() {
+        assert!(is_token(b"x-custom_1.0~"));
+        assert!(!is_token(b""));
+        assert!(!is_token(b"a b"));
+        assert!(!is_token(b"a:b"));
+    }
+
+    #[test]
+    fn test_has_token() {
+        let map = build_header_map();
+        assert!(has_token(&map, b"connection", "upgrade"));
+        assert!(has_token(&map, b"upgrade", "H2C"));
+        assert!(!has_token(&map, b"upgrade", "websocket"));
+    }
+}
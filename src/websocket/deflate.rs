@@ -0,0 +1,331 @@
+use flate2::{
+    Compress, Compression, Decompress, FlushCompress, FlushDecompress,
+};
+
+use super::{Role, error::WebSocketError};
+
+pub const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+const SERVER_NO_CONTEXT_TAKEOVER: &str = "server_no_context_takeover";
+const CLIENT_NO_CONTEXT_TAKEOVER: &str = "client_no_context_takeover";
+const SERVER_MAX_WINDOW_BITS: &str = "server_max_window_bits";
+const CLIENT_MAX_WINDOW_BITS: &str = "client_max_window_bits";
+const MAX_WINDOW_BITS: u8 = 15;
+// RFC 7692 7.2.1, removed from each compressed message
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+const CHUNK: usize = 4096;
+
+// negotiated permessage-deflate parameters, RFC 7692 7.1
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DeflateConfig {
+    pub server_no_context_takeover: bool,
+    pub client_no_context_takeover: bool,
+    pub server_max_window_bits: Option<u8>,
+    pub client_max_window_bits: Option<u8>,
+}
+
+impl DeflateConfig {
+    /* Description:
+     *      Parse the first permessage-deflate entry in a
+     *      Sec-WebSocket-Extensions value.
+     *
+     *      permessage-deflate; client_max_window_bits, x-other
+     *
+     * Returns:
+     *      None if permessage-deflate is not present or has an unknown or
+     *      invalid parameter.
+     */
+
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        let value = std::str::from_utf8(value).ok()?;
+        let mut params = value
+            .split(',')
+            .map(|ext| ext.split(';').map(str::trim))
+            .find_map(|mut ext| {
+                ext.next()
+                    .is_some_and(|name| {
+                        name.eq_ignore_ascii_case(PERMESSAGE_DEFLATE)
+                    })
+                    .then_some(ext)
+            })?;
+
+        params.try_fold(DeflateConfig::default(), |mut config, param| {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => {
+                    (name.trim(), Some(value.trim().trim_matches('"')))
+                }
+                None => (param, None),
+            };
+            let window_bits = || match value {
+                Some(bits) => bits
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|bits| (8..=MAX_WINDOW_BITS).contains(bits)),
+                None => Some(MAX_WINDOW_BITS),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                SERVER_NO_CONTEXT_TAKEOVER => {
+                    config.server_no_context_takeover = true
+                }
+                CLIENT_NO_CONTEXT_TAKEOVER => {
+                    config.client_no_context_takeover = true
+                }
+                SERVER_MAX_WINDOW_BITS => {
+                    config.server_max_window_bits = Some(window_bits()?)
+                }
+                CLIENT_MAX_WINDOW_BITS => {
+                    config.client_max_window_bits = Some(window_bits()?)
+                }
+                _ => return None,
+            }
+            Some(config)
+        })
+    }
+
+    pub fn to_header_value(&self) -> String {
+        let mut value = PERMESSAGE_DEFLATE.to_string();
+        if self.server_no_context_takeover {
+            value.push_str("; ");
+            value.push_str(SERVER_NO_CONTEXT_TAKEOVER);
+        }
+        if self.client_no_context_takeover {
+            value.push_str("; ");
+            value.push_str(CLIENT_NO_CONTEXT_TAKEOVER);
+        }
+        if let Some(bits) = self.server_max_window_bits {
+            value.push_str(&format!("; {SERVER_MAX_WINDOW_BITS}={bits}"));
+        }
+        if let Some(bits) = self.client_max_window_bits {
+            value.push_str(&format!("; {CLIENT_MAX_WINDOW_BITS}={bits}"));
+        }
+        value
+    }
+}
+
+/* Description:
+ *      Compression state for one side of the connection.
+ *
+ *      flate2 pure rust backend only supports a 15 bit window. If the peer
+ *      limited our sending window below 15, outgoing messages are sent
+ *      uncompressed, which RFC 7692 permits per message. Inflating always
+ *      uses a 15 bit window, which accepts data compressed with any
+ *      smaller window.
+ */
+
+pub(crate) struct DeflateContext {
+    inflater: Decompress,
+    deflater: Compress,
+    reset_inflater: bool,
+    reset_deflater: bool,
+    can_compress: bool,
+}
+
+impl DeflateContext {
+    pub(crate) fn new(config: &DeflateConfig, role: Role) -> Self {
+        let (own, peer) = match role {
+            Role::Client => (
+                (
+                    config.client_no_context_takeover,
+                    config.client_max_window_bits,
+                ),
+                config.server_no_context_takeover,
+            ),
+            Role::Server => (
+                (
+                    config.server_no_context_takeover,
+                    config.server_max_window_bits,
+                ),
+                config.client_no_context_takeover,
+            ),
+        };
+        DeflateContext {
+            inflater: Decompress::new(false),
+            deflater: Compress::new(Compression::default(), false),
+            reset_inflater: peer,
+            reset_deflater: own.0,
+            can_compress: own.1.is_none_or(|bits| bits == MAX_WINDOW_BITS),
+        }
+    }
+
+    pub(crate) fn can_compress(&self) -> bool {
+        self.can_compress
+    }
+
+    /* Steps:
+     *      1. Append DEFLATE_TAIL to the payload.
+     *      2. Inflate till all input is consumed, growing the output in
+     *         CHUNK sized steps and bailing out once max_size is exceeded.
+     *      3. Reset the context if peer uses no_context_takeover.
+     */
+
+    pub(crate) fn inflate(
+        &mut self,
+        payload: &[u8],
+        max_size: usize,
+    ) -> Result<Vec<u8>, WebSocketError> {
+        let mut input = Vec::with_capacity(payload.len() + DEFLATE_TAIL.len());
+        input.extend_from_slice(payload);
+        input.extend_from_slice(&DEFLATE_TAIL);
+
+        let mut out = Vec::with_capacity(CHUNK);
+        let mut offset = 0;
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(CHUNK);
+            }
+            let (total_in, total_out) =
+                (self.inflater.total_in(), self.inflater.total_out());
+            self.inflater
+                .decompress_vec(
+                    &input[offset..],
+                    &mut out,
+                    FlushDecompress::Sync,
+                )
+                .map_err(|e| WebSocketError::Deflate(e.to_string()))?;
+            let consumed = (self.inflater.total_in() - total_in) as usize;
+            offset += consumed;
+            if out.len() > max_size {
+                return Err(WebSocketError::PayloadTooLarge(out.len() as u64));
+            }
+            let progress =
+                consumed > 0 || self.inflater.total_out() > total_out;
+            if (offset == input.len() && out.len() < out.capacity())
+                || !progress
+            {
+                break;
+            }
+        }
+        if self.reset_inflater {
+            self.inflater.reset(false);
+        }
+        Ok(out)
+    }
+
+    pub(crate) fn deflate(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, WebSocketError> {
+        let mut out = Vec::with_capacity(payload.len() / 2 + CHUNK);
+        let mut offset = 0;
+        loop {
+            if out.len() == out.capacity() {
+                out.reserve(CHUNK);
+            }
+            let total_in = self.deflater.total_in();
+            self.deflater
+                .compress_vec(
+                    &payload[offset..],
+                    &mut out,
+                    FlushCompress::Sync,
+                )
+                .map_err(|e| WebSocketError::Deflate(e.to_string()))?;
+            offset += (self.deflater.total_in() - total_in) as usize;
+            if offset == payload.len() && out.len() < out.capacity() {
+                break;
+            }
+        }
+        if out.ends_with(&DEFLATE_TAIL) {
+            out.truncate(out.len() - DEFLATE_TAIL.len());
+        }
+        if self.reset_deflater {
+            self.deflater.reset();
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deflate_config_parse() {
+        let value = b"x-webkit-deflate-frame, permessage-deflate; \
+                      client_no_context_takeover; server_max_window_bits=10";
+        let config = DeflateConfig::parse(value).unwrap();
+        let verify = DeflateConfig {
+            client_no_context_takeover: true,
+            server_max_window_bits: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(config, verify);
+        assert_eq!(
+            config.to_header_value(),
+            "permessage-deflate; client_no_context_takeover; \
+             server_max_window_bits=10"
+        );
+    }
+
+    #[test]
+    fn test_deflate_config_parse_bare_window_bits() {
+        let config = DeflateConfig::parse(
+            b"permessage-deflate; client_max_window_bits",
+        )
+        .unwrap();
+        assert_eq!(config.client_max_window_bits, Some(15));
+    }
+
+    #[test]
+    fn test_deflate_config_parse_invalid() {
+        assert!(DeflateConfig::parse(b"x-webkit-deflate-frame").is_none());
+        assert!(
+            DeflateConfig::parse(
+                b"permessage-deflate; server_max_window_bits=7"
+            )
+            .is_none()
+        );
+        assert!(
+            DeflateConfig::parse(b"permessage-deflate; unknown").is_none()
+        );
+    }
+
+    #[test]
+    fn test_inflate_rfc_example() {
+        // RFC 7692 7.2.3.1
+        let config = DeflateConfig::default();
+        let mut ctx = DeflateContext::new(&config, Role::Client);
+        let payload = [0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+        assert_eq!(ctx.inflate(&payload, 1024).unwrap(), b"Hello");
+        // shared sliding window, RFC 7692 7.2.3.2
+        let payload = [0xf2, 0x00, 0x11, 0x00, 0x00];
+        assert_eq!(ctx.inflate(&payload, 1024).unwrap(), b"Hello");
+    }
+
+    #[test]
+    fn test_deflate_round_trip() {
+        let config = DeflateConfig {
+            client_no_context_takeover: true,
+            ..Default::default()
+        };
+        let mut client = DeflateContext::new(&config, Role::Client);
+        let mut server = DeflateContext::new(&config, Role::Server);
+        let input = "hello websocket ".repeat(1000);
+        for _ in 0..2 {
+            let compressed = client.deflate(input.as_bytes()).unwrap();
+            assert!(compressed.len() < input.len());
+            assert!(!compressed.ends_with(&DEFLATE_TAIL));
+            let inflated = server.inflate(&compressed, usize::MAX).unwrap();
+            assert_eq!(inflated, input.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_inflate_max_size() {
+        let config = DeflateConfig::default();
+        let mut ctx = DeflateContext::new(&config, Role::Server);
+        let compressed = ctx.deflate(&[0; 100_000]).unwrap();
+        assert!(matches!(
+            ctx.inflate(&compressed, 10_000),
+            Err(WebSocketError::PayloadTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_deflate_window_bits_below_max() {
+        let config = DeflateConfig {
+            server_max_window_bits: Some(10),
+            ..Default::default()
+        };
+        assert!(!DeflateContext::new(&config, Role::Server).can_compress());
+        assert!(DeflateContext::new(&config, Role::Client).can_compress());
+    }
+}
@@ -0,0 +1,54 @@
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum WebSocketError {
+    // handshake
+    #[error("Invalid Method| {0}")]
+    InvalidMethod(String),
+    #[error("Not a websocket upgrade")]
+    NotUpgrade,
+    #[error("Missing Sec-WebSocket-Key")]
+    MissingKey,
+    #[error("Invalid Sec-WebSocket-Key| {0}")]
+    InvalidKey(String),
+    #[error("Unsupported Sec-WebSocket-Version| {0}")]
+    UnsupportedVersion(String),
+    #[error("Unsupported Sec-WebSocket-Protocol| {0}")]
+    UnsupportedProtocol(String),
+    #[error("Not 101 Switching Protocols| {0}")]
+    NotSwitchingProtocols(String),
+    #[error("Invalid Sec-WebSocket-Accept| {0}")]
+    InvalidAccept(String),
+    // frame
+    #[error("Invalid OpCode| {0}")]
+    InvalidOpCode(u8),
+    #[error("Reserved bits set")]
+    ReservedBits,
+    #[error("Fragmented control frame")]
+    FragmentedControl,
+    #[error("Control frame payload too long| {0}")]
+    ControlTooLong(u64),
+    #[error("Invalid payload length| {0}")]
+    InvalidPayloadLength(u64),
+    #[error("Payload too large| {0}")]
+    PayloadTooLarge(u64),
+    #[error("Masked frame required")]
+    MaskRequired,
+    #[error("Unexpected masked frame")]
+    UnexpectedMask,
+    // message
+    #[error("Continuation without a started message")]
+    UnexpectedContinuation,
+    #[error("Expected continuation frame")]
+    ExpectedContinuation,
+    #[error("Invalid UTF-8 in text payload")]
+    InvalidUtf8,
+    #[error("Invalid close payload")]
+    InvalidClosePayload,
+    #[error("Invalid close code| {0}")]
+    InvalidCloseCode(u16),
+    #[error("Failed to Deflate| {0}")]
+    Deflate(String),
+    #[error("Failed to generate masking key| {0}")]
+    MaskKey(String),
+}
@@ -0,0 +1,483 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::error::WebSocketError;
+
+const FIN: u8 = 0x80;
+const RSV1: u8 = 0x40;
+const RSV2_RSV3: u8 = 0x30;
+const OPCODE: u8 = 0x0f;
+const MASK: u8 = 0x80;
+const LEN: u8 = 0x7f;
+const LEN_U16: u8 = 126;
+const LEN_U64: u8 = 127;
+const MAX_CONTROL_PAYLOAD: u64 = 125;
+const MSB_U64: u64 = 1 << 63;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    pub fn is_control(&self) -> bool {
+        matches!(self, OpCode::Close | OpCode::Ping | OpCode::Pong)
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            OpCode::Continuation => 0x0,
+            OpCode::Text => 0x1,
+            OpCode::Binary => 0x2,
+            OpCode::Close => 0x8,
+            OpCode::Ping => 0x9,
+            OpCode::Pong => 0xa,
+        }
+    }
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = WebSocketError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let opcode = match value {
+            0x0 => OpCode::Continuation,
+            0x1 => OpCode::Text,
+            0x2 => OpCode::Binary,
+            0x8 => OpCode::Close,
+            0x9 => OpCode::Ping,
+            0xa => OpCode::Pong,
+            _ => return Err(WebSocketError::InvalidOpCode(value)),
+        };
+        Ok(opcode)
+    }
+}
+
+// RFC 6455 7.4.1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CloseCode {
+    Normal,
+    GoingAway,
+    ProtocolError,
+    Unsupported,
+    NoStatus,
+    Abnormal,
+    InvalidPayload,
+    PolicyViolation,
+    TooBig,
+    MandatoryExtension,
+    InternalError,
+    ServiceRestart,
+    TryAgainLater,
+    BadGateway,
+    TlsHandshake,
+    Other(u16),
+}
+
+impl CloseCode {
+    pub fn as_u16(&self) -> u16 {
+        match self {
+            CloseCode::Normal => 1000,
+            CloseCode::GoingAway => 1001,
+            CloseCode::ProtocolError => 1002,
+            CloseCode::Unsupported => 1003,
+            CloseCode::NoStatus => 1005,
+            CloseCode::Abnormal => 1006,
+            CloseCode::InvalidPayload => 1007,
+            CloseCode::PolicyViolation => 1008,
+            CloseCode::TooBig => 1009,
+            CloseCode::MandatoryExtension => 1010,
+            CloseCode::InternalError => 1011,
+            CloseCode::ServiceRestart => 1012,
+            CloseCode::TryAgainLater => 1013,
+            CloseCode::BadGateway => 1014,
+            CloseCode::TlsHandshake => 1015,
+            CloseCode::Other(code) => *code,
+        }
+    }
+
+    // 1005, 1006 and 1015 are reserved for local use and must not be sent
+    pub fn is_sendable(&self) -> bool {
+        matches!(self.as_u16(), 1000..=1003 | 1007..=1014 | 3000..=4999)
+    }
+}
+
+impl From<u16> for CloseCode {
+    fn from(code: u16) -> Self {
+        match code {
+            1000 => CloseCode::Normal,
+            1001 => CloseCode::GoingAway,
+            1002 => CloseCode::ProtocolError,
+            1003 => CloseCode::Unsupported,
+            1005 => CloseCode::NoStatus,
+            1006 => CloseCode::Abnormal,
+            1007 => CloseCode::InvalidPayload,
+            1008 => CloseCode::PolicyViolation,
+            1009 => CloseCode::TooBig,
+            1010 => CloseCode::MandatoryExtension,
+            1011 => CloseCode::InternalError,
+            1012 => CloseCode::ServiceRestart,
+            1013 => CloseCode::TryAgainLater,
+            1014 => CloseCode::BadGateway,
+            1015 => CloseCode::TlsHandshake,
+            code => CloseCode::Other(code),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloseFrame {
+    pub code: CloseCode,
+    pub reason: String,
+}
+
+impl CloseFrame {
+    // code(2) | reason, an empty payload has no status
+    pub fn parse(mut payload: Bytes) -> Result<Option<Self>, WebSocketError> {
+        match payload.len() {
+            0 => return Ok(None),
+            1 => return Err(WebSocketError::InvalidClosePayload),
+            _ => (),
+        }
+        let code = CloseCode::from(payload.get_u16());
+        if !code.is_sendable() {
+            return Err(WebSocketError::InvalidCloseCode(code.as_u16()));
+        }
+        let reason = String::from_utf8(payload.to_vec())
+            .map_err(|_| WebSocketError::InvalidUtf8)?;
+        Ok(Some(CloseFrame {
+            code,
+            reason,
+        }))
+    }
+
+    pub fn into_payload(self) -> Bytes {
+        let mut payload = BytesMut::with_capacity(2 + self.reason.len());
+        payload.put_u16(self.code.as_u16());
+        payload.extend_from_slice(self.reason.as_bytes());
+        payload.freeze()
+    }
+}
+
+// payload is always stored unmasked, mask is applied in write()
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub fin: bool,
+    pub rsv1: bool,
+    pub opcode: OpCode,
+    pub mask: Option<[u8; 4]>,
+    pub payload: Bytes,
+}
+
+impl Frame {
+    pub fn new(opcode: OpCode, payload: impl Into<Bytes>) -> Self {
+        Frame {
+            fin: true,
+            rsv1: false,
+            opcode,
+            mask: None,
+            payload: payload.into(),
+        }
+    }
+
+    pub fn with_fin(mut self, fin: bool) -> Self {
+        self.fin = fin;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: [u8; 4]) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    /* Description:
+     *      Parse a single frame from the start of buf, RFC 6455 5.2.
+     *
+     * Steps:
+     *      1. Read the 2 byte header, reject RSV2/RSV3 and unknown opcodes.
+     *      2. Read extended payload length (16 or 64 bit), the most
+     *         significant bit of a 64 bit length must be 0.
+     *      3. Control frames must not be fragmented and must carry atmost
+     *         125 bytes.
+     *      4. Read optional masking key.
+     *      5. Wait till the complete payload is received, then split the
+     *         frame off buf and unmask the payload.
+     *
+     * Returns:
+     *      Ok(None) if buf does not contain a complete frame, buf is left
+     *      untouched.
+     *
+     * Error:
+     *      WebSocketError::ReservedBits        [1]
+     *      WebSocketError::InvalidOpCode       [1]
+     *      WebSocketError::InvalidPayloadLength [2]
+     *      WebSocketError::PayloadTooLarge     [2]
+     *      WebSocketError::FragmentedControl   [3]
+     *      WebSocketError::ControlTooLong      [3]
+     */
+
+    pub fn parse(
+        buf: &mut BytesMut,
+        max_payload: usize,
+    ) -> Result<Option<Self>, WebSocketError> {
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+        let (first, second) = (buf[0], buf[1]);
+        if first & RSV2_RSV3 != 0 {
+            return Err(WebSocketError::ReservedBits);
+        }
+        let fin = first & FIN != 0;
+        let opcode = OpCode::try_from(first & OPCODE)?;
+
+        let mut header_len = 2;
+        let len = match second & LEN {
+            LEN_U16 => {
+                header_len += 2;
+                match buf.get(2..4) {
+                    Some(len) => u16::from_be_bytes([len[0], len[1]]) as u64,
+                    None => return Ok(None),
+                }
+            }
+            LEN_U64 => {
+                header_len += 8;
+                let len = match buf.get(2..10) {
+                    Some(len) => u64::from_be_bytes(len.try_into().unwrap()),
+                    None => return Ok(None),
+                };
+                // most significant bit must be 0
+                if len & MSB_U64 != 0 {
+                    return Err(WebSocketError::InvalidPayloadLength(len));
+                }
+                len
+            }
+            len => len as u64,
+        };
+        if opcode.is_control() {
+            if !fin {
+                return Err(WebSocketError::FragmentedControl);
+            }
+            if len > MAX_CONTROL_PAYLOAD {
+                return Err(WebSocketError::ControlTooLong(len));
+            }
+        }
+        if len > max_payload as u64 {
+            return Err(WebSocketError::PayloadTooLarge(len));
+        }
+
+        let mask = if second & MASK != 0 {
+            header_len += 4;
+            match buf.get(header_len - 4..header_len) {
+                Some(mask) => Some([mask[0], mask[1], mask[2], mask[3]]),
+                None => return Ok(None),
+            }
+        } else {
+            None
+        };
+
+        let frame_len = usize::try_from(len)
+            .ok()
+            .and_then(|len| header_len.checked_add(len))
+            .ok_or(WebSocketError::PayloadTooLarge(len))?;
+        if buf.len() < frame_len {
+            return Ok(None);
+        }
+        let len = frame_len - header_len;
+        buf.advance(header_len);
+        let mut payload = buf.split_to(len);
+        if let Some(mask) = mask {
+            apply_mask(&mut payload, mask);
+        }
+        Ok(Some(Frame {
+            fin,
+            rsv1: first & RSV1 != 0,
+            opcode,
+            mask,
+            payload: payload.freeze(),
+        }))
+    }
+
+    pub fn write(&self, buf: &mut BytesMut) {
+        let mut first = self.opcode.as_u8();
+        if self.fin {
+            first |= FIN;
+        }
+        if self.rsv1 {
+            first |= RSV1;
+        }
+        buf.put_u8(first);
+
+        let mask_bit = if self.mask.is_some() {
+            MASK
+        } else {
+            0
+        };
+        let len = self.payload.len();
+        if len < LEN_U16 as usize {
+            buf.put_u8(mask_bit | len as u8);
+        } else if len <= u16::MAX as usize {
+            buf.put_u8(mask_bit | LEN_U16);
+            buf.put_u16(len as u16);
+        } else {
+            buf.put_u8(mask_bit | LEN_U64);
+            buf.put_u64(len as u64);
+        }
+
+        let start = buf.len();
+        buf.extend_from_slice(&self.payload);
+        if let Some(mask) = self.mask {
+            buf.put_slice(&[0; 4]);
+            buf[start..].rotate_right(4);
+            buf[start..start + 4].copy_from_slice(&mask);
+            apply_mask(&mut buf[start + 4..], mask);
+        }
+    }
+
+    pub fn into_bytes(self) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(14 + self.payload.len());
+        self.write(&mut buf);
+        buf
+    }
+}
+
+// masking and unmasking are the same operation
+#[inline]
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    payload.iter_mut().enumerate().for_each(|(i, b)| *b ^= mask[i % 4]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: usize = 1 << 20;
+
+    #[test]
+    fn test_frame_parse_unmasked_text() {
+        // RFC 6455 5.7
+        let mut buf = BytesMut::from(&b"\x81\x05Hello"[..]);
+        let frame = Frame::parse(&mut buf, MAX).unwrap().unwrap();
+        assert_eq!(frame, Frame::new(OpCode::Text, "Hello"));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_frame_parse_masked_text() {
+        let input = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58";
+        let mut buf = BytesMut::from(&input[..]);
+        let frame = Frame::parse(&mut buf, MAX).unwrap().unwrap();
+        assert_eq!(frame.payload, "Hello");
+        assert_eq!(frame.mask, Some([0x37, 0xfa, 0x21, 0x3d]));
+        assert_eq!(frame.into_bytes(), input[..]);
+    }
+
+    #[test]
+    fn test_frame_parse_fragmented() {
+        let mut buf = BytesMut::from(&b"\x01\x03Hel\x80\x02lo"[..]);
+        let first = Frame::parse(&mut buf, MAX).unwrap().unwrap();
+        assert!(!first.fin);
+        assert_eq!(first.opcode, OpCode::Text);
+        let second = Frame::parse(&mut buf, MAX).unwrap().unwrap();
+        assert!(second.fin);
+        assert_eq!(second.opcode, OpCode::Continuation);
+        assert_eq!(second.payload, "lo");
+    }
+
+    #[test]
+    fn test_frame_extended_length() {
+        let frame = Frame::new(OpCode::Binary, vec![7u8; 256]);
+        let bytes = frame.clone().into_bytes();
+        assert_eq!(bytes[..4], [0x82, 0x7e, 0x01, 0x00]);
+        let mut buf = bytes;
+        assert_eq!(Frame::parse(&mut buf, MAX).unwrap().unwrap(), frame);
+
+        let frame = Frame::new(OpCode::Binary, vec![7u8; 65536]);
+        let bytes = frame.clone().into_bytes();
+        assert_eq!(bytes[..10], [0x82, 0x7f, 0, 0, 0, 0, 0, 1, 0, 0]);
+        let mut buf = bytes;
+        assert_eq!(Frame::parse(&mut buf, MAX).unwrap().unwrap(), frame);
+    }
+
+    #[test]
+    fn test_frame_parse_incomplete() {
+        let input = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58";
+        for len in [1, 2, 5, 10] {
+            let mut buf = BytesMut::from(&input[..len]);
+            assert!(Frame::parse(&mut buf, MAX).unwrap().is_none());
+            assert_eq!(buf.len(), len);
+        }
+        let mut buf = BytesMut::from(&b"\x82\x7e\x01"[..]);
+        assert!(Frame::parse(&mut buf, MAX).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_frame_parse_invalid() {
+        let mut buf = BytesMut::from(&b"\x83\x00"[..]);
+        assert_eq!(
+            Frame::parse(&mut buf, MAX),
+            Err(WebSocketError::InvalidOpCode(3))
+        );
+        let mut buf = BytesMut::from(&b"\xa1\x00"[..]);
+        assert_eq!(
+            Frame::parse(&mut buf, MAX),
+            Err(WebSocketError::ReservedBits)
+        );
+        let mut buf = BytesMut::from(&b"\x09\x00"[..]);
+        assert_eq!(
+            Frame::parse(&mut buf, MAX),
+            Err(WebSocketError::FragmentedControl)
+        );
+        let mut buf = BytesMut::from(&b"\x89\x7e\x00\x7e"[..]);
+        assert_eq!(
+            Frame::parse(&mut buf, MAX),
+            Err(WebSocketError::ControlTooLong(126))
+        );
+        let mut buf = BytesMut::from(&b"\x82\x7e\x01\x00"[..]);
+        assert_eq!(
+            Frame::parse(&mut buf, 255),
+            Err(WebSocketError::PayloadTooLarge(256))
+        );
+        let mut buf =
+            BytesMut::from(&b"\x82\x7f\x80\x00\x00\x00\x00\x00\x00\x00"[..]);
+        assert_eq!(
+            Frame::parse(&mut buf, usize::MAX),
+            Err(WebSocketError::InvalidPayloadLength(1 << 63))
+        );
+        // largest valid length, waits for the payload without overflow
+        let mut buf =
+            BytesMut::from(&b"\x82\x7f\x7f\xff\xff\xff\xff\xff\xff\xff"[..]);
+        assert_eq!(Frame::parse(&mut buf, usize::MAX), Ok(None));
+    }
+
+    #[test]
+    fn test_close_frame() {
+        let close = CloseFrame {
+            code: CloseCode::GoingAway,
+            reason: "bye".to_string(),
+        };
+        let payload = close.clone().into_payload();
+        assert_eq!(payload, b"\x03\xe9bye"[..]);
+        assert_eq!(CloseFrame::parse(payload).unwrap(), Some(close));
+        assert_eq!(CloseFrame::parse(Bytes::new()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_close_frame_invalid() {
+        assert_eq!(
+            CloseFrame::parse(Bytes::from_static(b"\x03")),
+            Err(WebSocketError::InvalidClosePayload)
+        );
+        assert_eq!(
+            CloseFrame::parse(Bytes::from_static(b"\x03\xed")),
+            Err(WebSocketError::InvalidCloseCode(1005))
+        );
+        assert_eq!(
+            CloseFrame::parse(Bytes::from_static(b"\x03\xe8\xff")),
+            Err(WebSocketError::InvalidUtf8)
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use header_plz::{
+    HeaderMap, OneRequestLine, OneResponseLine,
+    const_headers::{
+        CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS,
+        SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION,
+        UPGRADE,
+    },
+    method::Method,
+    status::StatusCode,
+};
+
+use crate::{
+    OneOne, Response, Version,
+    utils::token::{has_token, header_tokens},
+};
+
+use super::{
+    WEBSOCKET_VERSION, deflate::DeflateConfig, error::WebSocketError,
+    websocket_accept,
+};
+
+const WEBSOCKET: &str = "websocket";
+const UPGRADE_TOKEN: &str = "upgrade";
+const KEY_LEN: usize = 16;
+
+impl OneOne<OneRequestLine> {
+    pub fn is_websocket_upgrade(&self) -> bool {
+        self.validate_websocket_upgrade().is_ok()
+    }
+
+    /* Description:
+     *      Validate the client opening handshake, RFC 6455 4.2.1.
+     *
+     * Steps:
+     *      1. Method must be GET.
+     *      2. Connection must contain "upgrade" and Upgrade must contain
+     *         "websocket".
+     *      3. Sec-WebSocket-Key must be base64 of 16 bytes.
+     *      4. Sec-WebSocket-Version must be 13.
+     *
+     * Returns:
+     *      Sec-WebSocket-Key
+     *
+     * Error:
+     *      WebSocketError::InvalidMethod       [1]
+     *      WebSocketError::NotUpgrade          [2]
+     *      WebSocketError::MissingKey          [3]
+     *      WebSocketError::InvalidKey          [3]
+     *      WebSocketError::UnsupportedVersion  [4]
+     */
+
+    pub fn validate_websocket_upgrade(&self) -> Result<&[u8], WebSocketError> {
+        if self.method_as_enum() != Method::GET {
+            return Err(WebSocketError::InvalidMethod(
+                self.method_as_string().to_string(),
+            ));
+        }
+        let header_map = self.message_head.header_map();
+        if !has_token(header_map, CONNECTION, UPGRADE_TOKEN)
+            || !has_token(header_map, UPGRADE, WEBSOCKET)
+        {
+            return Err(WebSocketError::NotUpgrade);
+        }
+
+        let key = header_map
+            .value_of_key(SEC_WEBSOCKET_KEY)
+            .ok_or(WebSocketError::MissingKey)?
+            .trim_ascii();
+        if STANDARD.decode(key).map_or(true, |k| k.len() != KEY_LEN) {
+            return Err(WebSocketError::InvalidKey(lossy(key)));
+        }
+
+        let version = header_map
+            .value_of_key(SEC_WEBSOCKET_VERSION)
+            .unwrap_or_default()
+            .trim_ascii();
+        if version != WEBSOCKET_VERSION.as_bytes() {
+            return Err(WebSocketError::UnsupportedVersion(lossy(version)));
+        }
+        Ok(key)
+    }
+
+    // Sec-WebSocket-Protocol offered by the client, in order of preference
+    pub fn websocket_protocols(&self) -> Vec<&[u8]> {
+        header_tokens(self.message_head.header_map(), SEC_WEBSOCKET_PROTOCOL)
+            .collect()
+    }
+}
+
+impl OneOne<OneResponseLine> {
+    /* Description:
+     *      Build the server 101 reply for a websocket upgrade request.
+     *
+     * Steps:
+     *      1. Validate the request and get the key.
+     *      2. If protocol is provided, it must be one offered by the client.
+     *      3. Build HTTP/1.1 101 with Upgrade, Connection,
+     *         Sec-WebSocket-Accept and optional Sec-WebSocket-Protocol.
+     *
+     * Error:
+     *      OneRequest::validate_websocket_upgrade()    [1]
+     *      WebSocketError::UnsupportedProtocol         [2]
+     */
+
+    pub fn websocket_switching_protocols(
+        req: &OneOne<OneRequestLine>,
+        protocol: Option<&str>,
+    ) -> Result<Self, WebSocketError> {
+        let key = req.validate_websocket_upgrade()?;
+        let mut headers = HeaderMap::new();
+        headers.insert(UPGRADE, WEBSOCKET);
+        headers.insert(CONNECTION, "Upgrade");
+        headers.insert(SEC_WEBSOCKET_ACCEPT, websocket_accept(key).as_str());
+        if let Some(protocol) = protocol {
+            if !req.websocket_protocols().contains(&protocol.as_bytes()) {
+                return Err(WebSocketError::UnsupportedProtocol(
+                    protocol.to_string(),
+                ));
+            }
+            headers.insert(SEC_WEBSOCKET_PROTOCOL, protocol);
+        }
+        let res = Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .headers(headers)
+            .build();
+        Ok(Self::from_two(res, Version::H11))
+    }
+
+    /* Description:
+     *      Client side validation of the server reply, RFC 6455 4.1.
+     *
+     * Steps:
+     *      1. Status must be 101.
+     *      2. Upgrade must contain "websocket" and Connection "upgrade".
+     *      3. Sec-WebSocket-Accept must match the key sent in the request.
+     *      4. Sec-WebSocket-Protocol, if present, must be one offered in the
+     *         request.
+     *
+     * Returns:
+     *      Negotiated subprotocol, if any.
+     *
+     * Error:
+     *      WebSocketError::NotSwitchingProtocols   [1]
+     *      WebSocketError::NotUpgrade              [2]
+     *      WebSocketError::MissingKey              [3]
+     *      WebSocketError::InvalidAccept           [3]
+     *      WebSocketError::UnsupportedProtocol     [4]
+     */
+
+    pub fn validate_websocket_accept(
+        &self,
+        req: &OneOne<OneRequestLine>,
+    ) -> Result<Option<&[u8]>, WebSocketError> {
+        match self.status_code() {
+            Ok(StatusCode::SWITCHING_PROTOCOLS) => (),
+            Ok(status) => {
+                return Err(WebSocketError::NotSwitchingProtocols(
+                    status.to_string(),
+                ));
+            }
+            Err(e) => {
+                return Err(WebSocketError::NotSwitchingProtocols(
+                    e.to_string(),
+                ));
+            }
+        }
+        let header_map = self.message_head.header_map();
+        if !has_token(header_map, CONNECTION, UPGRADE_TOKEN)
+            || !has_token(header_map, UPGRADE, WEBSOCKET)
+        {
+            return Err(WebSocketError::NotUpgrade);
+        }
+
+        let key = req
+            .message_head
+            .header_map()
+            .value_of_key(SEC_WEBSOCKET_KEY)
+            .ok_or(WebSocketError::MissingKey)?;
+        let accept = header_map
+            .value_of_key(SEC_WEBSOCKET_ACCEPT)
+            .unwrap_or_default()
+            .trim_ascii();
+        if accept != websocket_accept(key).as_bytes() {
+            return Err(WebSocketError::InvalidAccept(lossy(accept)));
+        }
+
+        let Some(protocol) = header_map
+            .value_of_key(SEC_WEBSOCKET_PROTOCOL)
+            .map(|p| p.trim_ascii())
+        else {
+            return Ok(None);
+        };
+        if !req.websocket_protocols().contains(&protocol) {
+            return Err(WebSocketError::UnsupportedProtocol(lossy(protocol)));
+        }
+        Ok(Some(protocol))
+    }
+
+    // permessage-deflate parameters accepted by the server, if any
+    pub fn websocket_deflate(&self) -> Option<DeflateConfig> {
+        self.message_head
+            .header_map()
+            .value_of_key(SEC_WEBSOCKET_EXTENSIONS)
+            .and_then(DeflateConfig::parse)
+    }
+}
+
+fn lossy(src: &[u8]) -> String {
+    String::from_utf8_lossy(src).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use crate::{OneRequest, OneResponse};
+
+    use super::*;
+
+    // RFC 6455 1.3
+    const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+    #[test]
+    fn test_is_websocket_upgrade() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert!(req.is_websocket_upgrade());
+        assert_eq!(req.validate_websocket_upgrade().unwrap(), KEY.as_bytes());
+    }
+
+    #[test]
+    fn test_websocket_upgrade_invalid_method() {
+        let input = "POST /chat HTTP/1.1\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.validate_websocket_upgrade(),
+            Err(WebSocketError::InvalidMethod("POST".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_websocket_upgrade_missing_key() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\
+                     Sec-WebSocket-Version: 13\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.validate_websocket_upgrade(),
+            Err(WebSocketError::MissingKey)
+        );
+    }
+
+    #[test]
+    fn test_websocket_upgrade_invalid_key() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\
+                     Sec-WebSocket-Key: c2hvcnQ=\r\n\
+                     Sec-WebSocket-Version: 13\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.validate_websocket_upgrade(),
+            Err(WebSocketError::InvalidKey("c2hvcnQ=".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_websocket_upgrade_unsupported_version() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 8\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.validate_websocket_upgrade(),
+            Err(WebSocketError::UnsupportedVersion("8".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_websocket_protocols() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\
+                     Sec-WebSocket-Protocol: chat, superchat\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            req.websocket_protocols(),
+            vec![b"chat".as_slice(), b"superchat"]
+        );
+    }
+
+    #[test]
+    fn test_websocket_switching_protocols() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\
+                     Sec-WebSocket-Protocol: chat, superchat\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let res =
+            OneResponse::websocket_switching_protocols(&req, Some("chat"))
+                .unwrap();
+        let verify = "HTTP/1.1 101 Switching Protocols\r\n\
+                      upgrade: websocket\r\n\
+                      connection: Upgrade\r\n\
+                      sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+                      sec-websocket-protocol: chat\r\n\r\n";
+        assert_eq!(res.into_bytes(), verify);
+    }
+
+    #[test]
+    fn test_websocket_switching_protocols_unsupported_protocol() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\
+                     Sec-WebSocket-Protocol: chat\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            OneResponse::websocket_switching_protocols(&req, Some("mqtt"))
+                .unwrap_err(),
+            WebSocketError::UnsupportedProtocol("mqtt".to_string())
+        );
+        // subprotocol names are case-sensitive, RFC 6455 4.1
+        assert_eq!(
+            OneResponse::websocket_switching_protocols(&req, Some("Chat"))
+                .unwrap_err(),
+            WebSocketError::UnsupportedProtocol("Chat".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_websocket_accept() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\
+                     Sec-WebSocket-Protocol: chat\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let res =
+            OneResponse::websocket_switching_protocols(&req, Some("chat"))
+                .unwrap();
+        assert_eq!(
+            res.validate_websocket_accept(&req).unwrap(),
+            Some(b"chat".as_slice())
+        );
+
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let input = "HTTP/1.1 101 Switching Protocols\r\n\
+                     Upgrade: WebSocket\r\n\
+                     Connection: upgrade\r\n\
+                     Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(res.validate_websocket_accept(&req).unwrap(), None);
+    }
+
+    #[test]
+    fn test_websocket_deflate() {
+        let input = "HTTP/1.1 101 Switching Protocols\r\n\
+                     Sec-WebSocket-Extensions: permessage-deflate; \
+                     server_no_context_takeover\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        let verify = DeflateConfig {
+            server_no_context_takeover: true,
+            ..Default::default()
+        };
+        assert_eq!(res.websocket_deflate(), Some(verify));
+
+        let input = "HTTP/1.1 101 Switching Protocols\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(res.websocket_deflate(), None);
+    }
+
+    #[test]
+    fn test_validate_websocket_accept_mismatch() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let input = "HTTP/1.1 101 Switching Protocols\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\
+                     Sec-WebSocket-Accept: invalid\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            res.validate_websocket_accept(&req),
+            Err(WebSocketError::InvalidAccept("invalid".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_validate_websocket_accept_not_101() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let input = "HTTP/1.1 403 Forbidden\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert!(matches!(
+            res.validate_websocket_accept(&req),
+            Err(WebSocketError::NotSwitchingProtocols(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_websocket_accept_unoffered_protocol() {
+        let input = "GET /chat HTTP/1.1\r\n\
+                     Host: server.example.com\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: keep-alive, Upgrade\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                     Sec-WebSocket-Version: 13\r\n\r\n";
+        let req = OneRequest::try_from(BytesMut::from(input)).unwrap();
+        let input = "HTTP/1.1 101 Switching Protocols\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\
+                     Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+                     Sec-WebSocket-Protocol: chat\r\n\r\n";
+        let res = OneResponse::try_from(BytesMut::from(input)).unwrap();
+        assert_eq!(
+            res.validate_websocket_accept(&req),
+            Err(WebSocketError::UnsupportedProtocol("chat".to_string()))
+        );
+    }
+}
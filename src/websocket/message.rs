@@ -0,0 +1,421 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::utils::random::secure_random;
+
+use super::{
+    Role,
+    deflate::{DeflateConfig, DeflateContext},
+    error::WebSocketError,
+    frame::{CloseFrame, Frame, OpCode},
+};
+
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsMessage {
+    Text(String),
+    Binary(Bytes),
+    Ping(Bytes),
+    Pong(Bytes),
+    Close(Option<CloseFrame>),
+}
+
+struct Fragments {
+    opcode: OpCode,
+    compressed: bool,
+    payload: BytesMut,
+}
+
+/* Description:
+ *      Frame level codec for one direction pair of a websocket connection.
+ *
+ *      role is the side this codec acts as. A Server codec expects masked
+ *      frames from the client and sends unmasked frames, a Client codec the
+ *      opposite. An intercepting proxy uses a Server codec towards the
+ *      client and a Client codec towards the server.
+ */
+
+pub struct WebSocketCodec {
+    role: Role,
+    max_message_size: usize,
+    deflate: Option<DeflateContext>,
+    fragments: Option<Fragments>,
+}
+
+impl WebSocketCodec {
+    pub fn new(role: Role) -> Self {
+        WebSocketCodec {
+            role,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            deflate: None,
+            fragments: None,
+        }
+    }
+
+    pub fn with_deflate(mut self, config: &DeflateConfig) -> Self {
+        self.deflate = Some(DeflateContext::new(config, self.role));
+        self
+    }
+
+    pub fn with_max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /* Description:
+     *      Decode the next complete message from buf. Control frames
+     *      interleaved in a fragmented message are returned immediately.
+     *
+     * Returns:
+     *      Ok(None) if more bytes are needed. Consumed frames are removed
+     *      from buf, partial fragments are kept in the codec.
+     *
+     * Error:
+     *      Frame::parse()
+     *      WebSocketError::MaskRequired            [server, unmasked frame]
+     *      WebSocketError::UnexpectedMask          [client, masked frame]
+     *      WebSocketError::ReservedBits            [rsv1 without deflate]
+     *      WebSocketError::UnexpectedContinuation
+     *      WebSocketError::ExpectedContinuation
+     *      WebSocketError::PayloadTooLarge
+     *      WebSocketError::InvalidUtf8
+     */
+
+    pub fn decode(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<WsMessage>, WebSocketError> {
+        while let Some(frame) = Frame::parse(buf, self.max_message_size)? {
+            if let Some(message) = self.push_frame(frame)? {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn push_frame(
+        &mut self,
+        frame: Frame,
+    ) -> Result<Option<WsMessage>, WebSocketError> {
+        match (self.role, frame.mask.is_some()) {
+            (Role::Server, false) => return Err(WebSocketError::MaskRequired),
+            (Role::Client, true) => {
+                return Err(WebSocketError::UnexpectedMask);
+            }
+            _ => (),
+        }
+        if frame.rsv1
+            && (self.deflate.is_none()
+                || frame.opcode.is_control()
+                || frame.opcode == OpCode::Continuation)
+        {
+            return Err(WebSocketError::ReservedBits);
+        }
+
+        let message = match frame.opcode {
+            OpCode::Ping => WsMessage::Ping(frame.payload),
+            OpCode::Pong => WsMessage::Pong(frame.payload),
+            OpCode::Close => {
+                WsMessage::Close(CloseFrame::parse(frame.payload)?)
+            }
+            OpCode::Text | OpCode::Binary => {
+                if self.fragments.is_some() {
+                    return Err(WebSocketError::ExpectedContinuation);
+                }
+                let fragments = Fragments {
+                    opcode: frame.opcode,
+                    compressed: frame.rsv1,
+                    payload: BytesMut::from(frame.payload),
+                };
+                if !frame.fin {
+                    self.fragments = Some(fragments);
+                    return Ok(None);
+                }
+                self.finish(fragments)?
+            }
+            OpCode::Continuation => {
+                let mut fragments = self
+                    .fragments
+                    .take()
+                    .ok_or(WebSocketError::UnexpectedContinuation)?;
+                let len = fragments.payload.len() + frame.payload.len();
+                if len > self.max_message_size {
+                    return Err(WebSocketError::PayloadTooLarge(len as u64));
+                }
+                fragments.payload.extend_from_slice(&frame.payload);
+                if !frame.fin {
+                    self.fragments = Some(fragments);
+                    return Ok(None);
+                }
+                self.finish(fragments)?
+            }
+        };
+        Ok(Some(message))
+    }
+
+    fn finish(
+        &mut self,
+        fragments: Fragments,
+    ) -> Result<WsMessage, WebSocketError> {
+        let payload = match (fragments.compressed, self.deflate.as_mut()) {
+            (true, Some(deflate)) => Bytes::from(
+                deflate.inflate(&fragments.payload, self.max_message_size)?,
+            ),
+            _ => fragments.payload.freeze(),
+        };
+        let message = match fragments.opcode {
+            OpCode::Text => WsMessage::Text(
+                String::from_utf8(payload.to_vec())
+                    .map_err(|_| WebSocketError::InvalidUtf8)?,
+            ),
+            _ => WsMessage::Binary(payload),
+        };
+        Ok(message)
+    }
+
+    /* Description:
+     *      Encode message as a single frame into buf.
+     *
+     * Steps:
+     *      1. Build the frame for the message.
+     *      2. Compress Text and Binary payloads if deflate is negotiated.
+     *      3. Mask with a key from the OS CSPRNG if acting as Client.
+     *
+     * Error:
+     *      WebSocketError::InvalidCloseCode    [close code not sendable]
+     *      WebSocketError::ControlTooLong      [control payload > 125]
+     *      WebSocketError::Deflate             [2]
+     *      WebSocketError::MaskKey             [3]
+     */
+
+    pub fn encode(
+        &mut self,
+        message: WsMessage,
+        buf: &mut BytesMut,
+    ) -> Result<(), WebSocketError> {
+        let mut frame = match message {
+            WsMessage::Text(text) => Frame::new(OpCode::Text, text),
+            WsMessage::Binary(data) => Frame::new(OpCode::Binary, data),
+            WsMessage::Ping(data) => Frame::new(OpCode::Ping, data),
+            WsMessage::Pong(data) => Frame::new(OpCode::Pong, data),
+            WsMessage::Close(None) => Frame::new(OpCode::Close, Bytes::new()),
+            WsMessage::Close(Some(close)) => {
+                if !close.code.is_sendable() {
+                    return Err(WebSocketError::InvalidCloseCode(
+                        close.code.as_u16(),
+                    ));
+                }
+                Frame::new(OpCode::Close, close.into_payload())
+            }
+        };
+        if frame.opcode.is_control() && frame.payload.len() > 125 {
+            return Err(WebSocketError::ControlTooLong(
+                frame.payload.len() as u64
+            ));
+        }
+        if let Some(deflate) = self.deflate.as_mut()
+            && deflate.can_compress()
+            && !frame.opcode.is_control()
+        {
+            frame.payload = Bytes::from(deflate.deflate(&frame.payload)?);
+            frame.rsv1 = true;
+        }
+        if self.role == Role::Client {
+            let mask = secure_random()
+                .map_err(|e| WebSocketError::MaskKey(e.to_string()))?;
+            frame.mask = Some(mask);
+        }
+        frame.write(buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::websocket::frame::CloseCode;
+
+    use super::*;
+
+    fn masked(frame: Frame) -> BytesMut {
+        frame.with_mask([0x37, 0xfa, 0x21, 0x3d]).into_bytes()
+    }
+
+    #[test]
+    fn test_codec_decode_text() {
+        let mut codec = WebSocketCodec::new(Role::Server);
+        let mut buf = masked(Frame::new(OpCode::Text, "Hello"));
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(WsMessage::Text("Hello".to_string()))
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_codec_decode_fragmented_with_control() {
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf =
+            Frame::new(OpCode::Text, "Hel").with_fin(false).into_bytes();
+        buf.unsplit(Frame::new(OpCode::Ping, "p").into_bytes());
+        buf.unsplit(Frame::new(OpCode::Continuation, "lo").into_bytes());
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(WsMessage::Ping(Bytes::from("p")))
+        );
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(WsMessage::Text("Hello".to_string()))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn test_codec_decode_partial() {
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let input = Frame::new(OpCode::Binary, vec![1u8; 300]).into_bytes();
+        let mut buf = BytesMut::from(&input[..100]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&input[100..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(WsMessage::Binary(Bytes::from(vec![1u8; 300])))
+        );
+    }
+
+    #[test]
+    fn test_codec_decode_mask_mismatch() {
+        let mut codec = WebSocketCodec::new(Role::Server);
+        let mut buf = Frame::new(OpCode::Text, "a").into_bytes();
+        assert_eq!(codec.decode(&mut buf), Err(WebSocketError::MaskRequired));
+
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf = masked(Frame::new(OpCode::Text, "a"));
+        assert_eq!(
+            codec.decode(&mut buf),
+            Err(WebSocketError::UnexpectedMask)
+        );
+    }
+
+    #[test]
+    fn test_codec_decode_invalid_sequence() {
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf = Frame::new(OpCode::Continuation, "a").into_bytes();
+        assert_eq!(
+            codec.decode(&mut buf),
+            Err(WebSocketError::UnexpectedContinuation)
+        );
+
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf =
+            Frame::new(OpCode::Text, "a").with_fin(false).into_bytes();
+        buf.unsplit(Frame::new(OpCode::Binary, "b").into_bytes());
+        assert_eq!(
+            codec.decode(&mut buf),
+            Err(WebSocketError::ExpectedContinuation)
+        );
+    }
+
+    #[test]
+    fn test_codec_decode_invalid_utf8() {
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf = Frame::new(OpCode::Text, &b"\xff\xfe"[..]).into_bytes();
+        assert_eq!(codec.decode(&mut buf), Err(WebSocketError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_codec_decode_rsv1_without_deflate() {
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut frame = Frame::new(OpCode::Text, "a");
+        frame.rsv1 = true;
+        let mut buf = frame.into_bytes();
+        assert_eq!(codec.decode(&mut buf), Err(WebSocketError::ReservedBits));
+    }
+
+    #[test]
+    fn test_codec_decode_message_too_large() {
+        let mut codec =
+            WebSocketCodec::new(Role::Client).with_max_message_size(4);
+        let mut buf =
+            Frame::new(OpCode::Text, "abc").with_fin(false).into_bytes();
+        buf.unsplit(Frame::new(OpCode::Continuation, "de").into_bytes());
+        assert_eq!(
+            codec.decode(&mut buf),
+            Err(WebSocketError::PayloadTooLarge(5))
+        );
+    }
+
+    #[test]
+    fn test_codec_decode_close() {
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf =
+            Frame::new(OpCode::Close, &b"\x03\xe8done"[..]).into_bytes();
+        let verify = WsMessage::Close(Some(CloseFrame {
+            code: CloseCode::Normal,
+            reason: "done".to_string(),
+        }));
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(verify));
+    }
+
+    #[test]
+    fn test_codec_round_trip() {
+        let mut client = WebSocketCodec::new(Role::Client);
+        let mut server = WebSocketCodec::new(Role::Server);
+        let mut buf = BytesMut::new();
+        let message = WsMessage::Text("Hello".to_string());
+        client.encode(message.clone(), &mut buf).unwrap();
+        assert!(buf[1] & 0x80 != 0);
+        assert_eq!(server.decode(&mut buf).unwrap(), Some(message));
+
+        let message = WsMessage::Binary(Bytes::from_static(b"\x00\x01"));
+        server.encode(message.clone(), &mut buf).unwrap();
+        assert_eq!(buf[..], b"\x82\x02\x00\x01"[..]);
+        assert_eq!(client.decode(&mut buf).unwrap(), Some(message));
+    }
+
+    #[test]
+    fn test_codec_round_trip_deflate() {
+        let config = DeflateConfig::default();
+        let mut client =
+            WebSocketCodec::new(Role::Client).with_deflate(&config);
+        let mut server =
+            WebSocketCodec::new(Role::Server).with_deflate(&config);
+        let text = "compress me ".repeat(100);
+        let mut buf = BytesMut::new();
+        for _ in 0..2 {
+            client.encode(WsMessage::Text(text.clone()), &mut buf).unwrap();
+            assert!(buf[0] & 0x40 != 0);
+            assert!(buf.len() < text.len());
+            assert_eq!(
+                server.decode(&mut buf).unwrap(),
+                Some(WsMessage::Text(text.clone()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_codec_decode_rfc_compressed() {
+        // RFC 7692 7.2.3.1, unmasked server to client
+        let config = DeflateConfig::default();
+        let mut codec =
+            WebSocketCodec::new(Role::Client).with_deflate(&config);
+        let mut buf =
+            BytesMut::from(&b"\xc1\x07\xf2\x48\xcd\xc9\xc9\x07\x00"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(WsMessage::Text("Hello".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_codec_encode_invalid_close() {
+        let mut codec = WebSocketCodec::new(Role::Server);
+        let close = CloseFrame {
+            code: CloseCode::Abnormal,
+            reason: String::new(),
+        };
+        assert_eq!(
+            codec.encode(WsMessage::Close(Some(close)), &mut BytesMut::new()),
+            Err(WebSocketError::InvalidCloseCode(1006))
+        );
+    }
+}
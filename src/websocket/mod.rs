@@ -0,0 +1,37 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sha1::{Digest, Sha1};
+
+pub mod deflate;
+pub mod error;
+pub mod frame;
+mod handshake;
+pub mod message;
+
+pub const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+pub const WEBSOCKET_VERSION: &str = "13";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+// base64(SHA-1(key + GUID)), RFC 6455 4.2.2
+pub fn websocket_accept(key: &[u8]) -> String {
+    let mut input = key.trim_ascii().to_vec();
+    input.extend_from_slice(WEBSOCKET_GUID.as_bytes());
+    STANDARD.encode(Sha1::digest(&input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_websocket_accept() {
+        assert_eq!(
+            websocket_accept(b"dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+}